@@ -0,0 +1,338 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use solana_sdk::signature::{write_keypair_file, Keypair, Signer};
+
+/// Characters a base58 address is made of, `0`, `O`, `I` and `l` are left out.
+pub const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Attempts a worker makes before adding them to the shared counter.
+const BATCH_SIZE: u64 = 1_000;
+
+#[derive(Debug, PartialEq)]
+pub enum PatternError {
+    Empty,
+    /// `character` can't appear in a base58 address at all.
+    InvalidCharacter { pattern: String, character: char },
+    /// Prefix and suffix together are longer than an address.
+    TooLong { length: usize },
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::Empty => write!(f, "Give a prefix, a suffix or both"),
+            PatternError::InvalidCharacter { pattern, character } => write!(
+                f,
+                "`{}` can never match, base58 addresses have no `{}` (nor 0, O, I and l)",
+                pattern, character
+            ),
+            PatternError::TooLong { length } => {
+                write!(f, "{} characters don't fit into a 44 character address", length)
+            }
+        }
+    }
+}
+
+impl Error for PatternError {}
+
+/// What the addresses have to start and end with.
+#[derive(Clone, Debug)]
+pub struct VanityPattern {
+    prefix: String,
+    suffix: String,
+    ignore_case: bool,
+}
+
+impl VanityPattern {
+    /// Checks every character up front, so a pattern that can't match fails
+    /// right away instead of grinding forever.
+    pub fn new(prefix: &str, suffix: &str, ignore_case: bool) -> Result<Self, PatternError> {
+        if prefix.is_empty() && suffix.is_empty() {
+            return Err(PatternError::Empty);
+        }
+        if prefix.len() + suffix.len() > 44 {
+            return Err(PatternError::TooLong { length: prefix.len() + suffix.len() });
+        }
+
+        for pattern in [prefix, suffix] {
+            if let Some(character) = pattern.chars().find(|c| matching_characters(*c, ignore_case) == 0) {
+                return Err(PatternError::InvalidCharacter { pattern: pattern.to_string(), character });
+            }
+        }
+
+        Ok(Self {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            ignore_case,
+        })
+    }
+
+    pub fn matches(&self, address: &str) -> bool {
+        if address.len() < self.prefix.len() + self.suffix.len() {
+            return false;
+        }
+
+        // Base58 is plain ASCII, so byte slicing is fine.
+        let start = &address[..self.prefix.len()];
+        let end = &address[address.len() - self.suffix.len()..];
+
+        if self.ignore_case {
+            start.eq_ignore_ascii_case(&self.prefix) && end.eq_ignore_ascii_case(&self.suffix)
+        } else {
+            start == self.prefix && end == self.suffix
+        }
+    }
+
+    /// Keys to try on average before one matches, treating every position of
+    /// the address as uniformly random.
+    pub fn expected_attempts(&self) -> f64 {
+        self.prefix
+            .chars()
+            .chain(self.suffix.chars())
+            .map(|c| BASE58_ALPHABET.len() as f64 / matching_characters(c, self.ignore_case) as f64)
+            .product()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.prefix.is_empty() {
+            parts.push(format!("starting with `{}`", self.prefix));
+        }
+        if !self.suffix.is_empty() {
+            parts.push(format!("ending with `{}`", self.suffix));
+        }
+        if self.ignore_case {
+            parts.push("ignoring case".to_string());
+        }
+
+        parts.join(", ")
+    }
+}
+
+/// How many alphabet characters `c` stands for, 0 when it can't be in an address.
+fn matching_characters(c: char, ignore_case: bool) -> usize {
+    if !ignore_case {
+        return BASE58_ALPHABET.contains(c) as usize;
+    }
+
+    let lower = c.to_ascii_lowercase();
+    let upper = c.to_ascii_uppercase();
+    if lower == upper {
+        return BASE58_ALPHABET.contains(c) as usize;
+    }
+
+    BASE58_ALPHABET.contains(lower) as usize + BASE58_ALPHABET.contains(upper) as usize
+}
+
+pub struct GrindOptions {
+    /// Defaults to every core.
+    pub threads: usize,
+    /// Keypairs to find before stopping.
+    pub count: usize,
+    /// Where the keypairs are written as `<address>.json`, like `solana-keygen grind` does.
+    pub out_dir: PathBuf,
+    pub report_interval: Duration,
+}
+
+impl Default for GrindOptions {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            count: 1,
+            out_dir: PathBuf::from("keypairs"),
+            report_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Generates keypairs on `options.threads` threads until `options.count` of them
+/// match, printing the rate every `options.report_interval`. Every match is saved
+/// to `options.out_dir` as soon as it is found.
+pub fn grind(pattern: &VanityPattern, options: &GrindOptions) -> Result<Vec<Keypair>, Box<dyn Error>> {
+    fs::create_dir_all(&options.out_dir)?;
+
+    let expected_attempts = pattern.expected_attempts();
+    println!(
+        "🔎 Looking for {} address(es) {} on {} threads, about {:.0} attempts each",
+        options.count,
+        pattern.describe(),
+        options.threads,
+        expected_attempts
+    );
+
+    let attempts = AtomicU64::new(0);
+    let done = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    let started = Instant::now();
+    let mut found = Vec::new();
+
+    thread::scope(|scope| -> Result<(), Box<dyn Error>> {
+        for _ in 0..options.threads.max(1) {
+            let sender = sender.clone();
+            let (attempts, done) = (&attempts, &done);
+
+            scope.spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    for _ in 0..BATCH_SIZE {
+                        let keypair = Keypair::new();
+                        if pattern.matches(&keypair.pubkey().to_string()) && sender.send(keypair).is_err() {
+                            return;
+                        }
+                    }
+                    attempts.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+                }
+            });
+        }
+        drop(sender);
+
+        // Stop the workers on the way out, also when writing a keypair fails.
+        let result = collect(&receiver, pattern, options, &attempts, started, &mut found);
+        done.store(true, Ordering::Relaxed);
+        result
+    })?;
+
+    let elapsed = started.elapsed();
+    let total = attempts.load(Ordering::Relaxed);
+    println!(
+        "✅ Found {} in {:.1}s after {} attempts ({:.0} keys/s)",
+        found.len(),
+        elapsed.as_secs_f64(),
+        total,
+        total as f64 / elapsed.as_secs_f64()
+    );
+
+    Ok(found)
+}
+
+/// Where `grind` saves `keypair` inside `out_dir`.
+pub fn keypair_path(out_dir: &Path, keypair: &Keypair) -> PathBuf {
+    out_dir.join(format!("{}.json", keypair.pubkey()))
+}
+
+fn collect(
+    receiver: &mpsc::Receiver<Keypair>,
+    pattern: &VanityPattern,
+    options: &GrindOptions,
+    attempts: &AtomicU64,
+    started: Instant,
+    found: &mut Vec<Keypair>,
+) -> Result<(), Box<dyn Error>> {
+    let expected_attempts = pattern.expected_attempts();
+
+    while found.len() < options.count {
+        match receiver.recv_timeout(options.report_interval) {
+            Ok(keypair) => {
+                let path = keypair_path(&options.out_dir, &keypair);
+                write_keypair_file(&keypair, &path)?;
+                println!("🔑 {} saved to {}", keypair.pubkey(), path.display());
+                found.push(keypair);
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let attempts = attempts.load(Ordering::Relaxed);
+                let rate = attempts as f64 / started.elapsed().as_secs_f64();
+                let remaining = (options.count - found.len()) as f64 * expected_attempts;
+                println!(
+                    "⏳ {} attempts, {:.0} keys/s, about {} to go",
+                    attempts,
+                    rate,
+                    format_duration(remaining / rate)
+                );
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Ok(())
+}
+
+fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "an unknown time".to_string();
+    }
+
+    match seconds as u64 {
+        seconds @ 0..60 => format!("{}s", seconds),
+        seconds @ 60..3_600 => format!("{}m {}s", seconds / 60, seconds % 60),
+        seconds @ 3_600..86_400 => format!("{}h {}m", seconds / 3_600, seconds % 3_600 / 60),
+        seconds => format!("{}d {}h", seconds / 86_400, seconds % 86_400 / 3_600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_patterns_that_can_never_match() {
+        assert_eq!(VanityPattern::new("", "", false).unwrap_err(), PatternError::Empty);
+        assert_eq!(
+            VanityPattern::new(&"a".repeat(40), "abcde", false).unwrap_err(),
+            PatternError::TooLong { length: 45 }
+        );
+
+        for (prefix, character) in [("s0", '0'), ("IO", 'I'), ("abl", 'l'), ("OK", 'O'), ("a b", ' ')] {
+            assert_eq!(
+                VanityPattern::new(prefix, "", false).unwrap_err(),
+                PatternError::InvalidCharacter { pattern: prefix.to_string(), character }
+            );
+        }
+        assert_eq!(
+            VanityPattern::new("ab", "x0", false).unwrap_err(),
+            PatternError::InvalidCharacter { pattern: "x0".to_string(), character: '0' }
+        );
+    }
+
+    #[test]
+    fn ignoring_case_accepts_letters_with_one_valid_case() {
+        // `l` and `O` are not in the alphabet, but `L` and `o` are.
+        for prefix in ["l", "L", "o", "O", "Sol"] {
+            assert!(VanityPattern::new(prefix, "", true).is_ok(), "{}", prefix);
+        }
+
+        // `0` has no other case to fall back to.
+        assert!(VanityPattern::new("0", "", true).is_err());
+    }
+
+    #[test]
+    fn matches_prefix_and_suffix() {
+        let pattern = VanityPattern::new("Ab", "9z", false).unwrap();
+
+        assert!(pattern.matches("AbCdEf9z"));
+        assert!(!pattern.matches("abCdEf9z"));
+        assert!(!pattern.matches("AbCdEf9Z"));
+        assert!(!pattern.matches("Ab9"));
+    }
+
+    #[test]
+    fn matches_ignoring_case() {
+        let pattern = VanityPattern::new("sOl", "L", true).unwrap();
+
+        assert!(pattern.matches("soLanaL"));
+        assert!(pattern.matches("SOLanaL"));
+        assert!(pattern.matches("solanaL"));
+        assert!(!pattern.matches("sunanaL"));
+        assert!(!VanityPattern::new("so", "", false).unwrap().matches("SOLana"));
+    }
+
+    #[test]
+    fn expected_attempts_counts_the_characters_each_position_allows() {
+        let alphabet = BASE58_ALPHABET.len() as f64;
+
+        assert_eq!(VanityPattern::new("A", "", false).unwrap().expected_attempts(), alphabet);
+        assert_eq!(VanityPattern::new("A", "b", false).unwrap().expected_attempts(), alphabet * alphabet);
+        // Both `a` and `A` match.
+        assert_eq!(VanityPattern::new("a", "", true).unwrap().expected_attempts(), alphabet / 2.0);
+        // Only `L` and only `o` exist, ignoring case doesn't help there.
+        assert_eq!(VanityPattern::new("l", "", true).unwrap().expected_attempts(), alphabet);
+        assert_eq!(VanityPattern::new("O", "", true).unwrap().expected_attempts(), alphabet);
+        // Digits have no case.
+        assert_eq!(VanityPattern::new("9", "", true).unwrap().expected_attempts(), alphabet);
+    }
+}
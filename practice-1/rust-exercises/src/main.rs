@@ -1,63 +1,58 @@
-use std::env;
+mod grinder;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
 use solana_client::rpc_client::RpcClient;
-use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::native_token::LAMPORTS_PER_SOL;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signer};
+use solana_utils::cluster::ClusterConfig;
+use solana_utils::load_env_keypair;
 
-fn generate_keypair() -> Keypair {
-    Keypair::new()
-}
+use crate::grinder::{grind, keypair_path, GrindOptions, VanityPattern};
 
-fn generate_keypair_with_prefix(prefix: &str) -> Keypair {
-    loop  {
-        let keypair = Keypair::new();
-        if keypair.pubkey().to_string().starts_with(prefix) {
-            show_keypair(&keypair);
-            return keypair;
-        } else {
-            println!("The public key is: {}", keypair.pubkey());
-        }
-    }
+#[derive(Parser)]
+#[command(about = "Practice 1 exercises, runs the keypair and balance demo without a command")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn generate_keypair_with_postfix(postfix: &str) -> Keypair {
-    loop  {
-        let keypair = Keypair::new();
-        if keypair.pubkey().to_string().ends_with(postfix) {
-            return keypair;
-        } else {
-            println!("The public key is: {}", keypair.pubkey());
-        }
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Generate vanity addresses on every core and save them as Solana CLI keypair files
+    Grind {
+        #[arg(long, default_value = "")]
+        prefix: String,
+        #[arg(long, default_value = "")]
+        suffix: String,
+        /// Match `abc` as well as `ABC` or `aBc`
+        #[arg(long)]
+        ignore_case: bool,
+        /// Keypairs to find
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        /// Defaults to every core
+        #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        threads: Option<usize>,
+        #[arg(long, default_value = "keypairs")]
+        out_dir: PathBuf,
+    },
 }
 
-fn load_keypair () -> Option<Keypair> {
-    let secret_key_str = env::var("SECRET_KEY")
-        .expect("Add SECRET_KEY to .env!");
-
-    let secret_key_str = secret_key_str.trim_matches(|c| c == '[' || c == ']');
-    let secret_key_bytes: Vec<u8> = secret_key_str
-        .split(',')
-        .map(|s| s.trim().parse::<u8>().expect("Can't parse secret_key string"))
-        .collect();
-
-    match Keypair::from_bytes(secret_key_bytes.as_ref()) {
-        Ok(keypair) => {
-            Some(keypair)
-        }
-        Err(_) => {
-            None
-        }
-    }
+fn generate_keypair() -> Keypair {
+    Keypair::new()
 }
 
 fn check_balance(public_key: Pubkey) -> Result<(), Box<dyn std::error::Error>> {
+    let config = ClusterConfig::resolve()?;
     let connection = RpcClient::new_with_commitment(
-        "https://api.devnet.solana.com".to_string(),
-        CommitmentConfig::confirmed(),
+        config.rpc_url.clone(),
+        config.commitment,
     );
-    println!("⚡️ Connected to devnet");
+    println!("⚡️ Connected to {}", config.cluster.as_str());
 
     let balance_in_lamports = connection.get_balance(&public_key)?;
     let balance_in_sol = balance_in_lamports as f64 / LAMPORTS_PER_SOL as f64;
@@ -72,24 +67,57 @@ fn check_balance(public_key: Pubkey) -> Result<(), Box<dyn std::error::Error>> {
 
 fn show_keypair(keypair: &Keypair) {
     println!("The public key is: {}", keypair.pubkey());
-    println!("The secret key is: {:?}", keypair.secret());
+    println!("The secret key is: {:?}", keypair.secret_bytes());
 }
 
-fn main() {
+fn main() -> ExitCode {
     dotenv::dotenv().ok();
 
+    let cli = Cli::parse();
+    if let Some(Command::Grind { prefix, suffix, ignore_case, count, threads, out_dir }) = cli.command {
+        let pattern = match VanityPattern::new(&prefix, &suffix, ignore_case) {
+            Ok(pattern) => pattern,
+            Err(error) => {
+                eprintln!("❌ {}", error);
+                return ExitCode::FAILURE;
+            }
+        };
+        let defaults = GrindOptions::default();
+        let options = GrindOptions {
+            threads: threads.unwrap_or(defaults.threads),
+            count,
+            out_dir,
+            ..defaults
+        };
+
+        return match grind(&pattern, &options) {
+            // The secret keys stay in the files, only say where they are.
+            Ok(keypairs) => {
+                for keypair in &keypairs {
+                    println!("{} {}", keypair.pubkey(), keypair_path(&options.out_dir, keypair).display());
+                }
+                ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("❌ {}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
     println!("-- generate_keypair --");
     let keypair = generate_keypair();
     show_keypair(&keypair);
 
     println!("-- load_keypair --");
-    let keypair = load_keypair().expect("Can't load keypair");
+    let keypair = load_env_keypair().expect("Can't load keypair");
     show_keypair(&keypair);
 
     println!("-- check_balance --");
     check_balance(keypair.pubkey()).expect("Can't check balance");
 
-    // println!("-- generate_keypair_with_prefix --");
-    // let keypair = generate_keypair_with_prefix("serg");
-    // show_keypair(&keypair);
+    println!("-- generate_vanity_keypair --");
+    println!("Run `cargo run -- grind --prefix serg` to grind on every core");
+
+    ExitCode::SUCCESS
 }
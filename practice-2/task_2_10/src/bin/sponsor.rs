@@ -0,0 +1,154 @@
+use std::env;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_utils::cluster::cluster_from_rpc_url;
+use solana_utils::error::SolanaUtilsError;
+use solana_utils::explorer_link::Cluster;
+use solana_utils::get_connection;
+use solana_utils::keypair::KeypairSource;
+use solana_utils::sponsor::{Sponsor, SponsorPolicy};
+use solana_utils::tx_envelope::TxEnvelope;
+
+const SPONSOR_SECRET_KEY_ENV: &str = "SPONSOR_SECRET_KEY";
+const SPONSOR_ADDR_ENV: &str = "SPONSOR_ADDR";
+const DEFAULT_SPONSOR_ADDR: &str = "127.0.0.1:8080";
+
+struct AppState {
+    connection: RpcClient,
+    cluster: Cluster,
+    sponsor: Sponsor,
+}
+
+/// `transaction` is either a base64 or base58 wire format transaction, as web3.js
+/// `serialize({ requireAllSignatures: false })` returns it, or a `TxEnvelope` JSON.
+#[derive(Deserialize)]
+struct SponsorRequest {
+    transaction: String,
+}
+
+struct ApiError(SolanaUtilsError);
+
+impl From<SolanaUtilsError> for ApiError {
+    fn from(error: SolanaUtilsError) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({
+            "error": self.0.to_string(),
+            "logs": self.0.logs(),
+        }));
+
+        match self.0 {
+            SolanaUtilsError::RateLimited { retry_after, .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+                body,
+            )
+                .into_response(),
+            SolanaUtilsError::Policy(_)
+            | SolanaUtilsError::Envelope(_)
+            | SolanaUtilsError::InvalidInput(_) => (StatusCode::BAD_REQUEST, body).into_response(),
+            SolanaUtilsError::Simulation { .. } | SolanaUtilsError::InsufficientFunds { .. } => {
+                (StatusCode::UNPROCESSABLE_ENTITY, body).into_response()
+            }
+            _ => (StatusCode::BAD_GATEWAY, body).into_response(),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let connection = get_connection().expect("Can't resolve cluster config");
+    let cluster = cluster_from_rpc_url(&connection.url());
+
+    let fee_payer = KeypairSource::env(SPONSOR_SECRET_KEY_ENV).load()
+        .expect("Can't load SPONSOR_SECRET_KEY")
+        ;
+    let policy = SponsorPolicy::from_env().expect("Can't load SPONSOR_POLICY");
+    let sponsor = Sponsor::new(fee_payer, policy);
+    println!("Спонсор (платник комісії): {}", sponsor.fee_payer());
+
+    let state = Arc::new(AppState { connection, cluster, sponsor });
+
+    let app = Router::new()
+        .route("/fee-payer", get(show_fee_payer))
+        .route("/check", post(check_transaction))
+        .route("/sponsor", post(sponsor_transaction))
+        .with_state(state);
+
+    let addr = env::var(SPONSOR_ADDR_ENV).unwrap_or_else(|_| DEFAULT_SPONSOR_ADDR.to_string());
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .expect("Can't bind sponsor address")
+        ;
+    println!("Сервіс спонсора слухає на http://{}", addr);
+
+    axum::serve(listener, app).await.expect("Sponsor service stopped");
+}
+
+/// The fee payer clients have to put into their transactions before signing them.
+async fn show_fee_payer(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let policy = state.sponsor.policy();
+
+    Json(json!({
+        "fee_payer": state.sponsor.fee_payer().to_string(),
+        "cluster": state.cluster.as_str(),
+        "allowed_programs": policy.allowed_programs.iter().map(|program| program.to_string()).collect::<Vec<_>>(),
+        "allowed_mints": policy.allowed_mints.iter().map(|mint| mint.to_string()).collect::<Vec<_>>(),
+        "max_fee_payer_lamports": policy.max_fee_payer_lamports,
+    }))
+}
+
+/// Runs the policy checks only, nothing is signed or sent.
+async fn check_transaction(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SponsorRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let envelope = parse_transaction(&state, &request.transaction)?;
+    let lamports = state.sponsor.check(&state.connection, &envelope).await?;
+
+    Ok(Json(json!({ "fee_payer_lamports": lamports })))
+}
+
+async fn sponsor_transaction(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SponsorRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let envelope = parse_transaction(&state, &request.transaction)?;
+    let receipt = state.sponsor.sponsor(&state.connection, envelope).await?;
+    println!("Sponsored transaction: {}", receipt.signature);
+
+    Ok(Json(json!({
+        "signature": receipt.signature.to_string(),
+        "slot": receipt.slot,
+        "explorer_link": receipt.explorer_link,
+    })))
+}
+
+fn parse_transaction(state: &AppState, transaction: &str) -> Result<TxEnvelope, SolanaUtilsError> {
+    if !transaction.trim_start().starts_with('{') {
+        return TxEnvelope::from_wire(transaction, state.cluster.clone());
+    }
+
+    let envelope = TxEnvelope::from_json(transaction)?;
+    if envelope.cluster != state.cluster {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "transaction is for {}, this sponsor runs on {}",
+            envelope.cluster.as_str(),
+            state.cluster.as_str()
+        )));
+    }
+
+    Ok(envelope)
+}
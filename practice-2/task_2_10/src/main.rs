@@ -8,21 +8,27 @@ use solana_sdk::{
     transaction::Transaction,
 };
 use spl_token_2022::instruction::transfer_checked;
-use crate::utils::{create_user_ata, deserialize_tx, get_connection, load_env_keypair, mint_tokens, serialize_tx};
+use crate::utils::{create_user_ata, deserialize_tx, fetch_decimals, get_connection, mint_tokens, serialize_tx, KeypairSource, SigningPolicy, TokenAmount, TokenProgram};
 
 #[tokio::main]
 async fn main() {
-    let amount = 50_000_000;
-    let token_decimals = 2;
     let token_mint_str = "5PcbXNtkdeVcRqZxizFxAhnrQi6j8SEx1uX8oMEimcZy";
     let token_mint = Pubkey::from_str(token_mint_str).expect("Can't get mint pubkey");
 
-    let connection = get_connection();
-    let mint_owner_keypair = load_env_keypair("MINT_OWNER_SECRET_KEY")
+    let connection = get_connection().expect("Can't resolve cluster config");
+    let token_program = TokenProgram::for_mint(&connection, &token_mint)
+        .await
+        .expect("Can't detect token program")
+        ;
+    let amount = TokenAmount::for_mint(&connection, &token_mint, "500000")
+        .await
+        .expect("Can't parse amount")
+        ;
+    let mint_owner_keypair = KeypairSource::env("MINT_OWNER_SECRET_KEY").load()
         .expect("Can't load MINT_OWNER_SECRET_KEY")
         ;
 
-    let sender_keypair = load_env_keypair("SENDER_SECRET_KEY")
+    let sender_keypair = KeypairSource::env("SENDER_SECRET_KEY").load()
         .expect("Can't load SENDER_SECRET_KEY")
         ;
     println!("Відправник (власник токенів): {}", sender_keypair.pubkey());
@@ -31,10 +37,11 @@ async fn main() {
         &connection,
         &sender_keypair,
         &token_mint,
+        token_program,
     ).await.expect("Can't create sender token account");
     println!("Токен-акаунт відправника: {}", sender_token_account);
 
-    let recipient_keypair = load_env_keypair("RECIPIENT_SECRET_KEY")
+    let recipient_keypair = KeypairSource::env("RECIPIENT_SECRET_KEY").load()
         .expect("Can't load RECIPIENT_SECRET_KEY")
         ;
     println!("Отримувач (платник комісії): {}", recipient_keypair.pubkey());
@@ -43,29 +50,32 @@ async fn main() {
         &connection,
         &recipient_keypair,
         &token_mint,
+        token_program,
     ).await.expect("Can't create recipient token account");
     println!("Токен-акаунт отримувача: {}", recipient_token_account);
 
-    mint_tokens(
+    let minted = mint_tokens(
         &connection,
         &mint_owner_keypair,
         token_mint_str,
         sender_token_account.to_string().as_str(),
         amount,
     ).await.expect("Can't mint tokens for sender");
+    println!("Mint Token Transaction Signature: {}", minted.receipt.signature);
 
     let transfer_ix = transfer_checked(
-        &spl_token_2022::id(),
+        &token_program.id(),
         &sender_token_account,
         &token_mint,
         &recipient_token_account,
         &sender_keypair.pubkey(),
         &[&sender_keypair.pubkey()],
-        amount,
-        token_decimals,
+        amount.raw(),
+        amount.decimals(),
     ).expect("Can't create transfer instruction");
 
-    let recent_blockhash = connection.get_latest_blockhash()
+    let (recent_blockhash, last_valid_block_height) = connection
+        .get_latest_blockhash_with_commitment(connection.commitment())
         .await
         .expect("Can't get latest blockhash")
         ;
@@ -78,16 +88,28 @@ async fn main() {
     let mut tx = Transaction::new_unsigned(message);
     tx.partial_sign(&[&sender_keypair], recent_blockhash);
 
-    let base64_encoded = serialize_tx(tx).expect("Can't serialize tx");
+    let envelope_json = serialize_tx(&connection, tx, last_valid_block_height).expect("Can't serialize tx");
+
+    let mut envelope = deserialize_tx(&envelope_json).expect("Can't deserialize tx");
 
-    let mut recovered_tx: Transaction = deserialize_tx(base64_encoded).expect("Can't deserialize tx");
+    // The recipient only pays the fee, so check what the sender is asking them to sign.
+    let decimals = fetch_decimals(&connection, &envelope).await.expect("Can't fetch mint decimals");
+    println!("Транзакція на підпис:");
+    for line in envelope.describe(&decimals) {
+        println!("   {}", line);
+    }
 
-    recovered_tx.partial_sign(&[&recipient_keypair], recent_blockhash);
+    // Refuses to sign when the transaction breaks the recipient's policy, e.g. spends too much of its SOL.
+    let policy = SigningPolicy::from_env().expect("Can't load SIGNING_POLICY");
+    if let Err(err) = policy.sign(&mut envelope, &recipient_keypair) {
+        println!("Отримувач не підписує транзакцію: {}", err);
+        return;
+    }
 
-    match connection.send_and_confirm_transaction(&recovered_tx).await {
-        Ok(signature) => {
+    match envelope.submit(&connection).await {
+        Ok(receipt) => {
             println!("Трансфер успішно виконано!");
-            println!("Signature: {}", signature);
+            println!("Signature: {}", receipt.signature);
         }
         Err(err) => {
             println!("Помилка відправлення транзакції: {}", err);
@@ -1,7 +1,13 @@
 use std::time::Duration;
 use log::info;
-use tokio::time::sleep;
-use crate::utils::{create_tx, deserialize_tx, serialize_tx, sign_and_send_tx};
+use solana_sdk::signature::Signer;
+use solana_utils::get_connection;
+use solana_utils::inspector::fetch_decimals;
+use solana_utils::keypair::KeypairSource;
+use solana_utils::scheduler::{run_until_idle, JobQueue, JobStatus, Trigger};
+use solana_utils::signing_policy::SigningPolicy;
+use solana_utils::tx_envelope::BlockhashSource;
+use crate::utils::create_tx;
 
 mod utils;
 
@@ -11,14 +17,49 @@ async fn main() {
         .format_timestamp_millis()
         .init();
 
-    let (tx, nonce) = create_tx().await;
-    let serialized_tx = serialize_tx(tx).unwrap();
-    info!("Mint tx was created");
+    // Kept on disk, a restarted runner picks the job up where this one stopped.
+    let queue = JobQueue::open("scheduled").unwrap();
 
-    sleep(Duration::from_secs(150)).await;
+    let (envelope, mut nonces) = create_tx(&queue).await;
+    let connection = get_connection().unwrap();
 
-    let tx = deserialize_tx(serialized_tx).unwrap();
+    // SIGNER2 co-signs whenever the job comes due, so it reviews the transaction now.
+    let decimals = fetch_decimals(&connection, &envelope).await.unwrap();
+    for line in envelope.describe(&decimals) {
+        info!("{}", line);
+    }
 
-    let signature = sign_and_send_tx(tx, nonce).await.unwrap();
-    info!("Mint tx Signature: {}", signature);
-}
\ No newline at end of file
+    let signer2 = KeypairSource::env("SIGNER2_SECRET_KEY").load().expect("Can't load SIGNER2_SECRET_KEY");
+    let policy = SigningPolicy::from_env().expect("Can't load SIGNING_POLICY");
+    let verdict = policy.evaluate(&envelope, &signer2.pubkey());
+    if !verdict.is_accepted() {
+        info!("SIGNER2 won't co-sign, the mint tx is not scheduled: {}", verdict);
+        if let BlockhashSource::Nonce { account, .. } = envelope.blockhash_source {
+            nonces.release(&account).expect("Can't release nonce account");
+        }
+        return;
+    }
+
+    let job = queue.schedule(envelope, Trigger::after(Duration::from_secs(150))).unwrap();
+    info!("Mint tx was scheduled as {}", job.id);
+
+    // SIGNER2 signs through the policy again once the job comes due. Errors with single
+    // jobs are retried by the runner, this only fails when the queue can't be read.
+    let jobs = match run_until_idle(&connection, &queue, &[&signer2], &policy, Duration::from_secs(5)).await {
+        Ok(jobs) => jobs,
+        Err(error) => {
+            info!("Scheduler stopped, run again to resume: {}", error);
+            return;
+        }
+    };
+
+    for job in jobs {
+        match job.status {
+            JobStatus::Confirmed { signature, .. } => info!("Mint tx Signature: {}", signature),
+            status => match job.last_error {
+                Some(error) => info!("Job {}: {}, retrying after: {}", job.id, status.as_str(), error),
+                None => info!("Job {}: {}", job.id, status.as_str()),
+            },
+        }
+    }
+}
@@ -1,110 +1,40 @@
-use std::env;
-use anyhow::anyhow;
-use base64::Engine;
-use base64::engine::general_purpose;
-use log::{error, info};
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_program::{
-    program_pack::Pack,
-    pubkey::Pubkey,
-    system_instruction,
-};
-use solana_program::hash::Hash;
-use solana_program::nonce::State;
-use solana_program::nonce::state::Versions;
-use solana_program::system_instruction::advance_nonce_account;
+use log::info;
+use solana_program::program_pack::Pack;
+#[allow(deprecated)]
+use solana_program::system_instruction::{self, advance_nonce_account};
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
     signature::{Keypair, Signer},
     transaction::Transaction,
 };
-use solana_sdk::signature::Signature;
+use solana_utils::get_connection;
+use solana_utils::amount::TokenAmount;
+use solana_utils::cluster::cluster_from_rpc_url;
+use solana_utils::keypair::KeypairSource;
+use solana_utils::nonce::NoncePool;
+use solana_utils::scheduler::JobQueue;
+use solana_utils::token_program::TokenProgram;
+use solana_utils::tx_envelope::{BlockhashSource, TxEnvelope};
 use spl_associated_token_account::{
     get_associated_token_address_with_program_id,
     instruction::{create_associated_token_account_idempotent}
 };
 use spl_token_2022::{
-    id,
     instruction::{initialize_mint2, initialize_multisig, mint_to},
     state::Multisig
 };
 
-pub fn load_env_keypair(key: &str) -> Option<Keypair> {
-    dotenv::dotenv().ok();
-
-    let secret_key_str = env::var(key)
-        .expect("Add {key} to .env!");
-
-    let secret_key_str = secret_key_str.trim_matches(|c| c == '[' || c == ']');
-    let secret_key_bytes: Vec<u8> = secret_key_str
-        .split(',')
-        .map(|s| s.trim().parse::<u8>().expect("Can't parse {key} string"))
-        .collect();
-
-    match Keypair::from_bytes(secret_key_bytes.as_ref()) {
-        Ok(keypair) => {
-            Some(keypair)
-        }
-        Err(error) => {
-            error!("{}", error);
-            None
-        }
-    }
-}
-
-pub fn get_connection() -> RpcClient {
-    RpcClient::new_with_commitment(
-        "https://api.devnet.solana.com".to_string(),
-        CommitmentConfig::confirmed(),
-    )
-}
-
-async fn get_nonce(connection: &RpcClient, nonce_account_pubkey: Pubkey) -> anyhow::Result<Hash>{
-    let nonce_account_data = connection.get_account(&nonce_account_pubkey)
-        .await?;
-
-    match bincode::deserialize::<Versions>(&nonce_account_data.data) {
-        Ok(Versions::Current(state)) => match *state {
-            State::Initialized(ref data) => Ok(data.blockhash()),
-            _ => Err(anyhow!("Nonce can't be initialized")),
-        },
-        _ => Err(anyhow!("Can't deserialize")),
-    }
-}
+/// Nonces handed out by earlier runs, kept next to the `scheduled` queue.
+const NONCE_POOL_PATH: &str = "nonce-pool.json";
 
-async fn setup_nonce_account(connection: &RpcClient, payer: &Keypair) -> anyhow::Result<Keypair> {
-    let nonce_account = Keypair::new();
-    let nonce_authority = payer.pubkey();
+/// Also returns the nonce pool, the nonce has to go back to it when the tx is not scheduled.
+pub async fn create_tx(queue: &JobQueue) -> (TxEnvelope, NoncePool) {
+    let connection = get_connection().expect("Can't resolve cluster config");
 
-    let min_balance = connection.get_minimum_balance_for_rent_exemption(80)
-        .await?;
-
-    let create_nonce_account_ix = system_instruction::create_nonce_account(
-        &payer.pubkey(),
-        &nonce_account.pubkey(),
-        &nonce_authority,                 // Nonce authority
-        min_balance,
-    );
-
-    let tx = Transaction::new_signed_with_payer(
-        &create_nonce_account_ix,
-        Some(&payer.pubkey()),
-        &[payer, &nonce_account],
-        connection.get_latest_blockhash().await.unwrap(),
-    );
+    let token_program = TokenProgram::from_env().expect("Can't parse TOKEN_PROGRAM");
 
-    connection.send_and_confirm_transaction(&tx).await
-        .expect("Can't create nonce account");
-
-    Ok(nonce_account)
-}
-
-pub async fn create_tx() -> (Transaction, Hash) {
-    let connection = get_connection();
-
-    let signer1 = load_env_keypair("SIGNER1_SECRET_KEY").expect("Can't load SIGNER1_SECRET_KEY");
-    let signer2 = load_env_keypair("SIGNER2_SECRET_KEY").expect("Can't load SIGNER2_SECRET_KEY");
-    let signer3 = load_env_keypair("SIGNER3_SECRET_KEY").expect("Can't load SIGNER3_SECRET_KEY");
+    let signer1 = KeypairSource::env("SIGNER1_SECRET_KEY").load().expect("Can't load SIGNER1_SECRET_KEY");
+    let signer2 = KeypairSource::env("SIGNER2_SECRET_KEY").load().expect("Can't load SIGNER2_SECRET_KEY");
+    let signer3 = KeypairSource::env("SIGNER3_SECRET_KEY").load().expect("Can't load SIGNER3_SECRET_KEY");
 
     let multisig = Keypair::new();
     let multisig_pubkey = multisig.pubkey();
@@ -119,7 +49,7 @@ pub async fn create_tx() -> (Transaction, Hash) {
         &multisig_pubkey,
         multisig_rent,
         multisig_space as u64,
-        &id(),
+        &token_program.id(),
     );
 
     let multisig_signers = [
@@ -129,7 +59,7 @@ pub async fn create_tx() -> (Transaction, Hash) {
     ];
 
     let init_multisig_ix = initialize_multisig(
-        &id(),
+        &token_program.id(),
         &multisig_pubkey,
         &multisig_signers,
         2, // m of n (2 of 3)
@@ -146,12 +76,12 @@ pub async fn create_tx() -> (Transaction, Hash) {
         &mint.pubkey(),
         mint_rent,
         spl_token_2022::state::Mint::get_packed_len() as u64,
-        &id(),
+        &token_program.id(),
     );
 
     let mint_decimals = 6;
     let init_mint_ix = initialize_mint2(
-        &id(),
+        &token_program.id(),
         &mint.pubkey(),
         &multisig_pubkey,
         None,
@@ -162,13 +92,13 @@ pub async fn create_tx() -> (Transaction, Hash) {
     let ata = get_associated_token_address_with_program_id(
         &recipient.pubkey(),
         &mint.pubkey(),
-        &id(),
+        &token_program.id(),
     );
     let create_ata_ix = create_associated_token_account_idempotent(
         &signer1.pubkey(),
         &recipient.pubkey(),
         &mint.pubkey(),
-        &id(),
+        &token_program.id(),
     );
 
     let mut tx1 = Transaction::new_with_payer(
@@ -191,20 +121,37 @@ pub async fn create_tx() -> (Transaction, Hash) {
     info!("ATA: {}", ata);
 
     let mint_to_ix = mint_to(
-        &id(),
+        &token_program.id(),
         &mint.pubkey(),
         &ata,
         &multisig_pubkey,
         &[&signer1.pubkey(), &signer2.pubkey()],
-        100 * 10u64.pow(mint_decimals as u32),
+        TokenAmount::parse("100", mint_decimals).unwrap().raw(),
     ).unwrap();
 
-    let nonce_account = setup_nonce_account(&connection, &signer1).await.expect("Can't create nonce account");
-    let nonce = get_nonce(&connection, nonce_account.pubkey())
+    // Reuses a nonce account of signer1 left over from an earlier run when there is one,
+    // but never the nonce of a job still waiting in the queue.
+    let mut nonces = NoncePool::open(signer1.pubkey(), NONCE_POOL_PATH).expect("Can't open nonce pool");
+    for job in queue.jobs().expect("Can't read scheduled jobs") {
+        let Some(nonce_account) = job.nonce_account() else {
+            continue;
+        };
+        if job.status.is_finished() {
+            nonces.release(&nonce_account)
+        } else {
+            nonces.reserve([nonce_account])
+        }
+        .expect("Can't update nonce pool");
+    }
+
+    let nonce = nonces
+        .acquire(&connection, &signer1)
         .await
-        .expect("Can't get nonce");
+        .expect("Can't acquire nonce account");
+    info!("Nonce account : {}", nonce.address);
+
     let advance_nonce_ix = advance_nonce_account(
-        &nonce_account.pubkey(),
+        &nonce.address,
         &signer1.pubkey(),
     );
 
@@ -212,29 +159,13 @@ pub async fn create_tx() -> (Transaction, Hash) {
         &[advance_nonce_ix, mint_to_ix],
         Some(&signer1.pubkey()),
     );
-    tx2.partial_sign(&[&signer1], nonce);
+    tx2.partial_sign(&[&signer1], nonce.blockhash);
 
-    (tx2, nonce)
-}
-
-pub fn serialize_tx(tx: Transaction) -> anyhow::Result<String> {
-    let serialized = bincode::serialize(&tx)?;
-    Ok(general_purpose::STANDARD.encode(&serialized))
-}
+    let envelope = TxEnvelope::new(
+        tx2,
+        cluster_from_rpc_url(&connection.url()),
+        BlockhashSource::Nonce { account: nonce.address, authority: nonce.authority },
+    );
 
-pub fn deserialize_tx(base64_encoded: String) -> anyhow::Result<Transaction> {
-    let received_bytes = general_purpose::STANDARD.decode(&base64_encoded)?;
-    Ok(bincode::deserialize(received_bytes.as_ref())?)
+    (envelope, nonces)
 }
-
-pub async fn sign_and_send_tx(mut tx: Transaction, nonce:Hash) -> anyhow::Result<Signature> {
-    let connection = get_connection();
-
-    let signer2 = load_env_keypair("SIGNER2_SECRET_KEY").expect("Can't load SIGNER2_SECRET_KEY");
-
-    tx.partial_sign(&[&signer2], nonce);
-
-    let signature = connection.send_and_confirm_transaction(&tx).await?;
-
-    Ok(signature)
-}
\ No newline at end of file
@@ -0,0 +1,183 @@
+use std::fmt;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::Mint;
+
+use crate::error::Result;
+
+pub const SOL_DECIMALS: u8 = 9;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum AmountError {
+    #[error("`{0}` is not a valid amount")]
+    Invalid(String),
+    #[error("`{value}` has more than {decimals} decimal places")]
+    TooPrecise { value: String, decimals: u8 },
+    #[error("`{value}` does not fit into u64 with {decimals} decimals")]
+    Overflow { value: String, decimals: u8 },
+    #[error("amount has {actual} decimals but {expected} are required")]
+    DecimalsMismatch { expected: u8, actual: u8 },
+}
+
+/// An amount in base units together with the decimals it is scaled by,
+/// so `12.345` of a 6-decimals mint is stored as `12_345_000`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TokenAmount {
+    raw: u64,
+    decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn from_raw(raw: u64, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    pub fn from_lamports(lamports: u64) -> Self {
+        Self::from_raw(lamports, SOL_DECIMALS)
+    }
+
+    /// Parses a human readable amount (`"12"`, `"12.345"`, `".5"`) without going through `f64`.
+    pub fn parse(value: &str, decimals: u8) -> std::result::Result<Self, AmountError> {
+        let trimmed = value.trim();
+        let (whole, fraction) = trimmed.split_once('.').unwrap_or((trimmed, ""));
+
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !is_digits(whole) || !is_digits(fraction) {
+            return Err(AmountError::Invalid(value.to_string()));
+        }
+
+        if fraction.len() > decimals as usize {
+            return Err(AmountError::TooPrecise { value: value.to_string(), decimals });
+        }
+
+        let overflow = || AmountError::Overflow { value: value.to_string(), decimals };
+
+        let scale = 10u64.checked_pow(decimals as u32).ok_or_else(overflow)?;
+        let whole = match whole {
+            "" => 0,
+            digits => digits.parse::<u64>().map_err(|_| overflow())?,
+        };
+        let fraction = match fraction {
+            "" => 0,
+            digits => format!("{:0<width$}", digits, width = decimals as usize)
+                .parse::<u64>()
+                .map_err(|_| overflow())?,
+        };
+
+        let raw = whole
+            .checked_mul(scale)
+            .and_then(|raw| raw.checked_add(fraction))
+            .ok_or_else(overflow)?;
+
+        Ok(Self { raw, decimals })
+    }
+
+    pub fn sol(value: &str) -> std::result::Result<Self, AmountError> {
+        Self::parse(value, SOL_DECIMALS)
+    }
+
+    /// Parses `value` with the decimals of `mint` as stored on-chain.
+    pub async fn for_mint(connection: &RpcClient, mint: &Pubkey, value: &str) -> Result<Self> {
+        let decimals = fetch_mint_decimals(connection, mint).await?;
+
+        Ok(Self::parse(value, decimals)?)
+    }
+
+    pub fn raw(&self) -> u64 {
+        self.raw
+    }
+
+    pub fn decimals(&self) -> u8 {
+        self.decimals
+    }
+
+    pub fn ensure_decimals(&self, expected: u8) -> std::result::Result<(), AmountError> {
+        if self.decimals != expected {
+            return Err(AmountError::DecimalsMismatch { expected, actual: self.decimals });
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(scale) = 10u64.checked_pow(self.decimals as u32) else {
+            return write!(f, "{}e-{}", self.raw, self.decimals);
+        };
+
+        let whole = self.raw / scale;
+        let fraction = self.raw % scale;
+
+        if fraction == 0 {
+            return write!(f, "{}", whole);
+        }
+
+        let fraction = format!("{:0>width$}", fraction, width = self.decimals as usize);
+        write!(f, "{}.{}", whole, fraction.trim_end_matches('0'))
+    }
+}
+
+pub async fn fetch_mint_decimals(connection: &RpcClient, mint: &Pubkey) -> Result<u8> {
+    let account = connection.get_account(mint).await?;
+    let mint_state = StateWithExtensions::<Mint>::unpack(&account.data)?;
+
+    Ok(mint_state.base.decimals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_whole_and_fractional_amounts() {
+        assert_eq!(TokenAmount::parse("12", 6), Ok(TokenAmount::from_raw(12_000_000, 6)));
+        assert_eq!(TokenAmount::parse("12.345", 6), Ok(TokenAmount::from_raw(12_345_000, 6)));
+        assert_eq!(TokenAmount::parse(".5", 6), Ok(TokenAmount::from_raw(500_000, 6)));
+        assert_eq!(TokenAmount::parse("7.", 2), Ok(TokenAmount::from_raw(700, 2)));
+        assert_eq!(TokenAmount::parse(" 0.000000001 ", 9), Ok(TokenAmount::from_raw(1, 9)));
+        assert_eq!(TokenAmount::parse("42", 0), Ok(TokenAmount::from_raw(42, 0)));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        for value in ["", ".", "abc", "-1", "1.2.3", "1e5", "1,5", "+1"] {
+            assert_eq!(TokenAmount::parse(value, 6), Err(AmountError::Invalid(value.to_string())), "{}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_more_decimal_places_than_the_mint_has() {
+        assert_eq!(
+            TokenAmount::parse("1.2345", 3),
+            Err(AmountError::TooPrecise { value: "1.2345".to_string(), decimals: 3 })
+        );
+        assert_eq!(
+            TokenAmount::parse("1.5", 0),
+            Err(AmountError::TooPrecise { value: "1.5".to_string(), decimals: 0 })
+        );
+    }
+
+    #[test]
+    fn rejects_amounts_that_overflow_u64() {
+        assert_eq!(TokenAmount::parse("18446744073709551615", 0), Ok(TokenAmount::from_raw(u64::MAX, 0)));
+
+        for (value, decimals) in [("18446744073709551616", 0), ("18446744074", 9), ("1", 20)] {
+            assert_eq!(
+                TokenAmount::parse(value, decimals),
+                Err(AmountError::Overflow { value: value.to_string(), decimals }),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for value in ["0", "12", "12.345", "0.5", "0.000000001"] {
+            assert_eq!(TokenAmount::parse(value, 9).unwrap().to_string(), value);
+        }
+    }
+}
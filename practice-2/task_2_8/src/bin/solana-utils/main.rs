@@ -0,0 +1,1605 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use mpl_token_metadata::types::{Collection, Creator, DataV2};
+use serde_json::{json, Value};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token_2022::instruction::AuthorityType;
+use spl_token_2022::state::AccountState;
+
+use solana_utils::amount::{fetch_mint_decimals, TokenAmount};
+use solana_utils::cluster::{parse_cluster, ClusterConfig};
+use solana_utils::distribution::{distribute_tokens, read_recipients_csv, DistributionMode};
+use solana_utils::error::{parse_pubkey, Result, SolanaUtilsError};
+use solana_utils::inspector::{fetch_decimals, inspect, InstructionSummary, MintDecimals};
+use solana_utils::keypair::KeypairSource;
+use solana_utils::lookup_table::{create_address_lookup_table, extend_address_lookup_table};
+use solana_utils::metadata::MetadataArgs;
+use solana_utils::mint_builder::{EmbeddedMetadata, MintBuilder};
+use solana_utils::multisig::{create_multisig, execute_multisig_action, fetch_multisig, MultisigAction};
+use solana_utils::nonce::{
+    advance_nonce, authorize_nonce, cancel_nonce_transaction, close_nonce, create_nonce, fetch_nonce,
+    fetch_nonces_by_authority, nonce_transaction_status, upgrade_nonce, withdraw_from_nonce, NonceInfo,
+    NonceTransactionStatus,
+};
+use solana_utils::offline_signing::{merge_files, OfflineTransaction};
+use solana_utils::queries::fetch_token_account;
+use solana_utils::results::TransactionReceipt;
+use solana_utils::scheduler::{run_due, run_until_idle, JobQueue, JobStatus, ScheduledJob, Trigger};
+use solana_utils::signing_policy::{PolicyVerdict, SigningPolicy};
+use solana_utils::simulation::SimulationReport;
+use solana_utils::token_program::TokenProgram;
+use solana_utils::tx_envelope::TxEnvelope;
+use solana_utils::tx_sender::TxSender;
+use solana_utils::{
+    create_token_account, create_token_metadata, mint_tokens, send_sol_with_memo, simulate_create_token_account,
+    simulate_create_token_metadata, simulate_mint_tokens, simulate_send_sol_with_memo, simulate_update_token_metadata,
+    update_token_metadata,
+};
+
+#[derive(Parser)]
+#[command(name = "solana-utils", version, about = "Send SOL and manage SPL Token and Token-2022 mints")]
+struct Cli {
+    /// Cluster moniker (devnet, mainnet-beta, localnet, ...) or RPC URL.
+    /// Defaults to SOLANA_CLUSTER / SOLANA_RPC_URL, then the Solana CLI config.
+    #[arg(long, short = 'u', global = true)]
+    cluster: Option<String>,
+
+    /// Signer and fee payer: a keypair file, `env:NAME` or `base58:KEY`.
+    #[arg(long, short = 'k', global = true, default_value = "env:SECRET_KEY")]
+    keypair: KeypairSource,
+
+    #[arg(long, short = 'o', global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Simulate the transaction and report logs, compute units, fee and balance changes
+    /// instead of sending it
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send SOL with a memo attached
+    SendSol {
+        #[arg(long)]
+        to: String,
+        /// Amount in SOL, e.g. 0.01
+        #[arg(long)]
+        amount: String,
+        #[arg(long, default_value = "Hello from Solana Training!")]
+        memo: String,
+    },
+    /// Create a new mint with the keypair as mint and freeze authority
+    CreateMint {
+        #[arg(long, default_value_t = 2)]
+        decimals: u8,
+        /// `token-2022` or `token` (classic SPL Token, no extensions)
+        #[arg(long, default_value = "token-2022")]
+        token_program: TokenProgram,
+        /// Transfer fee in basis points
+        #[arg(long, requires = "max_fee")]
+        transfer_fee_bps: Option<u16>,
+        /// Maximum transfer fee in tokens, e.g. 5.5
+        #[arg(long, requires = "transfer_fee_bps")]
+        max_fee: Option<String>,
+        /// Embed token metadata in the mint
+        #[arg(long, requires_all = ["symbol", "uri"])]
+        name: Option<String>,
+        #[arg(long, requires = "name")]
+        symbol: Option<String>,
+        #[arg(long, requires = "name")]
+        uri: Option<String>,
+        /// Additional embedded metadata field as key=value, repeatable
+        #[arg(long = "field", requires = "name", value_parser = parse_key_value)]
+        fields: Vec<(String, String)>,
+        /// Interest rate in basis points per year
+        #[arg(long, allow_negative_numbers = true)]
+        interest_rate: Option<i16>,
+        #[arg(long)]
+        non_transferable: bool,
+        #[arg(long)]
+        permanent_delegate: Option<String>,
+        #[arg(long)]
+        close_authority: Option<String>,
+        /// New token accounts start frozen
+        #[arg(long)]
+        default_frozen: bool,
+    },
+    /// Create the associated token account of `owner` for `mint`
+    CreateAta {
+        #[arg(long)]
+        mint: String,
+        #[arg(long)]
+        owner: String,
+    },
+    /// Mint tokens into a token account
+    MintTo {
+        #[arg(long)]
+        mint: String,
+        /// Destination token account
+        #[arg(long)]
+        to: String,
+        /// Amount in tokens, scaled by the mint's decimals, e.g. 12.345
+        #[arg(long)]
+        amount: String,
+    },
+    /// Create metadata for a mint
+    CreateMetadata {
+        #[arg(long)]
+        mint: String,
+        #[command(flatten)]
+        metadata: MetadataOptions,
+    },
+    /// Replace the metadata of a mint
+    UpdateMetadata {
+        #[arg(long)]
+        mint: String,
+        #[command(flatten)]
+        metadata: MetadataOptions,
+    },
+    /// Create an address lookup table for v0 transactions
+    CreateLookupTable {
+        /// Address to store in the table, repeatable
+        #[arg(long = "address")]
+        addresses: Vec<String>,
+    },
+    /// Append addresses to an address lookup table
+    ExtendLookupTable {
+        #[arg(long)]
+        table: String,
+        /// Address to append, repeatable
+        #[arg(long = "address", required = true)]
+        addresses: Vec<String>,
+    },
+    /// Pay every recipient of a CSV file (`owner,amount` per line), resuming an interrupted run
+    Distribute {
+        #[arg(long)]
+        mint: String,
+        #[arg(long)]
+        csv: PathBuf,
+        /// `mint` new tokens as mint authority, or `transfer` them from the keypair's token account
+        #[arg(long, value_enum, default_value_t = Mode::Transfer)]
+        mode: Mode,
+        /// Progress file, defaults to the CSV path with `.state.json` appended
+        #[arg(long)]
+        state: Option<PathBuf>,
+    },
+    /// Create, inspect and act through SPL Token multisig authorities
+    Multisig {
+        #[command(subcommand)]
+        command: MultisigCommand,
+    },
+    /// Manage durable nonce accounts
+    Nonce {
+        #[command(subcommand)]
+        command: NonceCommand,
+    },
+    /// Decode a transaction file for review before signing it
+    Inspect {
+        /// Envelope or `offline` export
+        #[arg(long)]
+        file: PathBuf,
+        /// Also check the transaction against this signing policy (YAML)
+        #[arg(long, requires = "signer")]
+        policy: Option<PathBuf>,
+        /// Signer the policy is checked for
+        #[arg(long)]
+        signer: Option<String>,
+    },
+    /// Review, sign, merge and submit multisig transactions exported with `multisig ... --export`
+    Offline {
+        #[command(subcommand)]
+        command: OfflineCommand,
+    },
+    /// Queue durable nonce transactions to send later and run the queue
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum NonceCommand {
+    /// Create a nonce account, paid for by the keypair
+    Create {
+        /// Defaults to the keypair
+        #[arg(long)]
+        authority: Option<String>,
+    },
+    /// List the nonce accounts of an authority
+    List {
+        /// Defaults to the keypair
+        #[arg(long)]
+        authority: Option<String>,
+    },
+    /// Show the current value and authority of a nonce account
+    Show {
+        #[arg(long)]
+        nonce: String,
+    },
+    /// Advance the nonce, invalidating transactions signed against its current value
+    Advance {
+        #[arg(long)]
+        nonce: String,
+    },
+    /// Upgrade a legacy nonce so it can back durable transactions
+    Upgrade {
+        #[arg(long)]
+        nonce: String,
+    },
+    /// Hand the nonce to another authority
+    Authorize {
+        #[arg(long)]
+        nonce: String,
+        #[arg(long)]
+        new_authority: String,
+    },
+    /// Withdraw SOL above the rent-exempt minimum
+    Withdraw {
+        #[arg(long)]
+        nonce: String,
+        #[arg(long)]
+        to: String,
+        /// Amount in SOL, e.g. 0.01
+        #[arg(long)]
+        amount: String,
+    },
+    /// Withdraw everything and delete the nonce account
+    Close {
+        #[arg(long)]
+        nonce: String,
+        /// Defaults to the keypair
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Tell whether a pre-signed nonce transaction file can still land
+    Status {
+        /// Envelope or `offline` export
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// Revoke a pre-signed nonce transaction file by advancing its nonce as the authority
+    Cancel {
+        /// Envelope or `offline` export
+        #[arg(long)]
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum OfflineCommand {
+    /// Decode the transaction and show who still has to sign
+    Inspect {
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// Add the keypair's signature, no RPC access needed
+    Sign {
+        #[arg(long)]
+        file: PathBuf,
+        /// Defaults to overwriting `--file`
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Refuse to sign unless the transaction passes this signing policy (YAML)
+        #[arg(long)]
+        policy: Option<PathBuf>,
+    },
+    /// Combine the signatures of several signed copies into one file
+    Merge {
+        /// Signed copy, repeatable
+        #[arg(long = "file", required = true)]
+        files: Vec<PathBuf>,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Merge the signed copies and send the transaction once it is fully signed
+    Submit {
+        /// Signed copy, repeatable
+        #[arg(long = "file", required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ScheduleCommand {
+    /// Queue a durable nonce transaction file (envelope or `offline` export),
+    /// due right away unless a trigger is given
+    Add {
+        #[command(flatten)]
+        queue: QueueArgs,
+        #[arg(long)]
+        file: PathBuf,
+        /// Not before this Unix time, in seconds
+        #[arg(long, group = "trigger")]
+        at: Option<u64>,
+        /// Not before this many seconds from now
+        #[arg(long, group = "trigger")]
+        delay: Option<u64>,
+        /// Once the cluster reached this slot
+        #[arg(long, group = "trigger")]
+        slot: Option<u64>,
+        /// Once this account holds `--min-lamports`
+        #[arg(long, group = "trigger", requires = "min_lamports")]
+        balance_of: Option<String>,
+        #[arg(long, requires = "balance_of")]
+        min_lamports: Option<u64>,
+    },
+    /// Show every queued transaction and what became of it
+    List {
+        #[command(flatten)]
+        queue: QueueArgs,
+    },
+    /// Send due transactions and record the outcomes until the queue is done
+    Run {
+        #[command(flatten)]
+        queue: QueueArgs,
+        /// Keypair adding its signature to due transactions that still miss it, repeatable
+        #[arg(long = "signer-keypair")]
+        signer_keypairs: Vec<KeypairSource>,
+        /// Make a single pass instead of waiting for every job to finish
+        #[arg(long)]
+        once: bool,
+        /// Seconds between passes
+        #[arg(long, default_value_t = 10)]
+        interval: u64,
+        /// Signing policy (YAML) the signer keypairs sign under, defaults to `SIGNING_POLICY` or the built-in one
+        #[arg(long)]
+        policy: Option<PathBuf>,
+    },
+    /// Keep the runner from sending a pending transaction
+    Cancel {
+        #[command(flatten)]
+        queue: QueueArgs,
+        #[arg(long)]
+        id: String,
+        /// Also advance the nonce as its authority (the keypair), so no copy can land either
+        #[arg(long)]
+        invalidate: bool,
+    },
+}
+
+#[derive(Args)]
+struct QueueArgs {
+    /// Directory holding the queued transactions
+    #[arg(long, default_value = "scheduled")]
+    queue: PathBuf,
+}
+
+#[derive(Subcommand)]
+enum MultisigCommand {
+    /// Create an M-of-N multisig, paid for by the keypair
+    Create {
+        /// Member pubkey, repeatable
+        #[arg(long = "signer", required = true)]
+        signers: Vec<String>,
+        /// Signatures required
+        #[arg(long)]
+        threshold: u8,
+        /// `token-2022` or `token`, must match the mints the multisig will control
+        #[arg(long, default_value = "token-2022")]
+        token_program: TokenProgram,
+    },
+    /// Show the threshold and members of a multisig
+    Show {
+        #[arg(long)]
+        multisig: String,
+    },
+    /// Mint tokens with the multisig as mint authority
+    MintTo {
+        #[command(flatten)]
+        authority: MultisigSigners,
+        #[arg(long)]
+        mint: String,
+        /// Destination token account
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: String,
+    },
+    /// Transfer tokens out of a token account owned by the multisig
+    Transfer {
+        #[command(flatten)]
+        authority: MultisigSigners,
+        /// Source token account
+        #[arg(long)]
+        from: String,
+        /// Destination token account
+        #[arg(long)]
+        to: String,
+        #[arg(long)]
+        amount: String,
+    },
+    /// Hand an authority held by the multisig to another key
+    SetAuthority {
+        #[command(flatten)]
+        authority: MultisigSigners,
+        /// Mint or token account
+        #[arg(long)]
+        account: String,
+        #[arg(long, value_enum)]
+        authority_type: AuthorityKind,
+        /// Leave out to remove the authority for good
+        #[arg(long)]
+        new_authority: Option<String>,
+    },
+    /// Freeze a token account with the multisig as freeze authority
+    Freeze {
+        #[command(flatten)]
+        authority: MultisigSigners,
+        #[arg(long)]
+        account: String,
+    },
+    /// Thaw a token account with the multisig as freeze authority
+    Thaw {
+        #[command(flatten)]
+        authority: MultisigSigners,
+        #[arg(long)]
+        account: String,
+    },
+    /// Burn tokens from a token account owned by the multisig
+    Burn {
+        #[command(flatten)]
+        authority: MultisigSigners,
+        #[arg(long)]
+        account: String,
+        #[arg(long)]
+        amount: String,
+    },
+}
+
+#[derive(Args)]
+struct MultisigSigners {
+    #[arg(long)]
+    multisig: String,
+    /// Member keypair signing for the multisig (file, `env:NAME` or `base58:KEY`), repeatable
+    #[arg(long = "signer-keypair", required_unless_present = "export", conflicts_with = "export")]
+    signer_keypairs: Vec<KeypairSource>,
+    /// Member pubkey that will sign the exported transaction offline, repeatable
+    #[arg(long = "signer", requires = "export")]
+    signers: Vec<String>,
+    /// Write the transaction to this file, signed only by the fee payer, instead of sending it
+    #[arg(long)]
+    export: Option<PathBuf>,
+    /// Durable nonce account, keeps the exported transaction valid until it is submitted
+    #[arg(long, requires = "export")]
+    nonce_account: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum AuthorityKind {
+    MintTokens,
+    FreezeAccount,
+    AccountOwner,
+    CloseAccount,
+    CloseMint,
+    TransferFeeConfig,
+    WithheldWithdraw,
+    InterestRate,
+    PermanentDelegate,
+    MetadataPointer,
+}
+
+impl AuthorityKind {
+    fn authority_type(self) -> AuthorityType {
+        match self {
+            AuthorityKind::MintTokens => AuthorityType::MintTokens,
+            AuthorityKind::FreezeAccount => AuthorityType::FreezeAccount,
+            AuthorityKind::AccountOwner => AuthorityType::AccountOwner,
+            AuthorityKind::CloseAccount => AuthorityType::CloseAccount,
+            AuthorityKind::CloseMint => AuthorityType::CloseMint,
+            AuthorityKind::TransferFeeConfig => AuthorityType::TransferFeeConfig,
+            AuthorityKind::WithheldWithdraw => AuthorityType::WithheldWithdraw,
+            AuthorityKind::InterestRate => AuthorityType::InterestRate,
+            AuthorityKind::PermanentDelegate => AuthorityType::PermanentDelegate,
+            AuthorityKind::MetadataPointer => AuthorityType::MetadataPointer,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Mode {
+    Mint,
+    Transfer,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum MetadataStandard {
+    /// A Metaplex metadata account
+    Metaplex,
+    /// The Token-2022 token metadata extension inside the mint
+    TokenExtension,
+}
+
+#[derive(Args)]
+struct MetadataOptions {
+    #[arg(long, value_enum, default_value_t = MetadataStandard::Metaplex)]
+    standard: MetadataStandard,
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    symbol: String,
+    #[arg(long)]
+    uri: String,
+    /// Metaplex only
+    #[arg(long, default_value_t = 0)]
+    seller_fee_basis_points: u16,
+    /// Metaplex creator as address:share, repeatable
+    #[arg(long = "creator", value_parser = parse_key_value)]
+    creators: Vec<(String, String)>,
+    /// Metaplex collection mint
+    #[arg(long)]
+    collection: Option<String>,
+    /// Token extension field as key=value, repeatable
+    #[arg(long = "field", value_parser = parse_key_value)]
+    fields: Vec<(String, String)>,
+}
+
+impl MetadataOptions {
+    fn to_args(&self) -> Result<MetadataArgs> {
+        match self.standard {
+            MetadataStandard::Metaplex => {
+                let creators = self
+                    .creators
+                    .iter()
+                    .map(|(address, share)| {
+                        Ok(Creator {
+                            address: parse_pubkey(address)?,
+                            verified: false,
+                            share: share.parse().map_err(|_| {
+                                SolanaUtilsError::InvalidInput(format!("invalid creator share `{}`", share))
+                            })?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                let collection = match &self.collection {
+                    Some(collection) => Some(Collection { verified: false, key: parse_pubkey(collection)? }),
+                    None => None,
+                };
+
+                Ok(MetadataArgs::Metaplex(DataV2 {
+                    name: self.name.clone(),
+                    symbol: self.symbol.clone(),
+                    uri: self.uri.clone(),
+                    seller_fee_basis_points: self.seller_fee_basis_points,
+                    creators: (!creators.is_empty()).then_some(creators),
+                    collection,
+                    uses: None,
+                }))
+            }
+            MetadataStandard::TokenExtension => Ok(MetadataArgs::TokenExtension(EmbeddedMetadata {
+                name: self.name.clone(),
+                symbol: self.symbol.clone(),
+                uri: self.uri.clone(),
+                additional_metadata: self.fields.clone(),
+                update_authority: None,
+            })),
+        }
+    }
+}
+
+/// Human readable lines plus the same data as JSON, printed according to `--output`.
+struct Report {
+    lines: Vec<String>,
+    json: Value,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli).await {
+        Ok(report) => {
+            match cli.output {
+                OutputFormat::Text => report.lines.iter().for_each(|line| println!("{}", line)),
+                OutputFormat::Json => println!("{:#}", report.json),
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            match cli.output {
+                OutputFormat::Text => {
+                    eprintln!("❌ {}", error);
+                    error.logs().iter().for_each(|log| eprintln!("   {}", log));
+                }
+                OutputFormat::Json => {
+                    println!("{:#}", json!({ "error": error.to_string(), "logs": error.logs() }));
+                }
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: &Cli) -> Result<Report> {
+    let connection = connection(cli)?;
+
+    // Most of these send several dependent transactions, which can't be simulated up front.
+    if cli.dry_run
+        && matches!(
+            cli.command,
+            Command::CreateLookupTable { .. }
+                | Command::ExtendLookupTable { .. }
+                | Command::Distribute { .. }
+                | Command::Multisig { .. }
+                | Command::Nonce { .. }
+                | Command::Offline { .. }
+                | Command::Schedule { .. }
+        )
+    {
+        return Err(SolanaUtilsError::InvalidInput(
+            "--dry-run is not supported for this command".to_string(),
+        ));
+    }
+
+    if let Command::Inspect { file, policy, signer } = &cli.command {
+        return inspect_file(&connection, file, policy.as_deref(), signer.as_deref()).await;
+    }
+
+    // Reviewing and merging don't need the keypair, signing loads it itself.
+    if let Command::Offline { command } = &cli.command {
+        return run_offline(cli, &connection, command).await;
+    }
+
+    // The runner signs with the `--signer-keypair`s, only `cancel --invalidate` needs the keypair.
+    if let Command::Schedule { command } = &cli.command {
+        return run_schedule(cli, &connection, command).await;
+    }
+
+    let signer = cli.keypair.load()?;
+
+    match &cli.command {
+        Command::SendSol { to, amount, memo } => {
+            let amount = TokenAmount::sol(amount)?;
+            if cli.dry_run {
+                return Ok(simulation_report(
+                    &simulate_send_sol_with_memo(&connection, &signer, to, amount, memo).await?,
+                ));
+            }
+            let transfer = send_sol_with_memo(&connection, &signer, to, amount, memo).await?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("💸 Sent {} SOL to {}", amount, transfer.recipient),
+                    format!("📝 memo is: {}", memo),
+                    format!("✅ Transaction confirmed, signature: {} !", transfer.receipt.signature),
+                    transfer.receipt.explorer_link.clone(),
+                ],
+                json: json!({
+                    "recipient": transfer.recipient.to_string(),
+                    "lamports": transfer.lamports,
+                    "memo": memo,
+                    "receipt": receipt_json(&transfer.receipt),
+                }),
+            })
+        }
+        Command::CreateMint {
+            decimals,
+            token_program,
+            transfer_fee_bps,
+            max_fee,
+            name,
+            symbol,
+            uri,
+            fields,
+            interest_rate,
+            non_transferable,
+            permanent_delegate,
+            close_authority,
+            default_frozen,
+        } => {
+            let mut builder = MintBuilder::new(signer.pubkey(), *decimals)
+                .token_program(*token_program)
+                .freeze_authority(signer.pubkey());
+
+            if let (Some(basis_points), Some(max_fee)) = (transfer_fee_bps, max_fee) {
+                builder = builder.transfer_fee(*basis_points, TokenAmount::parse(max_fee, *decimals)?.raw());
+            }
+            if let (Some(name), Some(symbol), Some(uri)) = (name, symbol, uri) {
+                builder = builder.metadata(name, symbol, uri);
+                for (key, value) in fields {
+                    builder = builder.additional_metadata(key, value);
+                }
+            }
+            if let Some(rate) = interest_rate {
+                builder = builder.interest_bearing(*rate);
+            }
+            if *non_transferable {
+                builder = builder.non_transferable();
+            }
+            if let Some(delegate) = permanent_delegate {
+                builder = builder.permanent_delegate(parse_pubkey(delegate)?);
+            }
+            if let Some(close_authority) = close_authority {
+                builder = builder.close_authority(parse_pubkey(close_authority)?);
+            }
+            if *default_frozen {
+                builder = builder.default_account_state(AccountState::Frozen);
+            }
+
+            if cli.dry_run {
+                return Ok(simulation_report(&builder.simulate(&connection, &signer).await?));
+            }
+
+            let created_mint = builder.create(&connection, &signer).await?;
+            let extensions: Vec<String> =
+                created_mint.extensions.iter().map(|extension| format!("{:?}", extension)).collect();
+
+            let mut lines = vec![format!("Mint Address: {}", created_mint.mint)];
+            if !extensions.is_empty() {
+                lines.push(format!("Extensions: {}", extensions.join(", ")));
+            }
+            lines.push(format!("Transaction Signature: {}", created_mint.receipt.signature));
+            lines.push(format!("✅ Token Mint: {}", created_mint.explorer_link));
+
+            Ok(Report {
+                lines,
+                json: json!({
+                    "mint": created_mint.mint.to_string(),
+                    "mint_authority": created_mint.mint_authority.to_string(),
+                    "decimals": created_mint.decimals,
+                    "token_program": created_mint.token_program.to_string(),
+                    "extensions": extensions,
+                    "explorer_link": created_mint.explorer_link,
+                    "receipt": receipt_json(&created_mint.receipt),
+                }),
+            })
+        }
+        Command::CreateAta { mint, owner } => {
+            if cli.dry_run {
+                return Ok(simulation_report(
+                    &simulate_create_token_account(&connection, &signer, mint, owner).await?,
+                ));
+            }
+
+            let created_account = create_token_account(&connection, &signer, mint, owner).await?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("Token Account: {}", created_account.token_account),
+                    format!("Transaction Signature: {}", created_account.receipt.signature),
+                    format!("✅ Created token account: {}", created_account.explorer_link),
+                ],
+                json: json!({
+                    "token_account": created_account.token_account.to_string(),
+                    "owner": created_account.owner.to_string(),
+                    "mint": created_account.mint.to_string(),
+                    "token_program": created_account.token_program.to_string(),
+                    "explorer_link": created_account.explorer_link,
+                    "receipt": receipt_json(&created_account.receipt),
+                }),
+            })
+        }
+        Command::MintTo { mint, to, amount } => {
+            let amount = TokenAmount::for_mint(&connection, &parse_pubkey(mint)?, amount).await?;
+            if cli.dry_run {
+                return Ok(simulation_report(&simulate_mint_tokens(&connection, &signer, mint, to, amount).await?));
+            }
+
+            let minted = mint_tokens(&connection, &signer, mint, to, amount).await?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("Minted {} tokens to {}", minted.amount, minted.destination),
+                    format!("Transaction Signature: {}", minted.receipt.signature),
+                    "✅ Success!".to_string(),
+                    format!("Mint Token Transaction: {}", minted.receipt.explorer_link),
+                ],
+                json: json!({
+                    "mint": minted.mint.to_string(),
+                    "destination": minted.destination.to_string(),
+                    "amount": minted.amount.to_string(),
+                    "raw_amount": minted.amount.raw(),
+                    "decimals": minted.amount.decimals(),
+                    "receipt": receipt_json(&minted.receipt),
+                }),
+            })
+        }
+        Command::CreateMetadata { mint, metadata } => {
+            if cli.dry_run {
+                return Ok(simulation_report(
+                    &simulate_create_token_metadata(&connection, &signer, mint, metadata.to_args()?).await?,
+                ));
+            }
+
+            let created_metadata = create_token_metadata(&connection, &signer, mint, metadata.to_args()?).await?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("Metadata Account: {}", created_metadata.metadata),
+                    format!("Transaction signature: {}", created_metadata.receipt.signature),
+                    format!("✅ Look at the token mint again: {}", created_metadata.explorer_link),
+                ],
+                json: json!({
+                    "mint": created_metadata.mint.to_string(),
+                    "metadata": created_metadata.metadata.to_string(),
+                    "explorer_link": created_metadata.explorer_link,
+                    "receipt": receipt_json(&created_metadata.receipt),
+                }),
+            })
+        }
+        Command::UpdateMetadata { mint, metadata } => {
+            if cli.dry_run {
+                return Ok(simulation_report(
+                    &simulate_update_token_metadata(&connection, &signer, mint, metadata.to_args()?).await?,
+                ));
+            }
+
+            let updated_metadata = update_token_metadata(&connection, &signer, mint, metadata.to_args()?).await?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("Metadata Account: {}", updated_metadata.metadata),
+                    format!("Transaction signature: {}", updated_metadata.receipt.signature),
+                    format!("✅ Metadata updated: {}", updated_metadata.receipt.explorer_link),
+                ],
+                json: json!({
+                    "mint": updated_metadata.mint.to_string(),
+                    "metadata": updated_metadata.metadata.to_string(),
+                    "receipt": receipt_json(&updated_metadata.receipt),
+                }),
+            })
+        }
+        Command::CreateLookupTable { addresses } => {
+            let addresses = parse_pubkeys(addresses)?;
+            let created_table = create_address_lookup_table(&connection, &signer, &addresses).await?;
+
+            let mut lines = vec![
+                format!("Lookup Table: {}", created_table.address),
+                format!("Addresses: {}", created_table.addresses.len()),
+            ];
+            lines.extend(
+                created_table
+                    .receipts
+                    .iter()
+                    .map(|receipt| format!("Transaction Signature: {}", receipt.signature)),
+            );
+            lines.push(format!("✅ Lookup table: {}", created_table.explorer_link));
+
+            Ok(Report {
+                lines,
+                json: json!({
+                    "lookup_table": created_table.address.to_string(),
+                    "authority": created_table.authority.to_string(),
+                    "addresses": created_table.addresses.iter().map(|address| address.to_string()).collect::<Vec<_>>(),
+                    "explorer_link": created_table.explorer_link,
+                    "receipts": created_table.receipts.iter().map(receipt_json).collect::<Vec<_>>(),
+                }),
+            })
+        }
+        Command::ExtendLookupTable { table, addresses } => {
+            let table = parse_pubkey(table)?;
+            let addresses = parse_pubkeys(addresses)?;
+            let receipts = extend_address_lookup_table(&connection, &signer, &table, &addresses).await?;
+
+            let mut lines: Vec<String> = receipts
+                .iter()
+                .map(|receipt| format!("Transaction Signature: {}", receipt.signature))
+                .collect();
+            if receipts.is_empty() {
+                lines.push(format!("✅ Lookup table {} already holds every address", table));
+            } else {
+                lines.push(format!("✅ Extended lookup table {}", table));
+            }
+
+            Ok(Report {
+                lines,
+                json: json!({
+                    "lookup_table": table.to_string(),
+                    "receipts": receipts.iter().map(receipt_json).collect::<Vec<_>>(),
+                }),
+            })
+        }
+        Command::Distribute { mint, csv, mode, state } => {
+            let mint = parse_pubkey(mint)?;
+            let recipients = read_recipients_csv(csv, fetch_mint_decimals(&connection, &mint).await?)?;
+            let mode = match mode {
+                Mode::Mint => DistributionMode::Mint,
+                Mode::Transfer => DistributionMode::Transfer,
+            };
+            let state = state.clone().unwrap_or_else(|| {
+                let mut state = csv.clone().into_os_string();
+                state.push(".state.json");
+                PathBuf::from(state)
+            });
+
+            let report = distribute_tokens(
+                &connection,
+                &signer,
+                &mint,
+                mode,
+                &recipients,
+                &state,
+                &TxSender::from_env()?,
+            ).await?;
+
+            let mut lines: Vec<String> = report
+                .distributed
+                .iter()
+                .map(|distributed| {
+                    format!(
+                        "✅ {} {} -> {} ({})",
+                        distributed.row, distributed.amount, distributed.owner, distributed.signature
+                    )
+                })
+                .collect();
+            lines.push(format!(
+                "💸 Paid {} recipients in {} transactions, {} were already paid",
+                report.distributed.len() - report.resumed,
+                report.transactions,
+                report.resumed
+            ));
+
+            Ok(Report {
+                lines,
+                json: json!({
+                    "mint": report.mint.to_string(),
+                    "mode": report.mode.as_str(),
+                    "transactions": report.transactions,
+                    "resumed": report.resumed,
+                    "state": state.display().to_string(),
+                    "distributed": report.distributed.iter().map(|distributed| json!({
+                        "row": distributed.row,
+                        "owner": distributed.owner.to_string(),
+                        "token_account": distributed.token_account.to_string(),
+                        "amount": distributed.amount.to_string(),
+                        "raw_amount": distributed.amount.raw(),
+                        "signature": distributed.signature.to_string(),
+                    })).collect::<Vec<_>>(),
+                }),
+            })
+        }
+        Command::Multisig { command } => run_multisig(&connection, &signer, command).await,
+        Command::Nonce { command } => run_nonce(&connection, &signer, command).await,
+        Command::Inspect { .. } => unreachable!("inspect is handled before loading the keypair"),
+        Command::Offline { .. } => unreachable!("offline commands are handled before loading the keypair"),
+        Command::Schedule { .. } => unreachable!("schedule commands are handled before loading the keypair"),
+    }
+}
+
+async fn run_multisig(connection: &RpcClient, payer: &Keypair, command: &MultisigCommand) -> Result<Report> {
+    let (authority, action) = match command {
+        MultisigCommand::Create { signers, threshold, token_program } => {
+            let signers = parse_pubkeys(signers)?;
+            let created = create_multisig(connection, payer, &signers, *threshold, *token_program).await?;
+
+            return Ok(Report {
+                lines: vec![
+                    format!("Multisig: {} ({} of {})", created.address, created.threshold, created.signers.len()),
+                    format!("Transaction Signature: {}", created.receipt.signature),
+                    format!("✅ Multisig: {}", created.explorer_link),
+                ],
+                json: json!({
+                    "multisig": created.address.to_string(),
+                    "threshold": created.threshold,
+                    "signers": created.signers.iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+                    "token_program": created.token_program.to_string(),
+                    "explorer_link": created.explorer_link,
+                    "receipt": receipt_json(&created.receipt),
+                }),
+            });
+        }
+        MultisigCommand::Show { multisig } => {
+            let info = fetch_multisig(connection, &parse_pubkey(multisig)?).await?;
+
+            let mut lines = vec![
+                format!("Multisig: {}", info.address),
+                format!("Token program: {}", info.token_program),
+                format!("Threshold: {} of {}", info.threshold, info.signers.len()),
+            ];
+            lines.extend(info.signers.iter().map(|signer| format!("   {}", signer)));
+
+            return Ok(Report {
+                lines,
+                json: json!({
+                    "multisig": info.address.to_string(),
+                    "token_program": info.token_program.to_string(),
+                    "threshold": info.threshold,
+                    "signers": info.signers.iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+                }),
+            });
+        }
+        MultisigCommand::MintTo { authority, mint, to, amount } => {
+            let mint = parse_pubkey(mint)?;
+            let action = MultisigAction::MintTo {
+                mint,
+                destination: parse_pubkey(to)?,
+                amount: TokenAmount::for_mint(connection, &mint, amount).await?,
+            };
+            (authority, action)
+        }
+        MultisigCommand::Transfer { authority, from, to, amount } => {
+            let source = parse_pubkey(from)?;
+            let mint = fetch_token_account(connection, &source).await?.mint;
+            let action = MultisigAction::Transfer {
+                source,
+                mint,
+                destination: parse_pubkey(to)?,
+                amount: TokenAmount::for_mint(connection, &mint, amount).await?,
+            };
+            (authority, action)
+        }
+        MultisigCommand::SetAuthority { authority, account, authority_type, new_authority } => {
+            let action = MultisigAction::SetAuthority {
+                account: parse_pubkey(account)?,
+                authority_type: authority_type.authority_type(),
+                new_authority: new_authority.as_deref().map(parse_pubkey).transpose()?,
+            };
+            (authority, action)
+        }
+        MultisigCommand::Freeze { authority, account } => {
+            let account = parse_pubkey(account)?;
+            let mint = fetch_token_account(connection, &account).await?.mint;
+            (authority, MultisigAction::Freeze { account, mint })
+        }
+        MultisigCommand::Thaw { authority, account } => {
+            let account = parse_pubkey(account)?;
+            let mint = fetch_token_account(connection, &account).await?.mint;
+            (authority, MultisigAction::Thaw { account, mint })
+        }
+        MultisigCommand::Burn { authority, account, amount } => {
+            let account = parse_pubkey(account)?;
+            let mint = fetch_token_account(connection, &account).await?.mint;
+            let action = MultisigAction::Burn {
+                account,
+                mint,
+                amount: TokenAmount::for_mint(connection, &mint, amount).await?,
+            };
+            (authority, action)
+        }
+    };
+
+    let multisig = parse_pubkey(&authority.multisig)?;
+
+    if let Some(export) = &authority.export {
+        let nonce_account = authority.nonce_account.as_deref().map(parse_pubkey).transpose()?;
+        let mut offline = OfflineTransaction::build(
+            connection,
+            &payer.pubkey(),
+            &multisig,
+            &parse_pubkeys(&authority.signers)?,
+            &action,
+            nonce_account.as_ref(),
+        ).await?;
+        offline.sign(payer)?;
+        offline.save(export)?;
+
+        let decimals = fetch_decimals(connection, &offline.envelope).await?;
+
+        return Ok(offline_report(&offline, &decimals, format!("📝 Exported to {}", export.display())));
+    }
+
+    let signers = authority
+        .signer_keypairs
+        .iter()
+        .map(|source| source.load())
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let signer_refs: Vec<&Keypair> = signers.iter().collect();
+
+    let receipt = execute_multisig_action(connection, payer, &multisig, &signer_refs, &action).await?;
+
+    Ok(Report {
+        lines: vec![
+            format!("Multisig {} signed by {} members", multisig, signers.len()),
+            format!("Transaction Signature: {}", receipt.signature),
+            format!("✅ Success: {}", receipt.explorer_link),
+        ],
+        json: json!({
+            "multisig": multisig.to_string(),
+            "signers": signers.iter().map(|signer| signer.pubkey().to_string()).collect::<Vec<_>>(),
+            "receipt": receipt_json(&receipt),
+        }),
+    })
+}
+
+async fn run_nonce(connection: &RpcClient, signer: &Keypair, command: &NonceCommand) -> Result<Report> {
+    let receipt = match command {
+        NonceCommand::Create { authority } => {
+            let authority = match authority {
+                Some(authority) => parse_pubkey(authority)?,
+                None => signer.pubkey(),
+            };
+            let created = create_nonce(connection, signer, &authority).await?;
+
+            return Ok(Report {
+                lines: vec![
+                    format!("Nonce Account: {}", created.address),
+                    format!("Authority: {}", created.authority),
+                    format!("Transaction Signature: {}", created.receipt.signature),
+                    format!("✅ Nonce account: {}", created.explorer_link),
+                ],
+                json: json!({
+                    "nonce": created.address.to_string(),
+                    "authority": created.authority.to_string(),
+                    "explorer_link": created.explorer_link,
+                    "receipt": receipt_json(&created.receipt),
+                }),
+            });
+        }
+        NonceCommand::List { authority } => {
+            let authority = match authority {
+                Some(authority) => parse_pubkey(authority)?,
+                None => signer.pubkey(),
+            };
+            let nonces = fetch_nonces_by_authority(connection, &authority).await?;
+
+            let mut lines = vec![format!("{} nonce accounts of {}", nonces.len(), authority)];
+            lines.extend(nonces.iter().map(|nonce| {
+                format!(
+                    "   {} {} {} SOL{}",
+                    nonce.address,
+                    nonce.blockhash,
+                    TokenAmount::from_lamports(nonce.lamports),
+                    if nonce.legacy { " (legacy)" } else { "" }
+                )
+            }));
+
+            return Ok(Report {
+                lines,
+                json: json!({
+                    "authority": authority.to_string(),
+                    "nonces": nonces.iter().map(nonce_json).collect::<Vec<_>>(),
+                }),
+            });
+        }
+        NonceCommand::Show { nonce } => {
+            let nonce = fetch_nonce(connection, &parse_pubkey(nonce)?).await?;
+
+            return Ok(Report {
+                lines: vec![
+                    format!("Nonce Account: {}", nonce.address),
+                    format!("Authority: {}", nonce.authority),
+                    format!("Nonce: {}", nonce.blockhash),
+                    format!("Balance: {} SOL", TokenAmount::from_lamports(nonce.lamports)),
+                    format!("Version: {}", if nonce.legacy { "legacy" } else { "current" }),
+                ],
+                json: nonce_json(&nonce),
+            });
+        }
+        NonceCommand::Status { file } => {
+            let envelope = load_envelope(file)?;
+            let status = nonce_transaction_status(connection, &envelope).await?;
+
+            let line = match &status {
+                NonceTransactionStatus::Executable { nonce } => {
+                    format!("✅ Executable, nonce {} still holds {}", nonce.address, nonce.blockhash)
+                }
+                NonceTransactionStatus::Executed { signature, slot } => {
+                    format!("✅ Already landed as {} in slot {}", signature, slot)
+                }
+                NonceTransactionStatus::Invalidated { nonce } => {
+                    format!("🚫 Invalidated, nonce {} moved on to {}", nonce.address, nonce.blockhash)
+                }
+                NonceTransactionStatus::NonceClosed => "🚫 Invalidated, the nonce account was closed".to_string(),
+            };
+            let status_name = match &status {
+                NonceTransactionStatus::Executable { .. } => "executable",
+                NonceTransactionStatus::Executed { .. } => "executed",
+                NonceTransactionStatus::Invalidated { .. } => "invalidated",
+                NonceTransactionStatus::NonceClosed => "nonce_closed",
+            };
+
+            return Ok(Report {
+                lines: vec![
+                    format!("Blockhash: {}", envelope.blockhash()),
+                    format!("Missing signatures: {}", envelope.missing_signers().len()),
+                    line,
+                ],
+                json: json!({
+                    "status": status_name,
+                    "executable": status.is_executable(),
+                    "blockhash": envelope.blockhash().to_string(),
+                    "missing_signers": envelope.missing_signers().iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+                }),
+            });
+        }
+        NonceCommand::Cancel { file } => cancel_nonce_transaction(connection, signer, &load_envelope(file)?).await?,
+        NonceCommand::Advance { nonce } => advance_nonce(connection, signer, &parse_pubkey(nonce)?).await?,
+        NonceCommand::Upgrade { nonce } => upgrade_nonce(connection, signer, &parse_pubkey(nonce)?).await?,
+        NonceCommand::Authorize { nonce, new_authority } => {
+            authorize_nonce(connection, signer, &parse_pubkey(nonce)?, &parse_pubkey(new_authority)?).await?
+        }
+        NonceCommand::Withdraw { nonce, to, amount } => {
+            let amount = TokenAmount::sol(amount)?;
+            withdraw_from_nonce(connection, signer, &parse_pubkey(nonce)?, &parse_pubkey(to)?, amount.raw()).await?
+        }
+        NonceCommand::Close { nonce, to } => {
+            let recipient = match to {
+                Some(to) => parse_pubkey(to)?,
+                None => signer.pubkey(),
+            };
+            close_nonce(connection, signer, &parse_pubkey(nonce)?, &recipient).await?
+        }
+    };
+
+    Ok(Report {
+        lines: vec![
+            format!("Transaction Signature: {}", receipt.signature),
+            format!("✅ Success: {}", receipt.explorer_link),
+        ],
+        json: json!({ "receipt": receipt_json(&receipt) }),
+    })
+}
+
+fn nonce_json(nonce: &NonceInfo) -> Value {
+    json!({
+        "nonce_account": nonce.address.to_string(),
+        "authority": nonce.authority.to_string(),
+        "nonce": nonce.blockhash.to_string(),
+        "lamports": nonce.lamports,
+        "lamports_per_signature": nonce.lamports_per_signature,
+        "legacy": nonce.legacy,
+    })
+}
+
+async fn run_offline(cli: &Cli, connection: &RpcClient, command: &OfflineCommand) -> Result<Report> {
+    match command {
+        OfflineCommand::Inspect { file } => {
+            let mut offline = OfflineTransaction::load(file)?;
+            offline.verify(connection).await?;
+            let decimals = fetch_decimals(connection, &offline.envelope).await?;
+            let status = if offline.is_complete() {
+                "✅ Fully signed, ready to submit".to_string()
+            } else {
+                format!("⏳ {} signatures missing", offline.missing_signers().len())
+            };
+
+            Ok(offline_report(&offline, &decimals, status))
+        }
+        OfflineCommand::Sign { file, out, policy } => {
+            let signer = cli.keypair.load()?;
+            let mut offline = OfflineTransaction::load(file)?;
+            // Without RPC access the multisig stays marked unverified, a mismatch still stops here.
+            match offline.verify(connection).await {
+                Ok(()) | Err(SolanaUtilsError::Rpc(_)) => {}
+                Err(error) => return Err(error),
+            }
+            match policy {
+                Some(policy) => SigningPolicy::from_file(policy)?.sign(&mut offline.envelope, &signer)?,
+                None => offline.sign(&signer)?,
+            }
+
+            let out = out.as_ref().unwrap_or(file);
+            offline.save(out)?;
+
+            // Signing works without RPC access, run `offline inspect` first to see scaled amounts.
+            Ok(offline_report(&offline, &MintDecimals::new(), format!("✍️ Signed as {}, saved to {}", signer.pubkey(), out.display())))
+        }
+        OfflineCommand::Merge { files, out } => {
+            let offline = merge_files(files)?;
+            offline.save(out)?;
+
+            Ok(offline_report(&offline, &MintDecimals::new(), format!("📝 Merged {} files into {}", files.len(), out.display())))
+        }
+        OfflineCommand::Submit { files } => {
+            let mut offline = merge_files(files)?;
+            offline.verify(connection).await?;
+            let receipt = offline.submit(connection).await?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("Transaction Signature: {}", receipt.signature),
+                    format!("✅ Submitted: {}", receipt.explorer_link),
+                ],
+                json: json!({
+                    "multisig": offline.multisig.address.to_string(),
+                    "receipt": receipt_json(&receipt),
+                }),
+            })
+        }
+    }
+}
+
+fn offline_report(offline: &OfflineTransaction, decimals: &MintDecimals, status: String) -> Report {
+    let mut lines = offline.describe(decimals);
+    lines.push(status.clone());
+
+    Report {
+        lines,
+        json: json!({
+            "multisig": offline.multisig.address.to_string(),
+            "threshold": offline.multisig.threshold,
+            "nonce_account": offline.nonce_account().map(|nonce_account| nonce_account.to_string()),
+            "required_signers": offline.required_signers().iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+            "missing_signers": offline.missing_signers().iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+            "complete": offline.is_complete(),
+            "cluster": offline.envelope.cluster.as_str(),
+            // Same bytes web3.js `VersionedTransaction.deserialize` and `sendTransaction` take.
+            "transaction": offline.envelope.to_wire().ok(),
+            "sign_only": offline.envelope.sign_only_output(),
+            "instructions": instructions_json(&inspect(&offline.envelope, decimals)),
+            "status": status,
+        }),
+    }
+}
+
+async fn run_schedule(cli: &Cli, connection: &RpcClient, command: &ScheduleCommand) -> Result<Report> {
+    match command {
+        ScheduleCommand::Add { queue, file, at, delay, slot, balance_of, min_lamports } => {
+            let trigger = match (at, delay, slot, balance_of, min_lamports) {
+                (Some(at), ..) => Trigger::NotBefore(*at),
+                (_, Some(delay), ..) => Trigger::after(Duration::from_secs(*delay)),
+                (_, _, Some(slot), ..) => Trigger::Slot(*slot),
+                (_, _, _, Some(account), Some(lamports)) => Trigger::Balance {
+                    account: parse_pubkey(account)?,
+                    lamports: *lamports,
+                },
+                _ => Trigger::NotBefore(0),
+            };
+
+            let envelope = load_envelope(file)?;
+            let job = JobQueue::open(&queue.queue)?.schedule(envelope, trigger)?;
+
+            Ok(Report {
+                lines: vec![
+                    format!("Job: {}", job.id),
+                    format!("Trigger: {}", job.trigger.describe()),
+                    format!("⏳ Waiting for {} more signatures", job.envelope.missing_signers().len()),
+                    format!("✅ Scheduled in {}", queue.queue.display()),
+                ],
+                json: job_json(&job),
+            })
+        }
+        ScheduleCommand::List { queue } => {
+            let job_queue = JobQueue::open(&queue.queue)?;
+            let jobs = job_queue.jobs()?;
+            let invalid = job_queue.invalid_jobs()?;
+
+            let mut lines = vec![format!("Jobs in {}: {}", queue.queue.display(), jobs.len())];
+            lines.extend(jobs.iter().map(job_line));
+            lines.extend(invalid.iter().map(|error| format!("⚠️ Skipped {}", error)));
+
+            Ok(Report {
+                lines,
+                json: json!({
+                    "jobs": jobs.iter().map(job_json).collect::<Vec<_>>(),
+                    "invalid": invalid.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                }),
+            })
+        }
+        ScheduleCommand::Run { queue, signer_keypairs, once, interval, policy } => {
+            let queue = JobQueue::open(&queue.queue)?;
+            let policy = match policy {
+                Some(policy) => SigningPolicy::from_file(policy)?,
+                None => SigningPolicy::from_env()?,
+            };
+            let signers = signer_keypairs
+                .iter()
+                .map(|source| source.load())
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let signer_refs: Vec<&Keypair> = signers.iter().collect();
+
+            let changed = if *once {
+                run_due(connection, &queue, &signer_refs, &policy).await?
+            } else {
+                run_until_idle(connection, &queue, &signer_refs, &policy, Duration::from_secs(*interval)).await?
+            };
+
+            let invalid = queue.invalid_jobs()?;
+
+            let mut lines = vec![format!("Updated jobs: {}", changed.len())];
+            lines.extend(changed.iter().map(job_line));
+            lines.extend(invalid.iter().map(|error| format!("⚠️ Skipped {}", error)));
+
+            Ok(Report {
+                lines,
+                json: json!({
+                    "updated": changed.iter().map(job_json).collect::<Vec<_>>(),
+                    "invalid": invalid.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                }),
+            })
+        }
+        ScheduleCommand::Cancel { queue, id, invalidate } => {
+            let job = JobQueue::open(&queue.queue)?.cancel(id)?;
+
+            if !invalidate {
+                return Ok(Report {
+                    lines: vec![
+                        job_line(&job),
+                        "⚠️ Copies signed elsewhere stay valid until the nonce is advanced, see --invalidate".to_string(),
+                    ],
+                    json: job_json(&job),
+                });
+            }
+
+            let authority = cli.keypair.load()?;
+            let receipt = cancel_nonce_transaction(connection, &authority, &job.envelope).await?;
+
+            Ok(Report {
+                lines: vec![
+                    job_line(&job),
+                    format!("Transaction Signature: {}", receipt.signature),
+                    format!("✅ Nonce advanced, no copy can land anymore: {}", receipt.explorer_link),
+                ],
+                json: json!({ "job": job_json(&job), "receipt": receipt_json(&receipt) }),
+            })
+        }
+    }
+}
+
+/// Offline exports carry the envelope inside, plain envelopes are read as they are.
+async fn inspect_file(
+    connection: &RpcClient,
+    file: &Path,
+    policy: Option<&Path>,
+    signer: Option<&str>,
+) -> Result<Report> {
+    let envelope = load_envelope(file)?;
+    let decimals = fetch_decimals(connection, &envelope).await?;
+
+    let mut lines = envelope.describe(&decimals);
+
+    let verdict = match (policy, signer) {
+        (Some(policy), Some(signer)) => {
+            let signer = parse_pubkey(signer)?;
+            let verdict = SigningPolicy::from_file(policy)?.evaluate(&envelope, &signer);
+            match &verdict {
+                PolicyVerdict::Accept => lines.push(format!("✅ Signing policy accepts signing as {}", signer)),
+                PolicyVerdict::Reject { reasons } => {
+                    lines.push(format!("🚫 Signing policy rejects signing as {}:", signer));
+                    lines.extend(reasons.iter().map(|reason| format!("   {}", reason)));
+                }
+            }
+            Some(verdict)
+        }
+        _ => None,
+    };
+
+    let status = if envelope.is_complete() {
+        "✅ Fully signed, ready to submit".to_string()
+    } else {
+        format!("⏳ {} signatures missing", envelope.missing_signers().len())
+    };
+    lines.push(status.clone());
+
+    Ok(Report {
+        lines,
+        json: json!({
+            "cluster": envelope.cluster.as_str(),
+            "blockhash": envelope.blockhash().to_string(),
+            "instructions": instructions_json(&inspect(&envelope, &decimals)),
+            "required_signers": envelope.required_signers().iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+            "missing_signers": envelope.missing_signers().iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+            "complete": envelope.is_complete(),
+            "policy": verdict.map(|verdict| match verdict {
+                PolicyVerdict::Accept => json!({ "accepted": true, "reasons": [] }),
+                PolicyVerdict::Reject { reasons } => json!({ "accepted": false, "reasons": reasons }),
+            }),
+            "status": status,
+        }),
+    })
+}
+
+fn instructions_json(summaries: &[InstructionSummary]) -> Value {
+    summaries
+        .iter()
+        .map(|summary| {
+            json!({
+                "program_id": summary.program_id.to_string(),
+                "program": summary.program,
+                "summary": summary.summary,
+            })
+        })
+        .collect()
+}
+
+fn load_envelope(path: &Path) -> Result<TxEnvelope> {
+    match TxEnvelope::load(path) {
+        Ok(envelope) => Ok(envelope),
+        Err(_) => Ok(OfflineTransaction::load(path)?.envelope),
+    }
+}
+
+fn job_line(job: &ScheduledJob) -> String {
+    let status = match &job.status {
+        JobStatus::Pending => format!("⏳ pending, {}", job.trigger.describe()),
+        JobStatus::Sent { signature } => format!("📤 sent {}", signature),
+        JobStatus::Confirmed { signature, slot } => format!("✅ confirmed {} in slot {}", signature, slot),
+        JobStatus::Failed { message } => format!("❌ failed: {}", message),
+        JobStatus::Stale => "⚠️ stale, the nonce was advanced by someone else".to_string(),
+        JobStatus::Cancelled => "🚫 cancelled".to_string(),
+    };
+
+    match &job.last_error {
+        Some(error) => format!("{} {} (retrying after: {})", job.id, status, error),
+        None => format!("{} {}", job.id, status),
+    }
+}
+
+fn job_json(job: &ScheduledJob) -> Value {
+    json!({
+        "id": job.id,
+        "status": job.status.as_str(),
+        "trigger": job.trigger.describe(),
+        "signature": match &job.status {
+            JobStatus::Sent { signature } | JobStatus::Confirmed { signature, .. } => Some(signature.to_string()),
+            _ => None,
+        },
+        "error": match &job.status {
+            JobStatus::Failed { message } => Some(message.clone()),
+            _ => None,
+        },
+        "missing_signers": job.envelope.missing_signers().iter().map(|signer| signer.to_string()).collect::<Vec<_>>(),
+        "attempts": job.attempts,
+        "last_error": job.last_error,
+        "created_at": job.created_at,
+        "updated_at": job.updated_at,
+    })
+}
+
+fn connection(cli: &Cli) -> Result<RpcClient> {
+    let config = match &cli.cluster {
+        Some(cluster) => ClusterConfig::from_cluster(parse_cluster(cluster)?)?,
+        None => ClusterConfig::resolve()?,
+    };
+
+    Ok(config.connection())
+}
+
+fn parse_pubkeys(addresses: &[String]) -> Result<Vec<Pubkey>> {
+    addresses.iter().map(|address| parse_pubkey(address)).collect()
+}
+
+fn parse_key_value(input: &str) -> std::result::Result<(String, String), String> {
+    input
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected key=value, got `{}`", input))
+}
+
+fn simulation_report(simulation: &SimulationReport) -> Report {
+    let mut lines = vec![match &simulation.error {
+        Some(error) => format!("❌ Simulation failed: {}", error),
+        None => "✅ Simulation succeeded, nothing was sent".to_string(),
+    }];
+    lines.push(format!("Compute units consumed: {}", simulation.units_consumed));
+    lines.push(format!("Fee: {} lamports", simulation.fee));
+    for change in &simulation.balance_changes {
+        let mut line = format!("{}: {:+} lamports", change.account, change.lamports_delta());
+        if let Some(delta) = change.token_amount_delta() {
+            line.push_str(&format!(", {:+} tokens (base units)", delta));
+        }
+        lines.push(line);
+    }
+    lines.extend(simulation.logs.iter().map(|log| format!("   {}", log)));
+
+    Report {
+        lines,
+        json: json!({
+            "dry_run": true,
+            "error": simulation.error.as_ref().map(|error| error.to_string()),
+            "logs": simulation.logs,
+            "units_consumed": simulation.units_consumed,
+            "fee": simulation.fee,
+            "balance_changes": simulation.balance_changes.iter().map(|change| json!({
+                "account": change.account.to_string(),
+                "lamports_before": change.lamports_before,
+                "lamports_after": change.lamports_after,
+                "token_amount_before": change.token_amount_before,
+                "token_amount_after": change.token_amount_after,
+            })).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+fn receipt_json(receipt: &TransactionReceipt) -> Value {
+    json!({
+        "signature": receipt.signature.to_string(),
+        "slot": receipt.slot,
+        "explorer_link": receipt.explorer_link,
+    })
+}
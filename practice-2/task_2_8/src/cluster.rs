@@ -0,0 +1,224 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::error::{Result, SolanaUtilsError};
+use crate::explorer_link::Cluster;
+
+pub const CLUSTER_ENV: &str = "SOLANA_CLUSTER";
+pub const RPC_URL_ENV: &str = "SOLANA_RPC_URL";
+pub const WS_URL_ENV: &str = "SOLANA_WS_URL";
+pub const COMMITMENT_ENV: &str = "SOLANA_COMMITMENT";
+pub const CLI_CONFIG_ENV: &str = "SOLANA_CONFIG";
+
+/// Connection settings shared by every helper: which cluster we talk to,
+/// its RPC and websocket endpoints and the commitment we wait for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterConfig {
+    pub cluster: Cluster,
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub commitment: CommitmentConfig,
+}
+
+/// Subset of the Solana CLI `config.yml` we care about.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CliConfig {
+    json_rpc_url: String,
+    websocket_url: String,
+    commitment: String,
+}
+
+impl ClusterConfig {
+    pub fn from_cluster(cluster: Cluster) -> Result<Self> {
+        let rpc_url = cluster.rpc_url();
+        let ws_url = websocket_url_from_rpc_url(&rpc_url)?;
+
+        Ok(Self {
+            cluster,
+            rpc_url,
+            ws_url,
+            commitment: CommitmentConfig::confirmed(),
+        })
+    }
+
+    /// Builds the config from `SOLANA_CLUSTER`, `SOLANA_RPC_URL`, `SOLANA_WS_URL`
+    /// and `SOLANA_COMMITMENT`. Returns `None` when neither a cluster nor an RPC URL is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        dotenv::dotenv().ok();
+
+        let cluster = env::var(CLUSTER_ENV).ok().map(|value| parse_cluster(&value)).transpose()?;
+        let rpc_url = env::var(RPC_URL_ENV).ok();
+
+        let mut config = match (cluster, rpc_url) {
+            (None, None) => return Ok(None),
+            (Some(cluster), None) => Self::from_cluster(cluster)?,
+            (cluster, Some(rpc_url)) => Self::from_rpc_url(cluster, &rpc_url)?,
+        };
+
+        if let Ok(ws_url) = env::var(WS_URL_ENV) {
+            config.ws_url = ws_url;
+        }
+
+        if let Ok(commitment) = env::var(COMMITMENT_ENV) {
+            config.commitment = parse_commitment(&commitment)?;
+        }
+
+        Ok(Some(config))
+    }
+
+    /// Reads a Solana CLI `config.yml` (as written by `solana config set`).
+    pub fn from_cli_config(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|error| SolanaUtilsError::Config(format!("{}: {}", path.display(), error)))?;
+        let cli_config: CliConfig = serde_yaml::from_str(&content)
+            .map_err(|error| SolanaUtilsError::Config(format!("{}: {}", path.display(), error)))?;
+
+        if cli_config.json_rpc_url.is_empty() {
+            return Err(SolanaUtilsError::Config(format!("{} has no json_rpc_url", path.display())));
+        }
+
+        let mut config = Self::from_rpc_url(None, &cli_config.json_rpc_url)?;
+
+        if !cli_config.websocket_url.is_empty() {
+            config.ws_url = cli_config.websocket_url;
+        }
+
+        if !cli_config.commitment.is_empty() {
+            config.commitment = parse_commitment(&cli_config.commitment)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Resolves the config from env vars first, then the Solana CLI config file,
+    /// falling back to devnet when neither is present.
+    pub fn resolve() -> Result<Self> {
+        if let Some(config) = Self::from_env()? {
+            return Ok(config);
+        }
+
+        if let Some(path) = cli_config_path().filter(|path| path.exists()) {
+            return Self::from_cli_config(path);
+        }
+
+        Self::from_cluster(Cluster::Devnet)
+    }
+
+    pub fn with_commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = commitment;
+        self
+    }
+
+    pub fn connection(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
+    }
+
+    fn from_rpc_url(cluster: Option<Cluster>, rpc_url: &str) -> Result<Self> {
+        let cluster = cluster.unwrap_or_else(|| cluster_from_rpc_url(rpc_url));
+
+        Ok(Self {
+            cluster,
+            rpc_url: rpc_url.to_string(),
+            ws_url: websocket_url_from_rpc_url(rpc_url)?,
+            commitment: CommitmentConfig::confirmed(),
+        })
+    }
+}
+
+/// Accepts the moniker names used by the Solana CLI (`devnet`, `localhost`, `m`, ...)
+/// as well as a raw `scheme://` RPC URL, which becomes `Cluster::Custom`. Anything
+/// else is rejected, so a typo like `devnt` doesn't turn into a bogus endpoint.
+pub fn parse_cluster(value: &str) -> Result<Cluster> {
+    if let Some((scheme, rest)) = value.split_once("://") {
+        if scheme.is_empty() || rest.is_empty() {
+            return Err(SolanaUtilsError::Config(format!("cluster `{}` is not a valid URL", value)));
+        }
+        return Ok(Cluster::Custom(value.to_string()));
+    }
+
+    match value.to_lowercase().as_str() {
+        "m" | "mainnet" | "mainnet-beta" => Ok(Cluster::MainnetBeta),
+        "t" | "testnet" => Ok(Cluster::Testnet),
+        "d" | "devnet" => Ok(Cluster::Devnet),
+        "l" | "localhost" | "localnet" | "test-validator" => Ok(Cluster::Localnet),
+        _ => Err(SolanaUtilsError::Config(format!(
+            "unknown cluster `{}`, use mainnet-beta, testnet, devnet, localnet or an RPC URL",
+            value
+        ))),
+    }
+}
+
+/// Maps a well-known RPC endpoint back to its cluster, anything else is `Cluster::Custom`.
+pub fn cluster_from_rpc_url(rpc_url: &str) -> Cluster {
+    let url = rpc_url.trim_end_matches('/');
+
+    for cluster in [
+        Cluster::MainnetBeta,
+        Cluster::Testnet,
+        Cluster::Devnet,
+        Cluster::Localnet,
+    ] {
+        if cluster.rpc_url() == url {
+            return cluster;
+        }
+    }
+
+    if url.contains("://localhost") || url.contains("://127.0.0.1") {
+        return Cluster::Localnet;
+    }
+
+    Cluster::Custom(rpc_url.to_string())
+}
+
+/// Same rule as the Solana CLI: switch the scheme to ws/wss and,
+/// if the URL carries an explicit port, use the next one.
+pub fn websocket_url_from_rpc_url(rpc_url: &str) -> Result<String> {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => {
+                let ws_port = port.checked_add(1).ok_or_else(|| {
+                    SolanaUtilsError::Config(format!("{} uses port {}, there is no next port for the websocket", rpc_url, port))
+                })?;
+                format!("{}:{}", host, ws_port)
+            }
+            Err(_) => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+
+    Ok(format!("{}://{}{}", scheme, authority, path))
+}
+
+fn parse_commitment(value: &str) -> Result<CommitmentConfig> {
+    CommitmentConfig::from_str(value)
+        .map_err(|error| SolanaUtilsError::Config(format!("commitment `{}`: {}", value, error)))
+}
+
+fn cli_config_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var(CLI_CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".config/solana/cli/config.yml"))
+}
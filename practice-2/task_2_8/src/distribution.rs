@@ -0,0 +1,445 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+use spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use spl_token_2022::instruction::{mint_to_checked, transfer_checked};
+use tokio::time::sleep;
+
+use crate::amount::{fetch_mint_decimals, TokenAmount};
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::token_program::TokenProgram;
+use crate::tx_sender::{wait_for_confirmation, TxSender};
+
+const PENDING_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Where distributed tokens come from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistributionMode {
+    /// `mint_to` signed by the mint authority.
+    Mint,
+    /// `transfer_checked` out of the authority's associated token account.
+    Transfer,
+}
+
+impl DistributionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DistributionMode::Mint => "mint",
+            DistributionMode::Transfer => "transfer",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recipient {
+    pub owner: Pubkey,
+    pub amount: TokenAmount,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistributedTokens {
+    /// 1-based position of the recipient in the input.
+    pub row: usize,
+    pub owner: Pubkey,
+    pub token_account: Pubkey,
+    pub amount: TokenAmount,
+    pub signature: Signature,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DistributionReport {
+    pub mint: Pubkey,
+    pub mode: DistributionMode,
+    pub distributed: Vec<DistributedTokens>,
+    /// Recipients that were already paid by an earlier, interrupted run.
+    pub resumed: usize,
+    pub transactions: usize,
+}
+
+/// Progress written after every change, so a rerun with the same input
+/// skips recipients whose transaction landed.
+#[derive(Debug, Serialize, Deserialize)]
+struct DistributionState {
+    mint: String,
+    mode: String,
+    recipients: Vec<RecipientState>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecipientState {
+    owner: String,
+    amount: u64,
+    /// Signature of the transaction paying this recipient, set before it is sent.
+    #[serde(default)]
+    signature: Option<String>,
+    #[serde(default)]
+    last_valid_block_height: Option<u64>,
+    #[serde(default)]
+    confirmed: bool,
+}
+
+/// Reads `owner,amount` lines, with amounts in tokens scaled by `decimals`.
+/// Empty lines, `#` comments and a header line are skipped.
+pub fn read_recipients_csv(path: impl AsRef<Path>, decimals: u8) -> Result<Vec<Recipient>> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .map_err(|error| SolanaUtilsError::InvalidInput(format!("{}: {}", path.display(), error)))?;
+
+    let mut recipients = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = |message: String| {
+            SolanaUtilsError::InvalidInput(format!("{}:{}: {}", path.display(), index + 1, message))
+        };
+
+        let Some((owner, amount)) = line.split_once(',') else {
+            return Err(invalid("expected `owner,amount`".to_string()));
+        };
+
+        let owner = match Pubkey::from_str(owner.trim()) {
+            Ok(owner) => owner,
+            Err(_) if recipients.is_empty() && TokenAmount::parse(amount, decimals).is_err() => continue,
+            Err(error) => return Err(invalid(format!("invalid owner `{}`: {}", owner.trim(), error))),
+        };
+        let amount = TokenAmount::parse(amount, decimals).map_err(|error| invalid(error.to_string()))?;
+
+        recipients.push(Recipient { owner, amount });
+    }
+
+    Ok(recipients)
+}
+
+/// Pays every recipient, creating their associated token accounts on the way,
+/// with as many recipients per transaction as fit.
+///
+/// Progress is kept in `state_path`. Before a transaction is sent its signature
+/// is recorded, and a rerun first settles those signatures: recipients whose
+/// transaction landed are skipped, the rest are only retried once their
+/// blockhash expired, so nobody is paid twice.
+pub async fn distribute_tokens(
+    connection: &RpcClient,
+    authority: &Keypair,
+    mint: &Pubkey,
+    mode: DistributionMode,
+    recipients: &[Recipient],
+    state_path: impl AsRef<Path>,
+    tx_sender: &TxSender,
+) -> Result<DistributionReport> {
+    let state_path = state_path.as_ref();
+
+    let token_program = TokenProgram::for_mint(connection, mint).await?;
+    let decimals = fetch_mint_decimals(connection, mint).await?;
+    for recipient in recipients {
+        recipient.amount.ensure_decimals(decimals)?;
+    }
+
+    let mut state = load_state(state_path, mint, mode, recipients)?;
+    let resumed = settle_pending(connection, &mut state, state_path).await?;
+
+    let source = token_program.associated_token_address(&authority.pubkey(), mint);
+    let payer = authority.pubkey();
+
+    let recipient_instructions = |recipient: &Recipient| -> Result<Vec<Instruction>> {
+        let token_account = token_program.associated_token_address(&recipient.owner, mint);
+
+        let pay_ix = match mode {
+            DistributionMode::Mint => mint_to_checked(
+                &token_program.id(),
+                mint,
+                &token_account,
+                &payer,
+                &[],
+                recipient.amount.raw(),
+                decimals,
+            )?,
+            DistributionMode::Transfer => transfer_checked(
+                &token_program.id(),
+                &source,
+                mint,
+                &token_account,
+                &payer,
+                &[],
+                recipient.amount.raw(),
+                decimals,
+            )?,
+        };
+
+        Ok(vec![
+            create_associated_token_account_idempotent(&payer, &recipient.owner, mint, &token_program.id()),
+            pay_ix,
+        ])
+    };
+
+    let mut batches: Vec<(Vec<usize>, Vec<Instruction>)> = Vec::new();
+    for (index, recipient) in recipients.iter().enumerate() {
+        if state.recipients[index].confirmed {
+            continue;
+        }
+
+        let instructions = recipient_instructions(recipient)?;
+
+        if let Some((rows, batch)) = batches.last_mut() {
+            let mut candidate = batch.clone();
+            candidate.extend_from_slice(&instructions);
+
+            if fits_in_transaction(&candidate, &payer) {
+                rows.push(index);
+                *batch = candidate;
+                continue;
+            }
+        }
+
+        batches.push((vec![index], instructions));
+    }
+
+    let mut transactions = 0;
+    for (rows, instructions) in &batches {
+        send_batch(connection, authority, instructions, rows, &mut state, state_path, tx_sender).await?;
+        transactions += 1;
+    }
+
+    let distributed = recipients
+        .iter()
+        .enumerate()
+        .map(|(index, recipient)| {
+            let signature = state.recipients[index]
+                .signature
+                .as_deref()
+                .map(Signature::from_str)
+                .transpose()
+                .map_err(|error| state_error(state_path, error.to_string()))?
+                .unwrap_or_default();
+
+            Ok(DistributedTokens {
+                row: index + 1,
+                owner: recipient.owner,
+                token_account: token_program.associated_token_address(&recipient.owner, mint),
+                amount: recipient.amount,
+                signature,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(DistributionReport {
+        mint: *mint,
+        mode,
+        distributed,
+        resumed,
+        transactions,
+    })
+}
+
+async fn send_batch(
+    connection: &RpcClient,
+    authority: &Keypair,
+    instructions: &[Instruction],
+    rows: &[usize],
+    state: &mut DistributionState,
+    state_path: &Path,
+    tx_sender: &TxSender,
+) -> Result<()> {
+    let payer = authority.pubkey();
+    let budgeted = tx_sender.budget(connection, instructions, &payer, None).await?;
+
+    for _ in 0..tx_sender.max_attempts {
+        let (blockhash, last_valid_block_height) = connection
+            .get_latest_blockhash_with_commitment(connection.commitment())
+            .await?;
+
+        let mut transaction = Transaction::new_with_payer(&budgeted.instructions, Some(&payer));
+        transaction.try_sign(&[authority], blockhash)?;
+        let signature = transaction.signatures[0];
+
+        for row in rows {
+            state.recipients[*row].signature = Some(signature.to_string());
+            state.recipients[*row].last_valid_block_height = Some(last_valid_block_height);
+        }
+        save_state(state_path, state)?;
+
+        connection
+            .send_transaction_with_config(
+                &transaction,
+                RpcSendTransactionConfig {
+                    skip_preflight: true,
+                    ..RpcSendTransactionConfig::default()
+                },
+            )
+            .await
+            .map_err(|error| SolanaUtilsError::from_transaction_error(error, &transaction.message))?;
+
+        let landed = wait_for_confirmation(connection, &signature, &transaction.message, last_valid_block_height)
+            .await?
+            .is_some();
+
+        for row in rows {
+            if landed {
+                state.recipients[*row].confirmed = true;
+            } else {
+                state.recipients[*row].signature = None;
+                state.recipients[*row].last_valid_block_height = None;
+            }
+        }
+        save_state(state_path, state)?;
+
+        if landed {
+            return Ok(());
+        }
+    }
+
+    Err(SolanaUtilsError::Expired { attempts: tx_sender.max_attempts })
+}
+
+/// Resolves signatures left by an interrupted run. Returns how many recipients were already paid.
+async fn settle_pending(connection: &RpcClient, state: &mut DistributionState, state_path: &Path) -> Result<usize> {
+    let mut pending: Vec<(String, u64)> = state
+        .recipients
+        .iter()
+        .filter(|recipient| !recipient.confirmed)
+        .filter_map(|recipient| Some((recipient.signature.clone()?, recipient.last_valid_block_height?)))
+        .collect();
+    pending.sort();
+    pending.dedup();
+
+    for (signature, last_valid_block_height) in pending {
+        let parsed = Signature::from_str(&signature).map_err(|error| state_error(state_path, error.to_string()))?;
+        let landed = has_landed(connection, &parsed, last_valid_block_height).await?;
+
+        for recipient in state.recipients.iter_mut() {
+            if recipient.signature.as_deref() == Some(signature.as_str()) {
+                if landed {
+                    recipient.confirmed = true;
+                } else {
+                    recipient.signature = None;
+                    recipient.last_valid_block_height = None;
+                }
+            }
+        }
+        save_state(state_path, state)?;
+    }
+
+    Ok(state.recipients.iter().filter(|recipient| recipient.confirmed).count())
+}
+
+/// Waits until `signature` either succeeded, failed, or can no longer land.
+async fn has_landed(connection: &RpcClient, signature: &Signature, last_valid_block_height: u64) -> Result<bool> {
+    loop {
+        let status = connection
+            .get_signature_statuses_with_history(&[*signature])
+            .await?
+            .value
+            .into_iter()
+            .flatten()
+            .next();
+
+        match status {
+            Some(status) if status.err.is_some() => return Ok(false),
+            Some(status) if status.satisfies_commitment(connection.commitment()) => return Ok(true),
+            None if connection.get_block_height().await? > last_valid_block_height => return Ok(false),
+            _ => sleep(PENDING_POLL_INTERVAL).await,
+        }
+    }
+}
+
+fn fits_in_transaction(instructions: &[Instruction], payer: &Pubkey) -> bool {
+    // Room for the compute budget instructions the sender adds.
+    let mut budgeted = vec![
+        ComputeBudgetInstruction::set_compute_unit_limit(0),
+        ComputeBudgetInstruction::set_compute_unit_price(0),
+    ];
+    budgeted.extend_from_slice(instructions);
+
+    let message = Message::new(&budgeted, Some(payer));
+    let signatures = message.header.num_required_signatures as usize;
+
+    1 + signatures * 64 + message.serialize().len() <= PACKET_DATA_SIZE
+}
+
+fn load_state(
+    state_path: &Path,
+    mint: &Pubkey,
+    mode: DistributionMode,
+    recipients: &[Recipient],
+) -> Result<DistributionState> {
+    if !state_path.exists() {
+        let state = DistributionState {
+            mint: mint.to_string(),
+            mode: mode.as_str().to_string(),
+            recipients: recipients
+                .iter()
+                .map(|recipient| RecipientState {
+                    owner: recipient.owner.to_string(),
+                    amount: recipient.amount.raw(),
+                    signature: None,
+                    last_valid_block_height: None,
+                    confirmed: false,
+                })
+                .collect(),
+        };
+        save_state(state_path, &state)?;
+
+        return Ok(state);
+    }
+
+    let content = fs::read_to_string(state_path).map_err(|error| state_error(state_path, error.to_string()))?;
+    let state: DistributionState =
+        serde_json::from_str(&content).map_err(|error| state_error(state_path, error.to_string()))?;
+
+    let matches = state.mint == mint.to_string()
+        && state.mode == mode.as_str()
+        && state.recipients.len() == recipients.len()
+        && state.recipients.iter().zip(recipients).all(|(saved, recipient)| {
+            parse_pubkey(&saved.owner).ok() == Some(recipient.owner) && saved.amount == recipient.amount.raw()
+        });
+
+    if !matches {
+        return Err(state_error(
+            state_path,
+            "was written for a different mint, mode or recipient list".to_string(),
+        ));
+    }
+
+    Ok(state)
+}
+
+/// Written to a temporary file first, so an interruption never leaves half a state behind.
+fn save_state(state_path: &Path, state: &DistributionState) -> Result<()> {
+    let content = serde_json::to_string_pretty(state).map_err(|error| state_error(state_path, error.to_string()))?;
+    let temporary_path = temporary_path(state_path);
+
+    fs::write(&temporary_path, content)
+        .and_then(|_| fs::rename(&temporary_path, state_path))
+        .map_err(|error| state_error(state_path, error.to_string()))
+}
+
+fn temporary_path(state_path: &Path) -> PathBuf {
+    let mut file_name = state_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+
+    state_path.with_file_name(file_name)
+}
+
+fn state_error(state_path: &Path, message: String) -> SolanaUtilsError {
+    SolanaUtilsError::State {
+        path: state_path.to_path_buf(),
+        message,
+    }
+}
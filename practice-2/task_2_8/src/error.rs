@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::{
+    instruction::InstructionError,
+    message::{CompileError, Message},
+    program_error::ProgramError,
+    pubkey::{ParsePubkeyError, Pubkey},
+    signer::SignerError,
+    system_instruction::SystemError,
+    transaction::TransactionError,
+};
+#[allow(deprecated)]
+use solana_sdk::system_program;
+use spl_token_2022::error::TokenError;
+
+use crate::amount::AmountError;
+use crate::keypair::KeypairError;
+
+pub type Result<T> = std::result::Result<T, SolanaUtilsError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SolanaUtilsError {
+    #[error("Invalid public key `{input}`: {source}")]
+    InvalidPubkey {
+        input: String,
+        #[source]
+        source: ParsePubkeyError,
+    },
+    #[error("RPC request failed: {0}")]
+    Rpc(#[source] Box<ClientError>),
+    #[error("Transaction simulation failed: {error}")]
+    Simulation {
+        error: TransactionError,
+        logs: Vec<String>,
+    },
+    #[error("Insufficient funds: {error}")]
+    InsufficientFunds {
+        error: TransactionError,
+        logs: Vec<String>,
+    },
+    #[error("Transaction expired {attempts} times without being confirmed")]
+    Expired { attempts: usize },
+    #[error("Account {0} already exists")]
+    AccountAlreadyExists(Pubkey),
+    #[error("Account {account} is owned by {owner}, not by a token program")]
+    NotTokenAccount { account: Pubkey, owner: Pubkey },
+    #[error("Can't decode account {account}: {message}")]
+    Decode { account: Pubkey, message: String },
+    #[error(transparent)]
+    Keypair(#[from] KeypairError),
+    #[error(transparent)]
+    Amount(#[from] AmountError),
+    #[error(transparent)]
+    Program(#[from] ProgramError),
+    #[error("Can't sign transaction: {0}")]
+    Signing(#[from] SignerError),
+    #[error("Can't compile v0 message: {0}")]
+    Compile(#[from] CompileError),
+    #[error("Invalid cluster config: {0}")]
+    Config(String),
+    #[error("Distribution state {path:?}: {message}")]
+    State { path: PathBuf, message: String },
+    #[error("{path:?}: {message}")]
+    File { path: PathBuf, message: String },
+    #[error("Invalid transaction envelope: {0}")]
+    Envelope(String),
+    #[error("Rejected by sponsor policy: {0}")]
+    Policy(String),
+    #[error("Too many sponsored transactions from {sender}, retry in {retry_after:?}")]
+    RateLimited { sender: Pubkey, retry_after: Duration },
+    #[error("Refused to sign as {signer}: {}", reasons.join("; "))]
+    SigningRejected { signer: Pubkey, reasons: Vec<String> },
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+impl SolanaUtilsError {
+    /// Program logs of a failed transaction, empty for non-transaction errors.
+    pub fn logs(&self) -> &[String] {
+        match self {
+            SolanaUtilsError::Simulation { logs, .. }
+            | SolanaUtilsError::InsufficientFunds { logs, .. } => logs,
+            _ => &[],
+        }
+    }
+
+    /// Classifies a send/confirm failure using the message that was sent, so
+    /// program-specific custom error codes can be told apart.
+    pub fn from_transaction_error(error: ClientError, message: &Message) -> Self {
+        let Some(transaction_error) = error.get_transaction_error() else {
+            return SolanaUtilsError::Rpc(Box::new(error));
+        };
+        let logs = preflight_logs(&error);
+
+        Self::from_transaction_failure(transaction_error, logs, message)
+    }
+
+    /// Same classification for a failure reported by a simulation or a signature status.
+    pub fn from_transaction_failure(transaction_error: TransactionError, logs: Vec<String>, message: &Message) -> Self {
+        match &transaction_error {
+            TransactionError::InsufficientFundsForFee
+            | TransactionError::InsufficientFundsForRent { .. } => {
+                return SolanaUtilsError::InsufficientFunds { error: transaction_error, logs };
+            }
+            TransactionError::InstructionError(index, instruction_error) => {
+                let index = *index as usize;
+                let program_id = message.program_id(index).copied().unwrap_or_default();
+
+                match instruction_error {
+                    InstructionError::Custom(code)
+                        if *code == SystemError::AccountAlreadyInUse as u32
+                            && (program_id == system_program::id()
+                                || (program_id == spl_associated_token_account::id()
+                                    && logs.iter().any(|log| log.contains("already in use")))) =>
+                    {
+                        // Both create_account and the ATA create instruction
+                        // take the new account as their second account. The ATA
+                        // program's own code 0 means a wrong owner, so there it only
+                        // counts when the system program logged the clash.
+                        if let Some(account) = instruction_account(message, index, 1) {
+                            return SolanaUtilsError::AccountAlreadyExists(account);
+                        }
+                    }
+                    InstructionError::Custom(code)
+                        if (program_id == system_program::id()
+                            && *code == SystemError::ResultWithNegativeLamports as u32)
+                            || (spl_token_2022::check_spl_token_program_account(&program_id).is_ok()
+                                && *code == TokenError::InsufficientFunds as u32) =>
+                    {
+                        return SolanaUtilsError::InsufficientFunds { error: transaction_error, logs };
+                    }
+                    InstructionError::InsufficientFunds => {
+                        return SolanaUtilsError::InsufficientFunds { error: transaction_error, logs };
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        SolanaUtilsError::Simulation { error: transaction_error, logs }
+    }
+}
+
+impl From<ClientError> for SolanaUtilsError {
+    fn from(error: ClientError) -> Self {
+        SolanaUtilsError::Rpc(Box::new(error))
+    }
+}
+
+pub fn parse_pubkey(input: &str) -> Result<Pubkey> {
+    Pubkey::from_str(input).map_err(|source| SolanaUtilsError::InvalidPubkey {
+        input: input.to_string(),
+        source,
+    })
+}
+
+fn preflight_logs(error: &ClientError) -> Vec<String> {
+    match error.kind() {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+            ..
+        }) => result.logs.clone().unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn instruction_account(message: &Message, instruction_index: usize, position: usize) -> Option<Pubkey> {
+    let instruction = message.instructions.get(instruction_index)?;
+    let account_index = *instruction.accounts.get(position)?;
+
+    message.account_keys.get(account_index as usize).copied()
+}
@@ -1,3 +1,13 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::error::{Result, SolanaUtilsError};
+
+pub const EXPLORER_ENV: &str = "SOLANA_EXPLORER";
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Cluster {
     MainnetBeta,
@@ -17,25 +27,39 @@ impl Cluster {
             Cluster::Custom(name) => name.clone(),
         }
     }
+
+    pub fn rpc_url(&self) -> String {
+        match self {
+            Cluster::MainnetBeta => "https://api.mainnet-beta.solana.com".to_string(),
+            Cluster::Testnet => "https://api.testnet.solana.com".to_string(),
+            Cluster::Devnet => "https://api.devnet.solana.com".to_string(),
+            Cluster::Localnet => "http://127.0.0.1:8899".to_string(),
+            Cluster::Custom(url) => url.clone(),
+        }
+    }
 }
 
 impl From<&str> for Cluster {
+    /// Cluster names match in any case, anything else is kept as given.
     fn from(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "mainnet-beta" => Cluster::MainnetBeta,
             "testnet" => Cluster::Testnet,
             "devnet" => Cluster::Devnet,
             "localnet" => Cluster::Localnet,
-            custom => Cluster::Custom(custom.to_string()),
+            _ => Cluster::Custom(s.to_string()),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LinkType {
     Transaction,
     Address,
+    /// A block, identified by its slot.
     Block,
+    /// A mint, shown with supply and holders where the explorer has a token page.
+    Token,
 }
 
 impl LinkType {
@@ -44,28 +68,266 @@ impl LinkType {
             LinkType::Transaction => "tx",
             LinkType::Address => "address",
             LinkType::Block => "block",
+            LinkType::Token => "token",
         }
     }
 }
 
-impl From<&str> for LinkType {
-    fn from(s: &str) -> Self {
-        match s.to_lowercase().as_str() {
-            "transaction" | "tx" => LinkType::Transaction,
-            "address" => LinkType::Address,
-            "block" => LinkType::Block,
-            _ => panic!("Invalid link type: {}", s),
+impl FromStr for LinkType {
+    type Err = SolanaUtilsError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "transaction" | "tx" => Ok(LinkType::Transaction),
+            "address" | "account" => Ok(LinkType::Address),
+            "block" | "slot" => Ok(LinkType::Block),
+            "token" | "mint" => Ok(LinkType::Token),
+            _ => Err(SolanaUtilsError::InvalidInput(format!("invalid link type `{}`", value))),
         }
     }
 }
 
-pub fn get_explorer_link(link_type: &str, id: String, cluster: &str) -> String {
-    let link_type = LinkType::from(link_type);
-    let cluster = Cluster::from(cluster);
-    let base_url = "https://explorer.solana.com";
-    let cluster_param = match cluster {
-        Cluster::MainnetBeta => "".to_string(),
-        _ => format!("?cluster={}", cluster.as_str())
-    };
-    format!("{}/{}/{}{}", base_url, link_type.as_str(), id, cluster_param)
-}
\ No newline at end of file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Explorer {
+    /// explorer.solana.com, the only one that can point at any RPC URL.
+    #[default]
+    Solana,
+    Solscan,
+    SolanaFm,
+    Xray,
+}
+
+impl Explorer {
+    /// Reads `SOLANA_EXPLORER`, defaulting to explorer.solana.com when it is not set.
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        match env::var(EXPLORER_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(Explorer::default()),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Explorer::Solana => "solana",
+            Explorer::Solscan => "solscan",
+            Explorer::SolanaFm => "solanafm",
+            Explorer::Xray => "xray",
+        }
+    }
+}
+
+impl fmt::Display for Explorer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Explorer {
+    type Err = SolanaUtilsError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "solana" | "explorer" | "explorer.solana.com" => Ok(Explorer::Solana),
+            "solscan" | "solscan.io" => Ok(Explorer::Solscan),
+            "solanafm" | "solana.fm" => Ok(Explorer::SolanaFm),
+            "xray" | "xray.helius.xyz" => Ok(Explorer::Xray),
+            _ => Err(SolanaUtilsError::InvalidInput(format!("unknown explorer `{}`", value))),
+        }
+    }
+}
+
+/// Builds links to one explorer for one cluster.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExplorerLink {
+    cluster: Cluster,
+    explorer: Explorer,
+}
+
+impl ExplorerLink {
+    pub fn new(cluster: Cluster) -> Self {
+        Self {
+            cluster,
+            explorer: Explorer::default(),
+        }
+    }
+
+    pub fn explorer(mut self, explorer: Explorer) -> Self {
+        self.explorer = explorer;
+        self
+    }
+
+    pub fn transaction(&self, signature: &Signature) -> Result<String> {
+        self.link(LinkType::Transaction, &signature.to_string())
+    }
+
+    pub fn address(&self, address: &Pubkey) -> Result<String> {
+        self.link(LinkType::Address, &address.to_string())
+    }
+
+    pub fn token(&self, mint: &Pubkey) -> Result<String> {
+        self.link(LinkType::Token, &mint.to_string())
+    }
+
+    pub fn block(&self, slot: u64) -> Result<String> {
+        self.link(LinkType::Block, &slot.to_string())
+    }
+
+    /// Fails when the explorer has no page for `link_type` or can't show the cluster.
+    pub fn link(&self, link_type: LinkType, id: &str) -> Result<String> {
+        let (base_url, path) = match self.explorer {
+            Explorer::Solana => ("https://explorer.solana.com", solana_explorer_path(link_type)),
+            Explorer::Solscan => ("https://solscan.io", solscan_path(link_type)),
+            Explorer::SolanaFm => ("https://solana.fm", solana_explorer_path(link_type)),
+            Explorer::Xray => match link_type {
+                LinkType::Block => return Err(self.unsupported(&format!("{} links", link_type.as_str()))),
+                _ => ("https://xray.helius.xyz", solscan_path(link_type)),
+            },
+        };
+
+        Ok(format!("{}/{}/{}{}", base_url, path, id, self.cluster_query()?))
+    }
+
+    /// Same as `link`, but falls back to explorer.solana.com, which supports every
+    /// link type and cluster, instead of failing.
+    pub fn link_or_default(&self, link_type: LinkType, id: &str) -> String {
+        self.link(link_type, id).unwrap_or_else(|_| {
+            format!(
+                "https://explorer.solana.com/{}/{}{}",
+                solana_explorer_path(link_type),
+                id,
+                solana_explorer_query(&self.cluster)
+            )
+        })
+    }
+
+    fn cluster_query(&self) -> Result<String> {
+        let query = match (self.explorer, &self.cluster) {
+            (Explorer::Solana, cluster) => solana_explorer_query(cluster),
+            (_, Cluster::MainnetBeta) => String::new(),
+            (Explorer::Solscan, Cluster::Testnet | Cluster::Devnet) => format!("?cluster={}", self.cluster.as_str()),
+            (Explorer::Solscan, cluster) => custom_url_query(cluster),
+            (Explorer::SolanaFm, Cluster::Testnet | Cluster::Devnet | Cluster::Localnet) => {
+                format!("?cluster={}-solana", self.cluster.as_str())
+            }
+            (Explorer::Xray, Cluster::Devnet) => "?network=devnet".to_string(),
+            (_, cluster) => return Err(self.unsupported(&format!("the {} cluster", cluster.as_str()))),
+        };
+
+        Ok(query)
+    }
+
+    fn unsupported(&self, what: &str) -> SolanaUtilsError {
+        SolanaUtilsError::InvalidInput(format!("{} explorer doesn't support {}", self.explorer, what))
+    }
+}
+
+/// Shorthand for a single link, e.g. `get_explorer_link("tx", signature, "devnet")`.
+pub fn get_explorer_link(link_type: &str, id: String, cluster: &str) -> Result<String> {
+    ExplorerLink::new(Cluster::from(cluster)).link(link_type.parse()?, &id)
+}
+
+fn solana_explorer_path(link_type: LinkType) -> &'static str {
+    match link_type {
+        LinkType::Token => "address",
+        _ => link_type.as_str(),
+    }
+}
+
+fn solscan_path(link_type: LinkType) -> &'static str {
+    match link_type {
+        LinkType::Address => "account",
+        _ => link_type.as_str(),
+    }
+}
+
+fn solana_explorer_query(cluster: &Cluster) -> String {
+    match cluster {
+        Cluster::MainnetBeta => String::new(),
+        Cluster::Testnet | Cluster::Devnet => format!("?cluster={}", cluster.as_str()),
+        Cluster::Localnet | Cluster::Custom(_) => custom_url_query(cluster),
+    }
+}
+
+fn custom_url_query(cluster: &Cluster) -> String {
+    format!("?cluster=custom&customUrl={}", encode_query_value(&cluster.rpc_url()))
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: &str = "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    fn link(explorer: Explorer, cluster: Cluster, link_type: LinkType) -> Result<String> {
+        ExplorerLink::new(cluster).explorer(explorer).link(link_type, SIGNATURE)
+    }
+
+    #[test]
+    fn keeps_custom_urls_as_given() {
+        assert_eq!(Cluster::from("DevNet"), Cluster::Devnet);
+        assert_eq!(
+            Cluster::from("https://Example.com/RPC?api-key=AbC"),
+            Cluster::Custom("https://Example.com/RPC?api-key=AbC".to_string())
+        );
+    }
+
+    #[test]
+    fn encodes_the_custom_url() {
+        let cluster = Cluster::from("https://Example.com:8899/RPC?api-key=AbC");
+
+        assert_eq!(
+            link(Explorer::Solana, cluster.clone(), LinkType::Transaction).unwrap(),
+            format!(
+                "https://explorer.solana.com/tx/{}?cluster=custom&customUrl=https%3A%2F%2FExample.com%3A8899%2FRPC%3Fapi-key%3DAbC",
+                SIGNATURE
+            )
+        );
+        assert_eq!(
+            link(Explorer::Solana, Cluster::Localnet, LinkType::Address).unwrap(),
+            format!("https://explorer.solana.com/address/{}?cluster=custom&customUrl=http%3A%2F%2F127.0.0.1%3A8899", SIGNATURE)
+        );
+        assert!(link(Explorer::SolanaFm, cluster, LinkType::Transaction).is_err());
+    }
+
+    #[test]
+    fn builds_the_url_shape_of_each_explorer() {
+        assert_eq!(
+            link(Explorer::Solscan, Cluster::Devnet, LinkType::Address).unwrap(),
+            format!("https://solscan.io/account/{}?cluster=devnet", SIGNATURE)
+        );
+        assert_eq!(
+            link(Explorer::Solscan, Cluster::MainnetBeta, LinkType::Token).unwrap(),
+            format!("https://solscan.io/token/{}", SIGNATURE)
+        );
+        assert_eq!(
+            link(Explorer::SolanaFm, Cluster::Testnet, LinkType::Token).unwrap(),
+            format!("https://solana.fm/address/{}?cluster=testnet-solana", SIGNATURE)
+        );
+        assert_eq!(
+            link(Explorer::Xray, Cluster::Devnet, LinkType::Transaction).unwrap(),
+            format!("https://xray.helius.xyz/tx/{}?network=devnet", SIGNATURE)
+        );
+        assert!(link(Explorer::Xray, Cluster::Devnet, LinkType::Block).is_err());
+        assert!(link(Explorer::Xray, Cluster::Testnet, LinkType::Transaction).is_err());
+        assert_eq!(
+            ExplorerLink::new(Cluster::Testnet).explorer(Explorer::Xray).link_or_default(LinkType::Transaction, SIGNATURE),
+            format!("https://explorer.solana.com/tx/{}?cluster=testnet", SIGNATURE)
+        );
+    }
+}
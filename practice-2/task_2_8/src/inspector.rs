@@ -0,0 +1,841 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+#[allow(deprecated)]
+use solana_sdk::{compute_budget, system_program};
+use solana_sdk::{
+    hash::hash,
+    program_option::COption,
+    pubkey,
+    pubkey::Pubkey,
+    system_instruction::SystemInstruction,
+};
+use spl_token_2022::instruction::TokenInstruction;
+
+use crate::amount::TokenAmount;
+use crate::error::Result;
+use crate::queries::decode_mint;
+use crate::token_program::TokenProgram;
+use crate::tx_envelope::TxEnvelope;
+
+/// Escrow programs of practice-3/task_3_3 and practice-4/task_4_3.
+pub const ESCROW_PROGRAM_IDS: [Pubkey; 2] = [
+    pubkey!("HSncvapKHrQYBWzkTCQRmvBPNLKoGyiwBbbhSjbJUcEu"),
+    pubkey!("9LHxMsGYu78d6KPQfyybLCEWj1sAF2nCd9CbKfefqj6E"),
+];
+
+/// Favorites programs of practice-4/task_4_1 and practice-4/task_4_2.
+pub const FAVORITES_PROGRAM_IDS: [Pubkey; 2] = [
+    pubkey!("EsL1PwU3Y7dDq8zaeiaUenZUq2NKqwgnq5ioNmqKfYLf"),
+    pubkey!("HngVG4VtZ83DshWrtQ556sez82HFZ4YmRcLCrDXN1pSv"),
+];
+
+/// `getMultipleAccounts` returns at most this many accounts per call.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Decimals of the mints a transaction touches, see `fetch_decimals`.
+pub type MintDecimals = HashMap<Pubkey, u8>;
+
+/// One instruction decoded for a signer to read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionSummary {
+    pub program_id: Pubkey,
+    /// Name of a known program, otherwise its address.
+    pub program: String,
+    pub summary: String,
+}
+
+impl fmt::Display for InstructionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.program, self.summary)
+    }
+}
+
+/// Decodes every instruction of `envelope`. Token amounts are scaled by the mints
+/// found in `decimals` and shown in base units otherwise.
+pub fn inspect(envelope: &TxEnvelope, decimals: &MintDecimals) -> Vec<InstructionSummary> {
+    let account_keys = envelope.transaction.message.static_account_keys();
+
+    envelope
+        .transaction
+        .message
+        .instructions()
+        .iter()
+        .map(|instruction| {
+            let accounts: Vec<Option<Pubkey>> = instruction
+                .accounts
+                .iter()
+                .map(|index| account_keys.get(*index as usize).copied())
+                .collect();
+            decode_instruction(
+                &account_keys[instruction.program_id_index as usize],
+                &instruction.data,
+                &accounts,
+                decimals,
+            )
+        })
+        .collect()
+}
+
+/// Looks up the decimals of every mint the instructions of `envelope` pass in.
+/// Accounts loaded from lookup tables are not resolved.
+pub async fn fetch_decimals(connection: &RpcClient, envelope: &TxEnvelope) -> Result<MintDecimals> {
+    let account_keys = envelope.transaction.message.static_account_keys();
+    let mut candidates = HashSet::new();
+
+    for instruction in envelope.transaction.message.instructions() {
+        let program_id = account_keys[instruction.program_id_index as usize];
+        let takes_mints = TokenProgram::from_program_id(&program_id).is_some()
+            || program_id == spl_associated_token_account::id()
+            || program_id == mpl_token_metadata::ID
+            || ESCROW_PROGRAM_IDS.contains(&program_id);
+
+        if takes_mints {
+            candidates.extend(instruction.accounts.iter().filter_map(|index| account_keys.get(*index as usize).copied()));
+        }
+    }
+
+    // Token accounts and everything else fail to decode as a mint and are skipped.
+    let candidates: Vec<Pubkey> = candidates.into_iter().collect();
+    let mut decimals = MintDecimals::new();
+
+    for chunk in candidates.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let accounts = connection.get_multiple_accounts(chunk).await?;
+
+        for (address, account) in chunk.iter().zip(accounts) {
+            if let Some(mint) = account.and_then(|account| decode_mint(address, &account).ok()) {
+                decimals.insert(*address, mint.decimals);
+            }
+        }
+    }
+
+    Ok(decimals)
+}
+
+/// Renders one instruction. `accounts` follows the instruction's account order,
+/// with `None` for accounts loaded from a lookup table.
+pub fn decode_instruction(
+    program_id: &Pubkey,
+    data: &[u8],
+    accounts: &[Option<Pubkey>],
+    decimals: &MintDecimals,
+) -> InstructionSummary {
+    let accounts = Accounts(accounts);
+
+    let (program, summary) = if *program_id == system_program::id() {
+        ("system".to_string(), decode_system(data, &accounts))
+    } else if let Some(token_program) = TokenProgram::from_program_id(program_id) {
+        (token_program.to_string(), decode_token(data, &accounts, decimals))
+    } else if *program_id == spl_associated_token_account::id() {
+        ("associated token account".to_string(), decode_associated_token_account(data, &accounts))
+    } else if *program_id == spl_memo::id() {
+        ("memo".to_string(), format!("\"{}\"", String::from_utf8_lossy(data)))
+    } else if *program_id == compute_budget::id() {
+        ("compute budget".to_string(), decode_compute_budget(data))
+    } else if *program_id == mpl_token_metadata::ID {
+        ("metaplex token metadata".to_string(), decode_metaplex(data, &accounts))
+    } else if ESCROW_PROGRAM_IDS.contains(program_id) {
+        ("escrow".to_string(), decode_escrow(data, &accounts, decimals))
+    } else if FAVORITES_PROGRAM_IDS.contains(program_id) {
+        ("favorites".to_string(), decode_favorites(data, &accounts))
+    } else {
+        (
+            program_id.to_string(),
+            format!("unknown instruction, {} accounts and {} bytes of data", accounts.0.len(), data.len()),
+        )
+    };
+
+    InstructionSummary {
+        program_id: *program_id,
+        program,
+        summary,
+    }
+}
+
+/// Position of the mint among the accounts of a token or associated token account
+/// instruction. `Ok(None)` for instructions of other programs and for the ones that
+/// only touch existing accounts whatever their mint, `Err` when the mint isn't passed.
+#[allow(deprecated)]
+pub(crate) fn mint_position(program_id: &Pubkey, data: &[u8]) -> std::result::Result<Option<usize>, &'static str> {
+    if TokenProgram::from_program_id(program_id).is_some() {
+        return match TokenInstruction::unpack(data) {
+            Ok(TokenInstruction::MintTo { .. } | TokenInstruction::MintToChecked { .. }) => Ok(Some(0)),
+            Ok(
+                TokenInstruction::TransferChecked { .. }
+                | TokenInstruction::ApproveChecked { .. }
+                | TokenInstruction::Burn { .. }
+                | TokenInstruction::BurnChecked { .. }
+                | TokenInstruction::InitializeAccount
+                | TokenInstruction::InitializeAccount2 { .. }
+                | TokenInstruction::InitializeAccount3 { .. }
+                | TokenInstruction::FreezeAccount
+                | TokenInstruction::ThawAccount,
+            ) => Ok(Some(1)),
+            Ok(TokenInstruction::CloseAccount | TokenInstruction::Revoke | TokenInstruction::SyncNative) => Ok(None),
+            _ => Err("token instruction without a mint to check, use the checked variant"),
+        };
+    }
+
+    if *program_id == spl_associated_token_account::id() {
+        // Create and CreateIdempotent take the mint fourth.
+        return match data {
+            [] | [0] | [1] => Ok(Some(3)),
+            _ => Err("only creating associated token accounts can be checked against a mint"),
+        };
+    }
+
+    Ok(None)
+}
+
+/// The accounts of one instruction, by position.
+struct Accounts<'a>(&'a [Option<Pubkey>]);
+
+impl Accounts<'_> {
+    fn key(&self, position: usize) -> Option<Pubkey> {
+        self.0.get(position).copied().flatten()
+    }
+
+    fn show(&self, position: usize) -> String {
+        match self.0.get(position) {
+            Some(Some(account)) => account.to_string(),
+            Some(None) => "a lookup table account".to_string(),
+            None => "a missing account".to_string(),
+        }
+    }
+}
+
+fn sol(lamports: u64) -> String {
+    format!("{} SOL", TokenAmount::from_lamports(lamports))
+}
+
+/// `raw` scaled by the decimals of `mint`, or base units when they are unknown.
+fn tokens(raw: u64, mint: Option<Pubkey>, decimals: &MintDecimals) -> String {
+    match mint {
+        Some(mint) => match decimals.get(&mint) {
+            Some(decimals) => format!("{} of mint {}", TokenAmount::from_raw(raw, *decimals), mint),
+            None => format!("{} base units of mint {}", raw, mint),
+        },
+        None => format!("{} base units", raw),
+    }
+}
+
+fn decode_system(data: &[u8], accounts: &Accounts) -> String {
+    let Ok(instruction) = bincode::deserialize::<SystemInstruction>(data) else {
+        return "unknown instruction".to_string();
+    };
+
+    match instruction {
+        SystemInstruction::Transfer { lamports } => {
+            format!("transfer {} from {} to {}", sol(lamports), accounts.show(0), accounts.show(1))
+        }
+        SystemInstruction::TransferWithSeed { lamports, from_seed, .. } => format!(
+            "transfer {} from {} (seed \"{}\") to {}",
+            sol(lamports),
+            accounts.show(0),
+            from_seed,
+            accounts.show(2)
+        ),
+        SystemInstruction::CreateAccount { lamports, space, owner } => format!(
+            "create account {} with {} bytes owned by {}, funded by {} with {}",
+            accounts.show(1),
+            space,
+            owner,
+            accounts.show(0),
+            sol(lamports)
+        ),
+        SystemInstruction::CreateAccountWithSeed { seed, lamports, space, owner, .. } => format!(
+            "create account {} (seed \"{}\") with {} bytes owned by {}, funded by {} with {}",
+            accounts.show(1),
+            seed,
+            space,
+            owner,
+            accounts.show(0),
+            sol(lamports)
+        ),
+        SystemInstruction::Assign { owner } => format!("assign {} to program {}", accounts.show(0), owner),
+        SystemInstruction::Allocate { space } => format!("allocate {} bytes for {}", space, accounts.show(0)),
+        SystemInstruction::AdvanceNonceAccount => {
+            format!("advance nonce {} as {}", accounts.show(0), accounts.show(2))
+        }
+        SystemInstruction::WithdrawNonceAccount(lamports) => format!(
+            "withdraw {} from nonce {} to {} as {}",
+            sol(lamports),
+            accounts.show(0),
+            accounts.show(1),
+            accounts.show(4)
+        ),
+        SystemInstruction::InitializeNonceAccount(authority) => {
+            format!("initialize nonce {} with authority {}", accounts.show(0), authority)
+        }
+        SystemInstruction::AuthorizeNonceAccount(authority) => format!(
+            "hand nonce {} over to {} as {}",
+            accounts.show(0),
+            authority,
+            accounts.show(1)
+        ),
+        SystemInstruction::UpgradeNonceAccount => format!("upgrade nonce {}", accounts.show(0)),
+        other => format!("{:?}", other),
+    }
+}
+
+#[allow(deprecated)]
+fn decode_token(data: &[u8], accounts: &Accounts, decimals: &MintDecimals) -> String {
+    let Ok(instruction) = TokenInstruction::unpack(data) else {
+        return "unknown instruction".to_string();
+    };
+
+    match instruction {
+        // The unchecked variants don't pass the mint, so the amount stays in base units.
+        TokenInstruction::Transfer { amount } => format!(
+            "transfer {} from {} to {} as {} (unchecked)",
+            tokens(amount, None, decimals),
+            accounts.show(0),
+            accounts.show(1),
+            accounts.show(2)
+        ),
+        TokenInstruction::TransferChecked { amount, decimals: mint_decimals } => format!(
+            "transfer {} from {} to {} as {}",
+            checked_tokens(amount, mint_decimals, accounts.key(1)),
+            accounts.show(0),
+            accounts.show(2),
+            accounts.show(3)
+        ),
+        TokenInstruction::Approve { amount } => format!(
+            "approve {} to spend {} from {} as {} (unchecked)",
+            accounts.show(1),
+            tokens(amount, None, decimals),
+            accounts.show(0),
+            accounts.show(2)
+        ),
+        TokenInstruction::ApproveChecked { amount, decimals: mint_decimals } => format!(
+            "approve {} to spend {} from {} as {}",
+            accounts.show(2),
+            checked_tokens(amount, mint_decimals, accounts.key(1)),
+            accounts.show(0),
+            accounts.show(3)
+        ),
+        TokenInstruction::Revoke => format!("revoke the delegate of {} as {}", accounts.show(0), accounts.show(1)),
+        TokenInstruction::MintTo { amount } => format!(
+            "mint {} to {} as {}",
+            tokens(amount, accounts.key(0), decimals),
+            accounts.show(1),
+            accounts.show(2)
+        ),
+        TokenInstruction::MintToChecked { amount, decimals: mint_decimals } => format!(
+            "mint {} to {} as {}",
+            checked_tokens(amount, mint_decimals, accounts.key(0)),
+            accounts.show(1),
+            accounts.show(2)
+        ),
+        TokenInstruction::Burn { amount } => format!(
+            "burn {} from {} as {}",
+            tokens(amount, accounts.key(1), decimals),
+            accounts.show(0),
+            accounts.show(2)
+        ),
+        TokenInstruction::BurnChecked { amount, decimals: mint_decimals } => format!(
+            "burn {} from {} as {}",
+            checked_tokens(amount, mint_decimals, accounts.key(1)),
+            accounts.show(0),
+            accounts.show(2)
+        ),
+        TokenInstruction::SetAuthority { authority_type, new_authority } => format!(
+            "set {:?} authority of {} to {} as {}",
+            authority_type,
+            accounts.show(0),
+            optional_authority(new_authority),
+            accounts.show(1)
+        ),
+        TokenInstruction::CloseAccount => format!(
+            "close {} and send its rent to {} as {}",
+            accounts.show(0),
+            accounts.show(1),
+            accounts.show(2)
+        ),
+        TokenInstruction::FreezeAccount => {
+            format!("freeze {} of mint {} as {}", accounts.show(0), accounts.show(1), accounts.show(2))
+        }
+        TokenInstruction::ThawAccount => {
+            format!("thaw {} of mint {} as {}", accounts.show(0), accounts.show(1), accounts.show(2))
+        }
+        TokenInstruction::InitializeMint { decimals, mint_authority, freeze_authority }
+        | TokenInstruction::InitializeMint2 { decimals, mint_authority, freeze_authority } => format!(
+            "initialize mint {} with {} decimals, mint authority {} and freeze authority {}",
+            accounts.show(0),
+            decimals,
+            mint_authority,
+            optional_authority(freeze_authority)
+        ),
+        TokenInstruction::InitializeAccount => format!(
+            "initialize token account {} of mint {} for {}",
+            accounts.show(0),
+            accounts.show(1),
+            accounts.show(2)
+        ),
+        TokenInstruction::InitializeAccount2 { owner } | TokenInstruction::InitializeAccount3 { owner } => format!(
+            "initialize token account {} of mint {} for {}",
+            accounts.show(0),
+            accounts.show(1),
+            owner
+        ),
+        TokenInstruction::InitializeMultisig { m } | TokenInstruction::InitializeMultisig2 { m } => format!(
+            "initialize multisig {} requiring {} of {} signers",
+            accounts.show(0),
+            m,
+            accounts.0.len().saturating_sub(1)
+        ),
+        TokenInstruction::SyncNative => format!("sync the SOL balance of {}", accounts.show(0)),
+        other => format!("{:?}", other),
+    }
+}
+
+/// The checked variants carry the decimals themselves, the program rejects them if they don't match the mint.
+fn checked_tokens(amount: u64, decimals: u8, mint: Option<Pubkey>) -> String {
+    let amount = TokenAmount::from_raw(amount, decimals);
+
+    match mint {
+        Some(mint) => format!("{} of mint {}", amount, mint),
+        None => format!("{} of a lookup table mint", amount),
+    }
+}
+
+fn optional_authority(authority: COption<Pubkey>) -> String {
+    match authority {
+        COption::Some(authority) => authority.to_string(),
+        COption::None => "nobody".to_string(),
+    }
+}
+
+fn decode_associated_token_account(data: &[u8], accounts: &Accounts) -> String {
+    // Payer, associated account, wallet, mint.
+    let kind = match data {
+        [] | [0] => "create",
+        [1] => "create if missing",
+        [2] => return format!("recover nested token account {} of {}", accounts.show(0), accounts.show(5)),
+        _ => return "unknown instruction".to_string(),
+    };
+
+    format!(
+        "{} {} of mint {} for {}, paid by {}",
+        kind,
+        accounts.show(1),
+        accounts.show(3),
+        accounts.show(2),
+        accounts.show(0)
+    )
+}
+
+fn decode_compute_budget(data: &[u8]) -> String {
+    // Tag byte followed by a little endian value, see `ComputeBudgetInstruction`.
+    match data {
+        [1, bytes @ ..] if bytes.len() == 4 => {
+            format!("request a {} byte heap", u32::from_le_bytes(bytes.try_into().unwrap()))
+        }
+        [2, limit @ ..] if limit.len() == 4 => {
+            format!("limit to {} compute units", u32::from_le_bytes(limit.try_into().unwrap()))
+        }
+        [3, price @ ..] if price.len() == 8 => {
+            format!("pay {} micro-lamports per compute unit", u64::from_le_bytes(price.try_into().unwrap()))
+        }
+        [4, bytes @ ..] if bytes.len() == 4 => {
+            format!("limit loaded accounts to {} bytes", u32::from_le_bytes(bytes.try_into().unwrap()))
+        }
+        _ => "unknown instruction".to_string(),
+    }
+}
+
+fn decode_metaplex(data: &[u8], accounts: &Accounts) -> String {
+    let (decoded, mint) = match data {
+        // CreateMetadataAccountV3: metadata, mint, mint authority, payer, update authority.
+        [33, args @ ..] => (name_symbol_uri(&mut BorshReader(args)).map(|data| format!("create metadata {}", data)), 1),
+        // CreateV1: metadata, master edition, mint, authority, payer, update authority.
+        [42, 0, args @ ..] => (name_symbol_uri(&mut BorshReader(args)).map(|data| format!("create {}", data)), 2),
+        // UpdateMetadataAccountV2: metadata, update authority. The mint isn't passed.
+        [15, args @ ..] => {
+            let mut reader = BorshReader(args);
+            let decoded = reader
+                .option(data_v2)
+                .zip(reader.option(BorshReader::pubkey))
+                .map(|(data, authority)| describe_update(data, authority));
+            return match decoded {
+                Some(update) => format!("update metadata {} as {}", accounts.show(0), update),
+                None => format!("update metadata {} as {}", accounts.show(0), accounts.show(1)),
+            };
+        }
+        // UpdateV1: authority, delegate record, token, mint, metadata.
+        [50, 0, args @ ..] => {
+            let mut reader = BorshReader(args);
+            let decoded = reader
+                .option(BorshReader::pubkey)
+                .and_then(|authority| Some((reader.option(name_symbol_uri)?, authority)))
+                .map(|(data, authority)| describe_update(data, authority));
+            (decoded.map(|update| format!("update metadata {}", update)), 3)
+        }
+        [discriminator, ..] => return format!("instruction #{}", discriminator),
+        [] => return "unknown instruction".to_string(),
+    };
+
+    match decoded {
+        Some(decoded) => format!("{} for mint {}", decoded, accounts.show(mint)),
+        None => format!("malformed instruction for mint {}", accounts.show(mint)),
+    }
+}
+
+fn describe_update(data: Option<String>, new_update_authority: Option<Pubkey>) -> String {
+    let mut changes = Vec::new();
+    if let Some(data) = data {
+        changes.push(format!("to {}", data));
+    }
+    if let Some(authority) = new_update_authority {
+        changes.push(format!("handing update authority to {}", authority));
+    }
+    if changes.is_empty() {
+        changes.push("without changing name or authority".to_string());
+    }
+
+    changes.join(", ")
+}
+
+/// `DataV2` of which only name, symbol and uri are shown, the rest is skipped to get past it.
+fn data_v2(reader: &mut BorshReader) -> Option<String> {
+    let data = name_symbol_uri(reader)?;
+
+    // Seller fee basis points, then creators (address, verified, share), collection (verified, key)
+    // and uses (method, remaining, total).
+    reader.take(2)?;
+    if let Some(creators) = reader.option(BorshReader::u32)? {
+        reader.take(creators as usize * 34)?;
+    }
+    reader.option(|reader| reader.take(33).map(|_| ()))?;
+    reader.option(|reader| reader.take(17).map(|_| ()))?;
+
+    Some(data)
+}
+
+/// Name, symbol and uri start every Metaplex `Data`, `DataV2` and `CreateV1` argument list.
+fn name_symbol_uri(reader: &mut BorshReader) -> Option<String> {
+    let name = reader.string()?;
+    let symbol = reader.string()?;
+    let uri = reader.string()?;
+
+    Some(format!("\"{}\" ({}) at {}", name, symbol, uri))
+}
+
+fn decode_escrow(data: &[u8], accounts: &Accounts, decimals: &MintDecimals) -> String {
+    let Some((name, mut args)) = anchor_instruction(data, &["make_offer", "take_offer", "cancel_offer"]) else {
+        return "unknown instruction".to_string();
+    };
+
+    match name {
+        // Maker, mint a, mint b, ...
+        "make_offer" => match (args.u64(), args.u64(), args.u64()) {
+            (Some(id), Some(offered), Some(wanted)) => format!(
+                "offer #{} by {}: give {} for {}",
+                id,
+                accounts.show(0),
+                tokens(offered, accounts.key(1), decimals),
+                tokens(wanted, accounts.key(2), decimals)
+            ),
+            _ => "malformed make_offer".to_string(),
+        },
+        // Taker, maker, mint a, mint b, ...
+        "take_offer" => format!(
+            "take the offer of {} as {}: pay in mint {}, receive mint {}",
+            accounts.show(1),
+            accounts.show(0),
+            accounts.show(3),
+            accounts.show(2)
+        ),
+        _ => format!(
+            "cancel the offer of {} for mint {} and return the offered tokens",
+            accounts.show(0),
+            accounts.show(1)
+        ),
+    }
+}
+
+fn decode_favorites(data: &[u8], accounts: &Accounts) -> String {
+    let names = [
+        "set_favorites",
+        "set_favorites_v1",
+        "update_favorites",
+        "update_favorites_multi_versions",
+        "set_authority",
+    ];
+    let Some((name, mut args)) = anchor_instruction(data, &names) else {
+        return "unknown instruction".to_string();
+    };
+
+    let decoded = match name {
+        "set_favorites" | "set_favorites_v1" => args
+            .u64()
+            .zip(args.string())
+            .map(|(number, color)| format!("{} number {} and color \"{}\"", name, number, color)),
+        "update_favorites" | "update_favorites_multi_versions" => {
+            args.option(BorshReader::u64).zip(args.option(BorshReader::string)).map(|(number, color)| {
+                let number = number.map_or("unchanged".to_string(), |number| number.to_string());
+                let color = color.map_or("unchanged".to_string(), |color| format!("\"{}\"", color));
+                format!("{} number {} and color {}", name, number, color)
+            })
+        }
+        _ => args.option(BorshReader::pubkey).map(|authority| match authority {
+            Some(authority) => format!("let {} update the favorites", authority),
+            None => "remove the extra favorites authority".to_string(),
+        }),
+    };
+
+    match decoded {
+        Some(decoded) => format!("{} as {}", decoded, accounts.show(0)),
+        None => format!("malformed {}", name),
+    }
+}
+
+/// Matches the 8 byte Anchor discriminator, `sha256("global:<name>")`, against `names`.
+fn anchor_instruction<'a, 'd>(data: &'d [u8], names: &[&'a str]) -> Option<(&'a str, BorshReader<'d>)> {
+    let (discriminator, args) = data.split_at_checked(8)?;
+
+    names
+        .iter()
+        .find(|name| hash(format!("global:{}", name).as_bytes()).to_bytes()[..8] == *discriminator)
+        .map(|name| (*name, BorshReader(args)))
+}
+
+/// Reads the few Borsh types the instructions above take, `None` once the data runs out.
+struct BorshReader<'a>(&'a [u8]);
+
+impl BorshReader<'_> {
+    fn take(&mut self, len: usize) -> Option<&[u8]> {
+        let (taken, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+
+        self.take(len).map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn pubkey(&mut self) -> Option<Pubkey> {
+        self.take(32).map(|bytes| Pubkey::try_from(bytes).unwrap())
+    }
+
+    fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match self.u8()? {
+            0 => Some(None),
+            1 => read(self).map(Some),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_token_metadata::instructions::{
+        CreateMetadataAccountV3Builder, CreateV1Builder, UpdateMetadataAccountV2Builder, UpdateV1Builder,
+    };
+    use mpl_token_metadata::types::{Collection, Creator, Data, DataV2, TokenStandard, UseMethod, Uses};
+    use solana_sdk::instruction::{AccountMeta, Instruction};
+    use spl_associated_token_account::instruction::{
+        create_associated_token_account, create_associated_token_account_idempotent, recover_nested,
+    };
+    use spl_token_2022::instruction as token;
+
+    use super::*;
+
+    fn summary(instruction: &Instruction, decimals: &MintDecimals) -> String {
+        let accounts: Vec<_> = instruction.accounts.iter().map(|meta| Some(meta.pubkey)).collect();
+
+        decode_instruction(&instruction.program_id, &instruction.data, &accounts, decimals).summary
+    }
+
+    /// Fills every part of `DataV2` the decoder has to skip: two creators, a collection and uses.
+    fn data_v2() -> DataV2 {
+        DataV2 {
+            name: "Name".to_string(),
+            symbol: "SYM".to_string(),
+            uri: "https://x".to_string(),
+            seller_fee_basis_points: 500,
+            creators: Some(vec![
+                Creator { address: Pubkey::new_unique(), verified: true, share: 60 },
+                Creator { address: Pubkey::new_unique(), verified: false, share: 40 },
+            ]),
+            collection: Some(Collection { verified: false, key: Pubkey::new_unique() }),
+            uses: Some(Uses { use_method: UseMethod::Multiple, remaining: 1, total: 2 }),
+        }
+    }
+
+    #[test]
+    fn decodes_metaplex_create_and_update() {
+        let metadata = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let new_authority = Pubkey::new_unique();
+        let decimals = MintDecimals::new();
+
+        let create_v3 = CreateMetadataAccountV3Builder::new()
+            .metadata(metadata)
+            .mint(mint)
+            .mint_authority(authority)
+            .payer(authority)
+            .update_authority(authority, true)
+            .data(data_v2())
+            .is_mutable(true)
+            .instruction();
+        assert_eq!(create_v3.data[0], 33);
+        assert_eq!(summary(&create_v3, &decimals), format!("create metadata \"Name\" (SYM) at https://x for mint {}", mint));
+
+        let create_v1 = CreateV1Builder::new()
+            .metadata(metadata)
+            .mint(mint, false)
+            .authority(authority)
+            .payer(authority)
+            .update_authority(authority, true)
+            .name("Name".to_string())
+            .symbol("SYM".to_string())
+            .uri("https://x".to_string())
+            .seller_fee_basis_points(0)
+            .token_standard(TokenStandard::Fungible)
+            .instruction();
+        assert_eq!(create_v1.data[..2], [42, 0]);
+        assert_eq!(summary(&create_v1, &decimals), format!("create \"Name\" (SYM) at https://x for mint {}", mint));
+
+        let update_v2 = UpdateMetadataAccountV2Builder::new()
+            .metadata(metadata)
+            .update_authority(authority)
+            .data(data_v2())
+            .new_update_authority(new_authority)
+            .instruction();
+        assert_eq!(update_v2.data[0], 15);
+        assert_eq!(
+            summary(&update_v2, &decimals),
+            format!(
+                "update metadata {} as to \"Name\" (SYM) at https://x, handing update authority to {}",
+                metadata, new_authority
+            )
+        );
+
+        let update_v1 = UpdateV1Builder::new()
+            .authority(authority)
+            .mint(mint)
+            .metadata(metadata)
+            .payer(authority)
+            .new_update_authority(new_authority)
+            .data(Data {
+                name: "Name".to_string(),
+                symbol: "SYM".to_string(),
+                uri: "https://x".to_string(),
+                seller_fee_basis_points: 0,
+                creators: None,
+            })
+            .instruction();
+        assert_eq!(update_v1.data[..2], [50, 0]);
+        assert_eq!(
+            summary(&update_v1, &decimals),
+            format!(
+                "update metadata to \"Name\" (SYM) at https://x, handing update authority to {} for mint {}",
+                new_authority, mint
+            )
+        );
+
+        let unknown = Instruction::new_with_bytes(mpl_token_metadata::ID, &[99], Vec::new());
+        assert_eq!(summary(&unknown, &decimals), "instruction #99");
+    }
+
+    #[test]
+    fn decodes_anchor_make_offer() {
+        let maker = Pubkey::new_unique();
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+        let decimals = MintDecimals::from([(mint_a, 6)]);
+
+        let mut data = hash(b"global:make_offer").to_bytes()[..8].to_vec();
+        for value in [7u64, 1_500_000, 42] {
+            data.extend(value.to_le_bytes());
+        }
+        let accounts = [maker, mint_a, mint_b].map(|account| AccountMeta::new(account, false));
+        let make_offer = Instruction::new_with_bytes(ESCROW_PROGRAM_IDS[0], &data, accounts.to_vec());
+
+        assert_eq!(
+            summary(&make_offer, &decimals),
+            format!("offer #7 by {}: give 1.5 of mint {} for 42 base units of mint {}", maker, mint_a, mint_b)
+        );
+
+        data[0] ^= 0xff;
+        let unknown = Instruction::new_with_bytes(ESCROW_PROGRAM_IDS[0], &data, accounts.to_vec());
+        assert_eq!(summary(&unknown, &decimals), "unknown instruction");
+    }
+
+    #[test]
+    fn borsh_options_need_a_valid_tag() {
+        assert_eq!(BorshReader(&[0]).option(BorshReader::u8), Some(None));
+        assert_eq!(BorshReader(&[1, 5]).option(BorshReader::u8), Some(Some(5)));
+        assert_eq!(BorshReader(&[1]).option(BorshReader::u8), None);
+        assert_eq!(BorshReader(&[2, 5]).option(BorshReader::u8), None);
+        assert_eq!(BorshReader(&[]).option(BorshReader::u8), None);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn finds_the_mint_of_every_token_instruction() {
+        let program = spl_token_2022::id();
+        let mint = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        let with_mint = [
+            token::mint_to(&program, &mint, &account, &owner, &[], 1).unwrap(),
+            token::mint_to_checked(&program, &mint, &account, &owner, &[], 1, 0).unwrap(),
+            token::transfer_checked(&program, &account, &mint, &other, &owner, &[], 1, 0).unwrap(),
+            token::approve_checked(&program, &account, &mint, &other, &owner, &[], 1, 0).unwrap(),
+            token::burn(&program, &account, &mint, &owner, &[], 1).unwrap(),
+            token::burn_checked(&program, &account, &mint, &owner, &[], 1, 0).unwrap(),
+            token::initialize_account(&program, &account, &mint, &owner).unwrap(),
+            token::initialize_account2(&program, &account, &mint, &owner).unwrap(),
+            token::initialize_account3(&program, &account, &mint, &owner).unwrap(),
+            token::freeze_account(&program, &account, &mint, &owner, &[]).unwrap(),
+            token::thaw_account(&program, &account, &mint, &owner, &[]).unwrap(),
+            create_associated_token_account(&owner, &owner, &mint, &program),
+            create_associated_token_account_idempotent(&owner, &owner, &mint, &program),
+        ];
+        for instruction in with_mint {
+            let position = mint_position(&instruction.program_id, &instruction.data).unwrap();
+            assert_eq!(position.map(|position| instruction.accounts[position].pubkey), Some(mint), "{:?}", instruction);
+        }
+
+        let without_mint = [
+            token::close_account(&program, &account, &owner, &owner, &[]).unwrap(),
+            token::revoke(&program, &account, &owner, &[]).unwrap(),
+            token::sync_native(&program, &account).unwrap(),
+            spl_memo::build_memo(b"memo", &[]),
+        ];
+        for instruction in without_mint {
+            assert_eq!(mint_position(&instruction.program_id, &instruction.data), Ok(None), "{:?}", instruction);
+        }
+
+        let unchecked = [
+            token::transfer(&program, &account, &other, &owner, &[], 1).unwrap(),
+            token::approve(&program, &account, &other, &owner, &[], 1).unwrap(),
+            token::set_authority(&program, &account, None, token::AuthorityType::CloseAccount, &owner, &[]).unwrap(),
+            recover_nested(&owner, &mint, &other, &program),
+        ];
+        for instruction in unchecked {
+            assert!(mint_position(&instruction.program_id, &instruction.data).is_err(), "{:?}", instruction);
+        }
+    }
+}
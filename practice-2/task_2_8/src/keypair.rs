@@ -0,0 +1,163 @@
+use std::convert::Infallible;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use bip39::{Language, Mnemonic, Seed};
+use solana_sdk::derivation_path::{DerivationPath, DerivationPathError};
+use solana_sdk::signature::{
+    keypair_from_seed, keypair_from_seed_and_derivation_path, read_keypair_file, Keypair,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeypairError {
+    #[error("Add {0} to .env!")]
+    MissingEnv(String),
+    #[error("Can't read keypair file {path}: {message}")]
+    File { path: PathBuf, message: String },
+    #[error("Can't parse secret key bytes: {0}")]
+    InvalidBytes(String),
+    #[error("Can't parse base58 secret key: {0}")]
+    InvalidBase58(#[from] bs58::decode::Error),
+    #[error("Invalid seed phrase: {0}")]
+    InvalidMnemonic(String),
+    #[error("Invalid derivation path: {0}")]
+    InvalidDerivationPath(#[from] DerivationPathError),
+}
+
+/// Where a signer comes from. Every variant ends up as a plain `Keypair`.
+#[derive(Clone, Debug)]
+pub enum KeypairSource {
+    /// JSON byte array file as written by `solana-keygen new`.
+    File(PathBuf),
+    /// Base58 encoded 64-byte secret key, as exported by Phantom.
+    Base58(String),
+    /// BIP39 seed phrase. Without a derivation path the seed is used directly,
+    /// the same way `solana-keygen recover` does by default.
+    Mnemonic {
+        phrase: String,
+        passphrase: String,
+        derivation_path: Option<DerivationPath>,
+    },
+    /// Environment variable (or `.env` entry) holding a byte array, a base58 key,
+    /// a seed phrase or a path to a keypair file. For seed phrases the derivation
+    /// path is taken from `<NAME>_DERIVATION_PATH` when it is set.
+    Env(String),
+}
+
+impl KeypairSource {
+    pub fn file(path: impl AsRef<Path>) -> Self {
+        KeypairSource::File(path.as_ref().to_path_buf())
+    }
+
+    pub fn env(key: &str) -> Self {
+        KeypairSource::Env(key.to_string())
+    }
+
+    pub fn mnemonic(phrase: &str, passphrase: &str, derivation_path: Option<&str>) -> Result<Self, KeypairError> {
+        let derivation_path = derivation_path
+            .map(DerivationPath::from_absolute_path_str)
+            .transpose()?;
+
+        Ok(KeypairSource::Mnemonic {
+            phrase: phrase.to_string(),
+            passphrase: passphrase.to_string(),
+            derivation_path,
+        })
+    }
+
+    pub fn load(&self) -> Result<Keypair, KeypairError> {
+        match self {
+            KeypairSource::File(path) => read_keypair_file(path)
+                .map_err(|error| KeypairError::File {
+                    path: path.clone(),
+                    message: error.to_string(),
+                }),
+            KeypairSource::Base58(secret) => keypair_from_base58(secret),
+            KeypairSource::Mnemonic { phrase, passphrase, derivation_path } => {
+                keypair_from_mnemonic(phrase, passphrase, derivation_path.clone())
+            }
+            KeypairSource::Env(key) => load_from_env(key),
+        }
+    }
+}
+
+/// Parses the `--keypair` style notation: `env:NAME`, `base58:KEY`,
+/// or a keypair file path (optionally prefixed with `file:`).
+impl FromStr for KeypairSource {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(key) = value.strip_prefix("env:") {
+            return Ok(KeypairSource::env(key));
+        }
+
+        if let Some(secret) = value.strip_prefix("base58:") {
+            return Ok(KeypairSource::Base58(secret.to_string()));
+        }
+
+        Ok(KeypairSource::file(value.strip_prefix("file:").unwrap_or(value)))
+    }
+}
+
+pub fn keypair_from_bytes_str(secret_key_str: &str) -> Result<Keypair, KeypairError> {
+    let secret_key_str = secret_key_str.trim().trim_matches(|c| c == '[' || c == ']');
+    let secret_key_bytes = secret_key_str
+        .split(',')
+        .map(|s| s.trim().parse::<u8>())
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|error| KeypairError::InvalidBytes(error.to_string()))?;
+
+    Keypair::try_from(secret_key_bytes.as_slice())
+        .map_err(|error| KeypairError::InvalidBytes(error.to_string()))
+}
+
+pub fn keypair_from_base58(secret: &str) -> Result<Keypair, KeypairError> {
+    let secret_key_bytes = bs58::decode(secret.trim()).into_vec()?;
+
+    Keypair::try_from(secret_key_bytes.as_slice())
+        .map_err(|error| KeypairError::InvalidBytes(error.to_string()))
+}
+
+pub fn keypair_from_mnemonic(
+    phrase: &str,
+    passphrase: &str,
+    derivation_path: Option<DerivationPath>,
+) -> Result<Keypair, KeypairError> {
+    let mnemonic = Mnemonic::from_phrase(phrase.trim(), Language::English)
+        .map_err(|error| KeypairError::InvalidMnemonic(error.to_string()))?;
+    let seed = Seed::new(&mnemonic, passphrase);
+
+    let keypair = match derivation_path {
+        Some(derivation_path) => keypair_from_seed_and_derivation_path(seed.as_bytes(), Some(derivation_path)),
+        None => keypair_from_seed(seed.as_bytes()),
+    };
+
+    keypair.map_err(|error| KeypairError::InvalidMnemonic(error.to_string()))
+}
+
+fn load_from_env(key: &str) -> Result<Keypair, KeypairError> {
+    dotenv::dotenv().ok();
+
+    let value = env::var(key).map_err(|_| KeypairError::MissingEnv(key.to_string()))?;
+    let value = value.trim();
+
+    if value.starts_with('[') {
+        return keypair_from_bytes_str(value);
+    }
+
+    if value.split_whitespace().count() > 1 {
+        let derivation_path = env::var(format!("{}_DERIVATION_PATH", key))
+            .ok()
+            .map(|path| DerivationPath::from_absolute_path_str(&path))
+            .transpose()?;
+
+        return keypair_from_mnemonic(value, "", derivation_path);
+    }
+
+    if Path::new(value).is_file() {
+        return KeypairSource::file(value).load();
+    }
+
+    keypair_from_base58(value)
+}
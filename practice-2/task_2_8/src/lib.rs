@@ -1,59 +1,81 @@
-use std::env;
-use std::str::FromStr;
-
-use mpl_token_metadata::ID as TOKEN_METADATA_PROGRAM_ID;
-use mpl_token_metadata::instructions::CreateV1Builder;
-use mpl_token_metadata::types::{DataV2, TokenStandard};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
-    native_token::LAMPORTS_PER_SOL,
-    program_pack::Pack,
+    instruction::Instruction,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    system_instruction::{create_account, transfer},
-    transaction::Transaction,
+    system_instruction::transfer,
 };
-use spl_associated_token_account::get_associated_token_address_with_program_id;
 use spl_associated_token_account::instruction::create_associated_token_account;
 use spl_memo::build_memo;
-use spl_token_2022::{
-    id as token_2022_program_id,
-    instruction::{initialize_mint, mint_to},
-    state::Mint
+use spl_token_2022::instruction::mint_to_checked;
+
+use crate::amount::{TokenAmount, SOL_DECIMALS};
+use crate::cluster::{cluster_from_rpc_url, ClusterConfig};
+use crate::error::{parse_pubkey, Result};
+use crate::explorer_link::{Explorer, ExplorerLink, LinkType};
+use crate::keypair::KeypairSource;
+use crate::metadata::{
+    metaplex_create_instruction, metaplex_metadata_address, metaplex_update_instruction,
+    token_extension_create_instructions, token_extension_update_instructions, MetadataArgs,
 };
+use crate::mint_builder::MintBuilder;
+use crate::results::{
+    CreatedMetadata, CreatedMint, CreatedTokenAccount, MintedTokens, SolTransfer, TransactionReceipt,
+    UpdatedMetadata,
+};
+use crate::simulation::{simulate_instructions, SimulationReport};
+use crate::token_program::TokenProgram;
+use crate::tx_sender::TxSender;
+
+pub mod amount;
+pub mod cluster;
+pub mod distribution;
+pub mod error;
+pub mod explorer_link;
+pub mod inspector;
+pub mod keypair;
+pub mod lookup_table;
+pub mod metadata;
+pub mod mint_builder;
+pub mod multisig;
+pub mod nonce;
+pub mod offline_signing;
+pub mod queries;
+pub mod results;
+pub mod scheduler;
+pub mod signing_policy;
+pub mod simulation;
+pub mod sponsor;
+pub mod token_program;
+pub mod tx_envelope;
+pub mod tx_sender;
+
+pub fn load_env_keypair() -> Result<Keypair> {
+    Ok(KeypairSource::env("SECRET_KEY").load()?)
+}
 
-use crate::explorer_link::get_explorer_link;
-
-mod explorer_link;
-
-pub fn load_env_keypair() -> Option<Keypair> {
-    dotenv::dotenv().ok();
-
-    let secret_key_str = env::var("SECRET_KEY")
-        .expect("Add SECRET_KEY to .env!");
+pub fn get_connection() -> Result<RpcClient> {
+    Ok(ClusterConfig::resolve()?.connection())
+}
 
-    let secret_key_str = secret_key_str.trim_matches(|c| c == '[' || c == ']');
-    let secret_key_bytes: Vec<u8> = secret_key_str
-        .split(',')
-        .map(|s| s.trim().parse::<u8>().expect("Can't parse secret_key string"))
-        .collect();
+/// Every helper sends through a `TxSender` configured from the environment.
+pub(crate) async fn send_instructions(
+    connection: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&Keypair],
+) -> Result<TransactionReceipt> {
+    let sent = TxSender::from_env()?.send(connection, instructions, payer, signers).await?;
 
-    match Keypair::from_bytes(secret_key_bytes.as_ref()) {
-        Ok(keypair) => {
-            Some(keypair)
-        }
-        Err(_) => {
-            None
-        }
-    }
+    Ok(sent.receipt)
 }
 
-pub fn get_connection() -> RpcClient {
-    RpcClient::new_with_commitment(
-        "https://api.devnet.solana.com".to_string(),
-        CommitmentConfig::confirmed(),
-    )
+/// Links in results point at the explorer picked by `SOLANA_EXPLORER`, falling back
+/// to explorer.solana.com when that is invalid or can't show the cluster.
+pub(crate) fn explorer_url(connection: &RpcClient, link_type: LinkType, id: impl ToString) -> String {
+    ExplorerLink::new(cluster_from_rpc_url(&connection.url()))
+        .explorer(Explorer::from_env().unwrap_or_default())
+        .link_or_default(link_type, &id.to_string())
 }
 
 pub fn show_public_key(pubkey: Pubkey) {
@@ -64,225 +86,304 @@ pub async fn send_sol_with_memo(
     connection: &RpcClient,
     sender: &Keypair,
     recipient_str: &str,
-    amount: f64,
+    amount: TokenAmount,
+    memo_text: &str,
+) -> Result<SolTransfer> {
+    let (recipient, ixs) = sol_transfer_instructions(sender, recipient_str, amount, memo_text)?;
+
+    let receipt = send_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await?;
+
+    Ok(SolTransfer {
+        recipient,
+        lamports: amount.raw(),
+        receipt,
+    })
+}
+
+pub async fn simulate_send_sol_with_memo(
+    connection: &RpcClient,
+    sender: &Keypair,
+    recipient_str: &str,
+    amount: TokenAmount,
+    memo_text: &str,
+) -> Result<SimulationReport> {
+    let (_, ixs) = sol_transfer_instructions(sender, recipient_str, amount, memo_text)?;
+
+    simulate_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await
+}
+
+fn sol_transfer_instructions(
+    sender: &Keypair,
+    recipient_str: &str,
+    amount: TokenAmount,
     memo_text: &str,
-) -> anyhow::Result<()> {
-    show_public_key(sender.pubkey());
+) -> Result<(Pubkey, Vec<Instruction>)> {
+    amount.ensure_decimals(SOL_DECIMALS)?;
 
-    let recipient = Pubkey::from_str_const(recipient_str);
-    println!("💸 Attempting to send {} SOL to {}...", amount, recipient);
+    let recipient = parse_pubkey(recipient_str)?;
 
     let send_sol_ix = transfer(
         &sender.pubkey(),
         &recipient,
-        (amount * LAMPORTS_PER_SOL as f64) as u64,
+        amount.raw(),
     );
 
-    println!("📝 memo is: {}", memo_text);
-
     let add_memo_ix = build_memo(
         memo_text.as_ref(),
         &[&sender.pubkey()],
     );
 
-    let mut transaction = Transaction::new_with_payer(
-        &[send_sol_ix, add_memo_ix],
-        Some(&sender.pubkey()),
-    );
-    transaction.sign(&[sender], connection.get_latest_blockhash().await?);
-
-    match connection.send_and_confirm_transaction(&transaction).await {
-        Ok(signature) => println!("✅ Transaction confirmed, signature: {} !", signature),
-        Err(err) => eprintln!("Error sending transaction: {}", err),
-    };
-
-    Ok(())
+    Ok((recipient, vec![send_sol_ix, add_memo_ix]))
 }
 
 pub async fn create_token_mint(
     connection: &RpcClient,
     sender: &Keypair,
-) -> anyhow::Result<()> {
-    show_public_key(sender.pubkey());
+    decimals: u8,
+) -> Result<CreatedMint> {
+    MintBuilder::new(sender.pubkey(), decimals)
+        .freeze_authority(sender.pubkey())
+        .create(connection, sender)
+        .await
+}
 
-    let token_mint = Keypair::new();
+pub async fn simulate_create_token_mint(
+    connection: &RpcClient,
+    sender: &Keypair,
+    decimals: u8,
+) -> Result<SimulationReport> {
+    MintBuilder::new(sender.pubkey(), decimals)
+        .freeze_authority(sender.pubkey())
+        .simulate(connection, sender)
+        .await
+}
 
-    let mint_space = Mint::LEN;
-    let rent = connection.get_minimum_balance_for_rent_exemption(mint_space).await?;
+pub async fn create_token_account(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint_str: &str,
+    recipient_str: &str,
+) -> Result<CreatedTokenAccount> {
+    let token_mint = parse_pubkey(token_mint_str)?;
 
-    let create_account_ix = create_account(
-        &sender.pubkey(),
-        &token_mint.pubkey(),
-        rent,
-        mint_space as u64,
-        &token_2022_program_id(),
-    );
+    let recipient = parse_pubkey(recipient_str)?;
 
-    let initialize_mint_ix = initialize_mint(
-        &token_2022_program_id(),
-        &token_mint.pubkey(),
-        &sender.pubkey(),
-        Some(&sender.pubkey()),
-        2,
-    )?;
+    let token_program = TokenProgram::for_mint(connection, &token_mint).await?;
 
-    let transaction = Transaction::new_signed_with_payer(
-        &[create_account_ix, initialize_mint_ix],
-        Some(&sender.pubkey()),
-        &[&sender, &token_mint],
-        connection.get_latest_blockhash().await?,
-    );
-
-    let transaction_signature = connection.send_and_confirm_transaction(&transaction).await?;
+    let token_account = token_program.associated_token_address(&recipient, &token_mint);
 
-    println!("Mint Address: {}", token_mint.pubkey());
-    println!("Transaction Signature: {}", transaction_signature);
+    let ixs = token_account_instructions(sender, &token_mint, &recipient, token_program);
 
-    let link = get_explorer_link("address", token_mint.pubkey().to_string(), "devnet");
-    println!("✅ Token Mint: {}", link);
+    let receipt = send_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await?;
 
-    Ok(())
+    Ok(CreatedTokenAccount {
+        token_account,
+        owner: recipient,
+        mint: token_mint,
+        token_program,
+        explorer_link: explorer_url(connection, LinkType::Address, token_account),
+        receipt,
+    })
 }
 
-pub async fn create_token_account(
+pub async fn simulate_create_token_account(
     connection: &RpcClient,
     sender: &Keypair,
     token_mint_str: &str,
     recipient_str: &str,
-) -> anyhow::Result<()> {
-    show_public_key(sender.pubkey());
+) -> Result<SimulationReport> {
+    let token_mint = parse_pubkey(token_mint_str)?;
 
-    let token_mint = Pubkey::from_str_const(token_mint_str);
+    let recipient = parse_pubkey(recipient_str)?;
 
-    let recipient = Pubkey::from_str_const(recipient_str);
+    let token_program = TokenProgram::for_mint(connection, &token_mint).await?;
 
-    let token_account = get_associated_token_address_with_program_id(
-        &recipient,
-        &token_mint,
-        &spl_token_2022::id(),
-    );
+    let ixs = token_account_instructions(sender, &token_mint, &recipient, token_program);
 
-    let ixs = [
+    simulate_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await
+}
+
+fn token_account_instructions(
+    sender: &Keypair,
+    token_mint: &Pubkey,
+    recipient: &Pubkey,
+    token_program: TokenProgram,
+) -> Vec<Instruction> {
+    vec![
         create_associated_token_account(
             &sender.pubkey(),
-            &recipient,
-            &token_mint,
-            &spl_token_2022::id(),
+            recipient,
+            token_mint,
+            &token_program.id(),
         ),
-    ];
-
-    let transaction = Transaction::new_signed_with_payer(
-        &ixs,
-        Some(&sender.pubkey()),
-        &[&sender],
-        connection.get_latest_blockhash().await?,
-    );
-
-    let result = connection.send_and_confirm_transaction(&transaction).await;
+    ]
+}
 
-    match result {
-        Ok(transaction_signature) => {
-            println!("Transaction Signature: {}", transaction_signature);
-            println!("Token Account: {}", token_account.to_string());
+pub async fn mint_tokens(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint_str: &str,
+    recipient_associated_token_str: &str,
+    amount: TokenAmount,
+) -> Result<MintedTokens> {
+    let (token_mint, recipient_associated_token, ixs) =
+        mint_tokens_instructions(connection, sender, token_mint_str, recipient_associated_token_str, amount).await?;
+
+    let receipt = send_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await?;
+
+    Ok(MintedTokens {
+        mint: token_mint,
+        destination: recipient_associated_token,
+        amount,
+        receipt,
+    })
+}
 
-            let link = get_explorer_link("address", token_account.to_string(), "devnet");
-            println!("✅ Created token account: {}", link);
-        }
-        Err(e) => {
-            if e.to_string().contains("already in use") {
-                println!("Token Account: {}", token_account.to_string());
-
-                let link = get_explorer_link("address", token_account.to_string(), "devnet");
-                println!("✅ Token Account already exists: {}", link);
-            } else {
-                eprintln!("Error creating Token Account: {}", e);
-            }
-        }
-    }
+pub async fn simulate_mint_tokens(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint_str: &str,
+    recipient_associated_token_str: &str,
+    amount: TokenAmount,
+) -> Result<SimulationReport> {
+    let (_, _, ixs) =
+        mint_tokens_instructions(connection, sender, token_mint_str, recipient_associated_token_str, amount).await?;
 
-    Ok(())
+    simulate_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await
 }
 
-pub async fn mint_tokens(
+async fn mint_tokens_instructions(
     connection: &RpcClient,
     sender: &Keypair,
     token_mint_str: &str,
     recipient_associated_token_str: &str,
-    amount: u64,
-) -> anyhow::Result<()> {
-    const MINOR_UNITS_PER_MAJOR_UNITS:u64 = 10u64.pow(2);
+    amount: TokenAmount,
+) -> Result<(Pubkey, Pubkey, Vec<Instruction>)> {
+    let token_mint = parse_pubkey(token_mint_str)?;
 
-    let token_mint = Pubkey::from_str_const(token_mint_str);
+    let recipient_associated_token = parse_pubkey(recipient_associated_token_str)?;
 
-    let recipient_associated_token = Pubkey::from_str_const(recipient_associated_token_str);
+    let token_program = TokenProgram::for_mint(connection, &token_mint).await?;
 
-    let mint_to_ix = mint_to(
-        &spl_token_2022::id(),
+    // The checked variant makes the token program reject an amount scaled
+    // with decimals other than the mint's own.
+    let mint_to_ix = mint_to_checked(
+        &token_program.id(),
         &token_mint,
         &recipient_associated_token,
         &sender.pubkey(),
         &[&sender.pubkey()],
-        amount * MINOR_UNITS_PER_MAJOR_UNITS,
+        amount.raw(),
+        amount.decimals(),
     )?;
 
-    let transaction = Transaction::new_signed_with_payer(
-        &[mint_to_ix],
-        Some(&sender.pubkey()),
-        &[&sender],
-        connection.get_latest_blockhash().await?,
-    );
+    Ok((token_mint, recipient_associated_token, vec![mint_to_ix]))
+}
 
-    let signature = connection.send_and_confirm_transaction(&transaction).await?;
+pub async fn create_token_metadata(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint_str: &str,
+    metadata: impl Into<MetadataArgs>,
+) -> Result<CreatedMetadata> {
+    let token_mint = parse_pubkey(token_mint_str)?;
 
-    println!("Transaction Signature: {}", signature);
-    println!("✅ Success!");
+    let (metadata_address, ixs) = create_metadata_instructions(connection, sender, &token_mint, metadata.into()).await?;
 
-    let link = get_explorer_link("transaction", signature.to_string(), "devnet");
-    println!("Mint Token Transaction: {}", link);
+    let receipt = send_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await?;
 
-    Ok(())
+    Ok(CreatedMetadata {
+        mint: token_mint,
+        metadata: metadata_address,
+        explorer_link: explorer_url(connection, LinkType::Token, token_mint),
+        receipt,
+    })
 }
 
-pub async fn create_token_metadata(
+pub async fn simulate_create_token_metadata(
     connection: &RpcClient,
     sender: &Keypair,
     token_mint_str: &str,
-    metadata_data: DataV2
-) -> anyhow::Result<()> {
-    let token_mint = Pubkey::from_str(token_mint_str)?;
-
-    let seeds = &[
-        b"metadata",
-        TOKEN_METADATA_PROGRAM_ID.as_ref(),
-        token_mint.as_ref(),
-    ];
-    let (metadata_pda, _) = Pubkey::find_program_address(seeds, &TOKEN_METADATA_PROGRAM_ID);
-
-    let create_metadata_ix = CreateV1Builder::new()
-        .metadata(metadata_pda)
-        .mint(token_mint, false)
-        .authority(sender.pubkey())
-        .payer(sender.pubkey())
-        .update_authority(sender.pubkey(), true)
-        .token_standard(TokenStandard::Fungible)
-        .name(metadata_data.name)
-        .symbol(metadata_data.symbol)
-        .uri(metadata_data.uri)
-        .seller_fee_basis_points(metadata_data.seller_fee_basis_points)
-        .instruction();
-
-    let transaction = Transaction::new_signed_with_payer(
-        &[create_metadata_ix],
-        Some(&sender.pubkey()),
-        &[&sender],
-        connection.get_latest_blockhash().await?,
-    );
+    metadata: impl Into<MetadataArgs>,
+) -> Result<SimulationReport> {
+    let token_mint = parse_pubkey(token_mint_str)?;
 
-    let signature = connection.send_and_confirm_transaction(&transaction).await?;
-    println!("Transaction signature: {}", signature);
+    let (_, ixs) = create_metadata_instructions(connection, sender, &token_mint, metadata.into()).await?;
 
-    let token_mint_link = get_explorer_link("address", token_mint.to_string(), "devnet");
-    println!("✅ Look at the token mint again: {}", token_mint_link);
+    simulate_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await
+}
 
-    Ok(())
-}
\ No newline at end of file
+async fn create_metadata_instructions(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint: &Pubkey,
+    metadata: MetadataArgs,
+) -> Result<(Pubkey, Vec<Instruction>)> {
+    match metadata {
+        MetadataArgs::Metaplex(metadata_data) => {
+            let token_program = TokenProgram::for_mint(connection, token_mint).await?;
+
+            let create_metadata_ix =
+                metaplex_create_instruction(token_mint, &sender.pubkey(), token_program, metadata_data);
+
+            Ok((metaplex_metadata_address(token_mint), vec![create_metadata_ix]))
+        }
+        MetadataArgs::TokenExtension(metadata) => {
+            let ixs = token_extension_create_instructions(connection, &sender.pubkey(), token_mint, &metadata).await?;
+
+            Ok((*token_mint, ixs))
+        }
+    }
+}
+
+pub async fn update_token_metadata(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint_str: &str,
+    metadata: impl Into<MetadataArgs>,
+) -> Result<UpdatedMetadata> {
+    let token_mint = parse_pubkey(token_mint_str)?;
+
+    let (metadata_address, ixs) = update_metadata_instructions(connection, sender, &token_mint, metadata.into()).await?;
+
+    let receipt = send_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await?;
+
+    Ok(UpdatedMetadata {
+        mint: token_mint,
+        metadata: metadata_address,
+        receipt,
+    })
+}
+
+pub async fn simulate_update_token_metadata(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint_str: &str,
+    metadata: impl Into<MetadataArgs>,
+) -> Result<SimulationReport> {
+    let token_mint = parse_pubkey(token_mint_str)?;
+
+    let (_, ixs) = update_metadata_instructions(connection, sender, &token_mint, metadata.into()).await?;
+
+    simulate_instructions(connection, &ixs, &sender.pubkey(), &[sender]).await
+}
+
+async fn update_metadata_instructions(
+    connection: &RpcClient,
+    sender: &Keypair,
+    token_mint: &Pubkey,
+    metadata: MetadataArgs,
+) -> Result<(Pubkey, Vec<Instruction>)> {
+    match metadata {
+        MetadataArgs::Metaplex(metadata_data) => {
+            let update_metadata_ix = metaplex_update_instruction(token_mint, &sender.pubkey(), metadata_data);
+
+            Ok((metaplex_metadata_address(token_mint), vec![update_metadata_ix]))
+        }
+        MetadataArgs::TokenExtension(metadata) => {
+            let ixs = token_extension_update_instructions(connection, &sender.pubkey(), token_mint, &metadata).await?;
+
+            Ok((*token_mint, ixs))
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table::{
+        instruction::{close_lookup_table, create_lookup_table, deactivate_lookup_table, extend_lookup_table},
+        state::AddressLookupTable,
+        AddressLookupTableAccount,
+    },
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use tokio::time::sleep;
+
+use crate::error::{Result, SolanaUtilsError};
+use crate::explorer_link::LinkType;
+use crate::explorer_url;
+use crate::results::TransactionReceipt;
+use crate::send_instructions;
+
+/// Addresses appended per extend transaction, so it stays under the legacy size limit.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+const ACTIVATION_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatedLookupTable {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    pub addresses: Vec<Pubkey>,
+    pub explorer_link: String,
+    /// The create transaction followed by one per extra chunk of addresses.
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+/// Creates a lookup table owned by `authority` and fills it with `addresses`.
+pub async fn create_address_lookup_table(
+    connection: &RpcClient,
+    authority: &Keypair,
+    addresses: &[Pubkey],
+) -> Result<CreatedLookupTable> {
+    // The derivation slot has to be in the SlotHashes sysvar, a finalized one always is.
+    let recent_slot = connection
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let (create_ix, table) = create_lookup_table(authority.pubkey(), authority.pubkey(), recent_slot);
+
+    let mut chunks = addresses.chunks(MAX_ADDRESSES_PER_EXTEND);
+    let mut instructions = vec![create_ix];
+    if let Some(first_chunk) = chunks.next() {
+        instructions.push(extend_lookup_table(
+            table,
+            authority.pubkey(),
+            Some(authority.pubkey()),
+            first_chunk.to_vec(),
+        ));
+    }
+
+    let mut receipts = vec![send_instructions(connection, &instructions, &authority.pubkey(), &[authority]).await?];
+
+    for chunk in chunks {
+        receipts.push(extend_chunk(connection, authority, &table, chunk).await?);
+    }
+
+    Ok(CreatedLookupTable {
+        address: table,
+        authority: authority.pubkey(),
+        addresses: addresses.to_vec(),
+        explorer_link: explorer_url(connection, LinkType::Address, table),
+        receipts,
+    })
+}
+
+/// Appends `addresses` to `table`, skipping the ones it already holds.
+pub async fn extend_address_lookup_table(
+    connection: &RpcClient,
+    authority: &Keypair,
+    table: &Pubkey,
+    addresses: &[Pubkey],
+) -> Result<Vec<TransactionReceipt>> {
+    let existing = fetch_address_lookup_table(connection, table).await?.addresses;
+
+    let mut new_addresses: Vec<Pubkey> = Vec::new();
+    for address in addresses {
+        if !existing.contains(address) && !new_addresses.contains(address) {
+            new_addresses.push(*address);
+        }
+    }
+
+    let mut receipts = Vec::new();
+    for chunk in new_addresses.chunks(MAX_ADDRESSES_PER_EXTEND) {
+        receipts.push(extend_chunk(connection, authority, table, chunk).await?);
+    }
+
+    Ok(receipts)
+}
+
+pub async fn fetch_address_lookup_table(connection: &RpcClient, table: &Pubkey) -> Result<AddressLookupTableAccount> {
+    let account = connection.get_account(table).await?;
+    let lookup_table = decode(table, &account.data)?;
+
+    Ok(AddressLookupTableAccount {
+        key: *table,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+/// Addresses appended in a slot can only be looked up from the next one on,
+/// so wait until the table has been extended in an earlier slot than the current.
+pub async fn wait_for_address_lookup_table(connection: &RpcClient, table: &Pubkey) -> Result<AddressLookupTableAccount> {
+    loop {
+        let account = connection.get_account(table).await?;
+        let lookup_table = decode(table, &account.data)?;
+
+        if connection.get_slot().await? > lookup_table.meta.last_extended_slot {
+            return Ok(AddressLookupTableAccount {
+                key: *table,
+                addresses: lookup_table.addresses.to_vec(),
+            });
+        }
+
+        sleep(ACTIVATION_POLL_INTERVAL).await;
+    }
+}
+
+/// First step of closing a table; it can be closed once the deactivation slot leaves SlotHashes.
+pub async fn deactivate_address_lookup_table(
+    connection: &RpcClient,
+    authority: &Keypair,
+    table: &Pubkey,
+) -> Result<TransactionReceipt> {
+    let deactivate_ix = deactivate_lookup_table(*table, authority.pubkey());
+
+    send_instructions(connection, &[deactivate_ix], &authority.pubkey(), &[authority]).await
+}
+
+pub async fn close_address_lookup_table(
+    connection: &RpcClient,
+    authority: &Keypair,
+    table: &Pubkey,
+    recipient: &Pubkey,
+) -> Result<TransactionReceipt> {
+    let close_ix = close_lookup_table(*table, authority.pubkey(), *recipient);
+
+    send_instructions(connection, &[close_ix], &authority.pubkey(), &[authority]).await
+}
+
+async fn extend_chunk(
+    connection: &RpcClient,
+    authority: &Keypair,
+    table: &Pubkey,
+    addresses: &[Pubkey],
+) -> Result<TransactionReceipt> {
+    let extend_ix = extend_lookup_table(*table, authority.pubkey(), Some(authority.pubkey()), addresses.to_vec());
+
+    send_instructions(connection, &[extend_ix], &authority.pubkey(), &[authority]).await
+}
+
+fn decode<'a>(table: &Pubkey, data: &'a [u8]) -> Result<AddressLookupTable<'a>> {
+    AddressLookupTable::deserialize(data).map_err(|error| SolanaUtilsError::Decode {
+        account: *table,
+        message: error.to_string(),
+    })
+}
@@ -0,0 +1,273 @@
+use mpl_token_metadata::accounts::Metadata;
+use mpl_token_metadata::instructions::{CreateV1Builder, UpdateV1Builder};
+use mpl_token_metadata::types::{CollectionToggle, Data, DataV2, TokenStandard, UsesToggle};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    system_instruction::transfer,
+};
+use spl_pod::optional_keys::OptionalNonZeroPubkey;
+use spl_token_2022::extension::{
+    metadata_pointer::MetadataPointer, BaseStateWithExtensions, StateWithExtensions,
+};
+use spl_token_2022::state::Mint;
+use spl_token_metadata_interface::{
+    instruction::{initialize, remove_key, update_authority, update_field},
+    state::{Field, TokenMetadata},
+};
+
+use crate::error::{Result, SolanaUtilsError};
+use crate::mint_builder::EmbeddedMetadata;
+use crate::token_program::TokenProgram;
+
+/// Where a mint's metadata is written: a Metaplex metadata account, or the
+/// Token-2022 token metadata extension inside the mint itself.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetadataArgs {
+    Metaplex(DataV2),
+    TokenExtension(EmbeddedMetadata),
+}
+
+impl From<DataV2> for MetadataArgs {
+    fn from(data: DataV2) -> Self {
+        MetadataArgs::Metaplex(data)
+    }
+}
+
+impl From<EmbeddedMetadata> for MetadataArgs {
+    fn from(metadata: EmbeddedMetadata) -> Self {
+        MetadataArgs::TokenExtension(metadata)
+    }
+}
+
+pub fn metaplex_metadata_address(mint: &Pubkey) -> Pubkey {
+    Metadata::find_pda(mint).0
+}
+
+pub(crate) fn metaplex_create_instruction(
+    mint: &Pubkey,
+    authority: &Pubkey,
+    token_program: TokenProgram,
+    data: DataV2,
+) -> Instruction {
+    let mut builder = CreateV1Builder::new();
+
+    builder
+        .metadata(metaplex_metadata_address(mint))
+        .mint(*mint, false)
+        .authority(*authority)
+        .payer(*authority)
+        .update_authority(*authority, true)
+        .spl_token_program(Some(token_program.id()))
+        .token_standard(TokenStandard::Fungible)
+        .name(data.name)
+        .symbol(data.symbol)
+        .uri(data.uri)
+        .seller_fee_basis_points(data.seller_fee_basis_points);
+
+    if let Some(creators) = data.creators {
+        builder.creators(creators);
+    }
+    if let Some(collection) = data.collection {
+        builder.collection(collection);
+    }
+    if let Some(uses) = data.uses {
+        builder.uses(uses);
+    }
+
+    builder.instruction()
+}
+
+/// Replaces the metadata data; a missing collection or uses clears it.
+pub(crate) fn metaplex_update_instruction(mint: &Pubkey, authority: &Pubkey, data: DataV2) -> Instruction {
+    UpdateV1Builder::new()
+        .authority(*authority)
+        .mint(*mint)
+        .metadata(metaplex_metadata_address(mint))
+        .payer(*authority)
+        .data(Data {
+            name: data.name,
+            symbol: data.symbol,
+            uri: data.uri,
+            seller_fee_basis_points: data.seller_fee_basis_points,
+            creators: data.creators,
+        })
+        .collection(match data.collection {
+            Some(collection) => CollectionToggle::Set(collection),
+            None => CollectionToggle::Clear,
+        })
+        .uses(match data.uses {
+            Some(uses) => UsesToggle::Set(uses),
+            None => UsesToggle::Clear,
+        })
+        .instruction()
+}
+
+/// Writes token metadata into a Token-2022 mint that was created with a
+/// metadata pointer to itself. The mint is topped up for the larger account first.
+pub(crate) async fn token_extension_create_instructions(
+    connection: &RpcClient,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    metadata: &EmbeddedMetadata,
+) -> Result<Vec<Instruction>> {
+    let account = connection.get_account(mint).await?;
+    let mint_state = StateWithExtensions::<Mint>::unpack(&account.data)?;
+
+    ensure_metadata_pointer(&mint_state, mint)?;
+
+    if mint_state.get_variable_len_extension::<TokenMetadata>().is_ok() {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "mint {} already has token metadata, update it instead",
+            mint
+        )));
+    }
+
+    let update_authority = metadata.update_authority.unwrap_or(*authority);
+    let token_metadata = TokenMetadata {
+        update_authority: OptionalNonZeroPubkey::try_from(Some(update_authority))?,
+        mint: *mint,
+        name: metadata.name.clone(),
+        symbol: metadata.symbol.clone(),
+        uri: metadata.uri.clone(),
+        additional_metadata: metadata.additional_metadata.clone(),
+    };
+
+    let new_len = account.data.len() + token_metadata.tlv_size_of()?;
+    let mut instructions = top_up_instruction(connection, authority, mint, account.lamports, new_len)
+        .await?
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    instructions.push(initialize(
+        &spl_token_2022::id(),
+        mint,
+        &update_authority,
+        mint,
+        authority,
+        metadata.name.clone(),
+        metadata.symbol.clone(),
+        metadata.uri.clone(),
+    ));
+
+    for (key, value) in &metadata.additional_metadata {
+        instructions.push(update_field(
+            &spl_token_2022::id(),
+            mint,
+            &update_authority,
+            Field::Key(key.clone()),
+            value.clone(),
+        ));
+    }
+
+    Ok(instructions)
+}
+
+/// Brings the embedded metadata in line with `metadata`: changed fields are
+/// updated, keys missing from `additional_metadata` are removed and the update
+/// authority is handed over last when a different one is given.
+pub(crate) async fn token_extension_update_instructions(
+    connection: &RpcClient,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    metadata: &EmbeddedMetadata,
+) -> Result<Vec<Instruction>> {
+    let account = connection.get_account(mint).await?;
+    let mint_state = StateWithExtensions::<Mint>::unpack(&account.data)?;
+
+    ensure_metadata_pointer(&mint_state, mint)?;
+
+    let current = mint_state.get_variable_len_extension::<TokenMetadata>()?;
+    let program_id = spl_token_2022::id();
+
+    let mut updated = current.clone();
+    let mut instructions = Vec::new();
+
+    let fields = [
+        (Field::Name, &current.name, &metadata.name),
+        (Field::Symbol, &current.symbol, &metadata.symbol),
+        (Field::Uri, &current.uri, &metadata.uri),
+    ];
+    for (field, current_value, value) in fields {
+        if current_value != value {
+            updated.update(field.clone(), value.clone());
+            instructions.push(update_field(&program_id, mint, authority, field, value.clone()));
+        }
+    }
+
+    for (key, value) in &metadata.additional_metadata {
+        let current_value = current
+            .additional_metadata
+            .iter()
+            .find(|(current_key, _)| current_key == key)
+            .map(|(_, current_value)| current_value);
+
+        if current_value != Some(value) {
+            updated.update(Field::Key(key.clone()), value.clone());
+            instructions.push(update_field(&program_id, mint, authority, Field::Key(key.clone()), value.clone()));
+        }
+    }
+
+    for (key, _) in &current.additional_metadata {
+        if !metadata.additional_metadata.iter().any(|(new_key, _)| new_key == key) {
+            updated.remove_key(key);
+            instructions.push(remove_key(&program_id, mint, authority, key.clone(), true));
+        }
+    }
+
+    if let Some(new_authority) = metadata
+        .update_authority
+        .filter(|new_authority| Option::<Pubkey>::from(current.update_authority) != Some(*new_authority))
+    {
+        instructions.push(update_authority(
+            &program_id,
+            mint,
+            authority,
+            OptionalNonZeroPubkey::try_from(Some(new_authority))?,
+        ));
+    }
+
+    if instructions.is_empty() {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "token metadata of {} is already up to date",
+            mint
+        )));
+    }
+
+    let new_len = account.data.len() - current.tlv_size_of()? + updated.tlv_size_of()?;
+    if let Some(top_up) = top_up_instruction(connection, authority, mint, account.lamports, new_len).await? {
+        instructions.insert(0, top_up);
+    }
+
+    Ok(instructions)
+}
+
+fn ensure_metadata_pointer(mint_state: &StateWithExtensions<Mint>, mint: &Pubkey) -> Result<()> {
+    let metadata_address = mint_state
+        .get_extension::<MetadataPointer>()
+        .ok()
+        .and_then(|pointer| Option::<Pubkey>::from(pointer.metadata_address));
+
+    if metadata_address != Some(*mint) {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "mint {} has no metadata pointer to itself, create it with MintBuilder::metadata",
+            mint
+        )));
+    }
+
+    Ok(())
+}
+
+/// The token program reallocates the mint when metadata grows but does not fund it.
+async fn top_up_instruction(
+    connection: &RpcClient,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    lamports: u64,
+    new_len: usize,
+) -> Result<Option<Instruction>> {
+    let rent = connection.get_minimum_balance_for_rent_exemption(new_len).await?;
+
+    Ok((rent > lamports).then(|| transfer(payer, mint, rent - lamports)))
+}
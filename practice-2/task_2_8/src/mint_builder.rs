@@ -0,0 +1,500 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+};
+use spl_pod::optional_keys::OptionalNonZeroPubkey;
+use spl_token_2022::{
+    extension::{
+        default_account_state::instruction::initialize_default_account_state,
+        interest_bearing_mint, metadata_pointer,
+        transfer_fee::instruction::initialize_transfer_fee_config, ExtensionType,
+    },
+    instruction::{
+        initialize_mint, initialize_mint_close_authority, initialize_non_transferable_mint,
+        initialize_permanent_delegate,
+    },
+    state::{AccountState, Mint},
+};
+use spl_token_metadata_interface::{
+    instruction::{initialize as initialize_token_metadata, update_field},
+    state::{Field, TokenMetadata},
+};
+
+use crate::error::{Result, SolanaUtilsError};
+use crate::explorer_link::LinkType;
+use crate::results::CreatedMint;
+use crate::simulation::{simulate_instructions, SimulationReport};
+use crate::token_program::TokenProgram;
+use crate::{explorer_url, send_instructions};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFee {
+    pub basis_points: u16,
+    pub maximum_fee: u64,
+    pub config_authority: Option<Pubkey>,
+    pub withdraw_withheld_authority: Option<Pubkey>,
+}
+
+/// Metadata stored inside the mint account itself, via the metadata pointer
+/// and token metadata extensions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmbeddedMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub additional_metadata: Vec<(String, String)>,
+    pub update_authority: Option<Pubkey>,
+}
+
+/// Builds a Token-2022 mint with an opt-in set of extensions, or a plain
+/// SPL Token mint when `token_program` is set to `TokenProgram::Token`.
+///
+/// The account is created with room for the fixed-size extensions and funded
+/// up front for the embedded metadata, which the token program reallocates
+/// into when the metadata is initialized after the mint.
+#[derive(Clone, Debug)]
+pub struct MintBuilder {
+    token_program: TokenProgram,
+    decimals: u8,
+    mint_authority: Pubkey,
+    freeze_authority: Option<Pubkey>,
+    transfer_fee: Option<TransferFee>,
+    metadata: Option<EmbeddedMetadata>,
+    interest_rate: Option<(i16, Option<Pubkey>)>,
+    non_transferable: bool,
+    permanent_delegate: Option<Pubkey>,
+    close_authority: Option<Pubkey>,
+    default_account_state: Option<AccountState>,
+}
+
+impl MintBuilder {
+    pub fn new(mint_authority: Pubkey, decimals: u8) -> Self {
+        Self {
+            token_program: TokenProgram::Token2022,
+            decimals,
+            mint_authority,
+            freeze_authority: None,
+            transfer_fee: None,
+            metadata: None,
+            interest_rate: None,
+            non_transferable: false,
+            permanent_delegate: None,
+            close_authority: None,
+            default_account_state: None,
+        }
+    }
+
+    /// The classic SPL Token program supports none of the extensions.
+    pub fn token_program(mut self, token_program: TokenProgram) -> Self {
+        self.token_program = token_program;
+        self
+    }
+
+    pub fn freeze_authority(mut self, freeze_authority: Pubkey) -> Self {
+        self.freeze_authority = Some(freeze_authority);
+        self
+    }
+
+    /// Fee in basis points of every transfer, capped at `maximum_fee` base units.
+    /// Both fee authorities default to the mint authority.
+    pub fn transfer_fee(mut self, basis_points: u16, maximum_fee: u64) -> Self {
+        self.transfer_fee = Some(TransferFee {
+            basis_points,
+            maximum_fee,
+            config_authority: Some(self.mint_authority),
+            withdraw_withheld_authority: Some(self.mint_authority),
+        });
+        self
+    }
+
+    pub fn transfer_fee_config(mut self, transfer_fee: TransferFee) -> Self {
+        self.transfer_fee = Some(transfer_fee);
+        self
+    }
+
+    /// Embeds name, symbol and uri in the mint. The update authority defaults to the mint authority.
+    pub fn metadata(mut self, name: &str, symbol: &str, uri: &str) -> Self {
+        self.metadata = Some(EmbeddedMetadata {
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+            additional_metadata: Vec::new(),
+            update_authority: Some(self.mint_authority),
+        });
+        self
+    }
+
+    /// Adds a key/value field to the embedded metadata, ignored without `metadata`.
+    pub fn additional_metadata(mut self, key: &str, value: &str) -> Self {
+        if let Some(metadata) = self.metadata.as_mut() {
+            metadata.additional_metadata.push((key.to_string(), value.to_string()));
+        }
+        self
+    }
+
+    pub fn embedded_metadata(mut self, metadata: EmbeddedMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Interest rate in basis points per year, rate changes are signed by the mint authority.
+    pub fn interest_bearing(mut self, rate: i16) -> Self {
+        self.interest_rate = Some((rate, Some(self.mint_authority)));
+        self
+    }
+
+    pub fn non_transferable(mut self) -> Self {
+        self.non_transferable = true;
+        self
+    }
+
+    pub fn permanent_delegate(mut self, delegate: Pubkey) -> Self {
+        self.permanent_delegate = Some(delegate);
+        self
+    }
+
+    pub fn close_authority(mut self, close_authority: Pubkey) -> Self {
+        self.close_authority = Some(close_authority);
+        self
+    }
+
+    /// State new token accounts start in. `Frozen` requires a freeze authority.
+    pub fn default_account_state(mut self, state: AccountState) -> Self {
+        self.default_account_state = Some(state);
+        self
+    }
+
+    /// Fixed-size extensions the account is allocated with.
+    pub fn extension_types(&self) -> Vec<ExtensionType> {
+        let mut extension_types = Vec::new();
+
+        if self.transfer_fee.is_some() {
+            extension_types.push(ExtensionType::TransferFeeConfig);
+        }
+        if self.metadata.is_some() {
+            extension_types.push(ExtensionType::MetadataPointer);
+        }
+        if self.interest_rate.is_some() {
+            extension_types.push(ExtensionType::InterestBearingConfig);
+        }
+        if self.non_transferable {
+            extension_types.push(ExtensionType::NonTransferable);
+        }
+        if self.permanent_delegate.is_some() {
+            extension_types.push(ExtensionType::PermanentDelegate);
+        }
+        if self.close_authority.is_some() {
+            extension_types.push(ExtensionType::MintCloseAuthority);
+        }
+        if self.default_account_state.is_some() {
+            extension_types.push(ExtensionType::DefaultAccountState);
+        }
+
+        extension_types
+    }
+
+    /// Space allocated by `create_account`.
+    pub fn account_len(&self) -> Result<usize> {
+        Ok(ExtensionType::try_calculate_account_len::<Mint>(&self.extension_types())?)
+    }
+
+    /// Space the account ends up with once the embedded metadata is written.
+    pub fn final_account_len(&self, mint: &Pubkey) -> Result<usize> {
+        let metadata_len = match self.token_metadata(mint)? {
+            Some(token_metadata) => token_metadata.tlv_size_of()?,
+            None => 0,
+        };
+
+        Ok(self.account_len()? + metadata_len)
+    }
+
+    pub fn instructions(&self, payer: &Pubkey, mint: &Pubkey, lamports: u64) -> Result<Vec<Instruction>> {
+        let extension_types = self.extension_types();
+
+        if self.token_program == TokenProgram::Token && !extension_types.is_empty() {
+            return Err(SolanaUtilsError::InvalidInput(format!(
+                "the SPL Token program does not support extensions: {:?}",
+                extension_types
+            )));
+        }
+
+        ExtensionType::check_for_invalid_mint_extension_combinations(&extension_types)
+            .map_err(ProgramError::from)?;
+
+        if self.default_account_state == Some(AccountState::Frozen) && self.freeze_authority.is_none() {
+            return Err(SolanaUtilsError::InvalidInput(
+                "default frozen accounts require a freeze authority".to_string(),
+            ));
+        }
+
+        let token_program_id = self.token_program.id();
+        let mut instructions = vec![create_account(
+            payer,
+            mint,
+            lamports,
+            self.account_len()? as u64,
+            &token_program_id,
+        )];
+
+        // Extensions must be initialized before the mint itself.
+        if let Some(transfer_fee) = &self.transfer_fee {
+            instructions.push(initialize_transfer_fee_config(
+                &token_program_id,
+                mint,
+                transfer_fee.config_authority.as_ref(),
+                transfer_fee.withdraw_withheld_authority.as_ref(),
+                transfer_fee.basis_points,
+                transfer_fee.maximum_fee,
+            )?);
+        }
+        if self.metadata.is_some() {
+            instructions.push(metadata_pointer::instruction::initialize(
+                &token_program_id,
+                mint,
+                self.update_authority(),
+                Some(*mint),
+            )?);
+        }
+        if let Some((rate, rate_authority)) = self.interest_rate {
+            instructions.push(interest_bearing_mint::instruction::initialize(
+                &token_program_id,
+                mint,
+                rate_authority,
+                rate,
+            )?);
+        }
+        if self.non_transferable {
+            instructions.push(initialize_non_transferable_mint(&token_program_id, mint)?);
+        }
+        if let Some(delegate) = &self.permanent_delegate {
+            instructions.push(initialize_permanent_delegate(&token_program_id, mint, delegate)?);
+        }
+        if let Some(close_authority) = &self.close_authority {
+            instructions.push(initialize_mint_close_authority(&token_program_id, mint, Some(close_authority))?);
+        }
+        if let Some(state) = &self.default_account_state {
+            instructions.push(initialize_default_account_state(&token_program_id, mint, state)?);
+        }
+
+        instructions.push(initialize_mint(
+            &token_program_id,
+            mint,
+            &self.mint_authority,
+            self.freeze_authority.as_ref(),
+            self.decimals,
+        )?);
+
+        // Token metadata lives in the mint and can only be written after it is initialized.
+        if let (Some(metadata), Some(update_authority)) = (&self.metadata, self.update_authority()) {
+            instructions.push(initialize_token_metadata(
+                &token_program_id,
+                mint,
+                &update_authority,
+                mint,
+                &self.mint_authority,
+                metadata.name.clone(),
+                metadata.symbol.clone(),
+                metadata.uri.clone(),
+            ));
+
+            for (key, value) in &metadata.additional_metadata {
+                instructions.push(update_field(
+                    &token_program_id,
+                    mint,
+                    &update_authority,
+                    Field::Key(key.clone()),
+                    value.clone(),
+                ));
+            }
+        }
+
+        Ok(instructions)
+    }
+
+    /// Creates the mint with a fresh keypair. The payer has to be the mint authority
+    /// (and the metadata update authority) when metadata is embedded.
+    pub async fn create(&self, connection: &RpcClient, payer: &Keypair) -> Result<CreatedMint> {
+        let token_mint = Keypair::new();
+
+        let instructions = self.checked_instructions(connection, &payer.pubkey(), &token_mint.pubkey()).await?;
+
+        let receipt = send_instructions(connection, &instructions, &payer.pubkey(), &[payer, &token_mint]).await?;
+
+        Ok(CreatedMint {
+            mint: token_mint.pubkey(),
+            mint_authority: self.mint_authority,
+            decimals: self.decimals,
+            token_program: self.token_program,
+            extensions: self.extension_types(),
+            explorer_link: explorer_url(connection, LinkType::Token, token_mint.pubkey()),
+            receipt,
+        })
+    }
+
+    /// Same as `create`, but only simulates the transaction with a throwaway mint keypair.
+    pub async fn simulate(&self, connection: &RpcClient, payer: &Keypair) -> Result<SimulationReport> {
+        let token_mint = Keypair::new();
+
+        let instructions = self.checked_instructions(connection, &payer.pubkey(), &token_mint.pubkey()).await?;
+
+        simulate_instructions(connection, &instructions, &payer.pubkey(), &[payer, &token_mint]).await
+    }
+
+    async fn checked_instructions(&self, connection: &RpcClient, payer: &Pubkey, mint: &Pubkey) -> Result<Vec<Instruction>> {
+        if let Some(metadata) = &self.metadata {
+            let update_authority = self.update_authority();
+
+            if self.mint_authority != *payer
+                || (!metadata.additional_metadata.is_empty() && update_authority != Some(*payer))
+            {
+                return Err(SolanaUtilsError::InvalidInput(
+                    "embedding metadata requires the payer to be the mint and update authority".to_string(),
+                ));
+            }
+        }
+
+        let space = self.final_account_len(mint)?;
+        let rent = connection.get_minimum_balance_for_rent_exemption(space).await?;
+
+        self.instructions(payer, mint, rent)
+    }
+
+    /// Update authority of the embedded metadata, the same for the pointer and the metadata itself.
+    fn update_authority(&self) -> Option<Pubkey> {
+        self.metadata
+            .as_ref()
+            .map(|metadata| metadata.update_authority.unwrap_or(self.mint_authority))
+    }
+
+    fn token_metadata(&self, mint: &Pubkey) -> Result<Option<TokenMetadata>> {
+        let Some(metadata) = &self.metadata else {
+            return Ok(None);
+        };
+
+        Ok(Some(TokenMetadata {
+            update_authority: OptionalNonZeroPubkey::try_from(self.update_authority())?,
+            mint: *mint,
+            name: metadata.name.clone(),
+            symbol: metadata.symbol.clone(),
+            uri: metadata.uri.clone(),
+            additional_metadata: metadata.additional_metadata.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(deprecated)]
+    use solana_sdk::system_program;
+    use solana_sdk::{program_pack::Pack, rent::Rent};
+    use spl_token_2022::instruction::TokenInstruction;
+
+    use super::*;
+
+    #[test]
+    fn initializes_extensions_before_the_mint_and_metadata_after() {
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let builder = MintBuilder::new(authority, 6)
+            .transfer_fee(50, 1_000)
+            .metadata("Name", "SYM", "https://x")
+            .additional_metadata("k", "v")
+            .permanent_delegate(authority)
+            .close_authority(authority);
+
+        let instructions = builder.instructions(&authority, &mint, 0).unwrap();
+        assert_eq!(instructions.len(), 8);
+        assert_eq!(instructions[0].program_id, system_program::id());
+
+        let token_instructions: Vec<_> =
+            instructions[1..6].iter().map(|instruction| TokenInstruction::unpack(&instruction.data).unwrap()).collect();
+        assert!(matches!(
+            token_instructions.as_slice(),
+            [
+                TokenInstruction::TransferFeeExtension,
+                TokenInstruction::MetadataPointerExtension,
+                TokenInstruction::InitializePermanentDelegate { .. },
+                TokenInstruction::InitializeMintCloseAuthority { .. },
+                TokenInstruction::InitializeMint { decimals: 6, .. },
+            ]
+        ));
+
+        let token_program_id = TokenProgram::Token2022.id();
+        assert_eq!(
+            instructions[6],
+            initialize_token_metadata(
+                &token_program_id,
+                &mint,
+                &authority,
+                &mint,
+                &authority,
+                "Name".to_string(),
+                "SYM".to_string(),
+                "https://x".to_string(),
+            )
+        );
+        assert_eq!(
+            instructions[7],
+            update_field(&token_program_id, &mint, &authority, Field::Key("k".to_string()), "v".to_string())
+        );
+    }
+
+    #[test]
+    fn uses_one_update_authority_for_the_pointer_and_the_metadata() {
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let builder = MintBuilder::new(authority, 0).embedded_metadata(EmbeddedMetadata {
+            name: "Name".to_string(),
+            symbol: "SYM".to_string(),
+            uri: "https://x".to_string(),
+            additional_metadata: Vec::new(),
+            update_authority: None,
+        });
+
+        let token_program_id = TokenProgram::Token2022.id();
+        let instructions = builder.instructions(&authority, &mint, 0).unwrap();
+
+        assert_eq!(
+            instructions[1],
+            metadata_pointer::instruction::initialize(&token_program_id, &mint, Some(authority), Some(mint)).unwrap()
+        );
+        assert_eq!(instructions[3].accounts[1].pubkey, authority);
+    }
+
+    #[test]
+    fn funds_the_metadata_but_allocates_only_the_fixed_extensions() {
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let builder = MintBuilder::new(authority, 6)
+            .metadata("Name", "SYM", "https://x")
+            .additional_metadata("k", "v");
+
+        // Base mint padded to an account, the account type and the 64 byte metadata pointer.
+        assert_eq!(builder.account_len().unwrap(), 234);
+        // Plus the metadata TLV: its 12 byte header, both keys, the three strings and one key/value pair.
+        assert_eq!(builder.final_account_len(&mint).unwrap(), 352);
+
+        let rent = Rent::default().minimum_balance(builder.final_account_len(&mint).unwrap());
+        let instructions = builder.instructions(&authority, &mint, rent).unwrap();
+        assert_eq!(
+            instructions[0],
+            create_account(&authority, &mint, rent, 234, &TokenProgram::Token2022.id())
+        );
+    }
+
+    #[test]
+    fn rejects_extensions_on_the_spl_token_program() {
+        let authority = Pubkey::new_unique();
+        let builder = MintBuilder::new(authority, 6).token_program(TokenProgram::Token).non_transferable();
+
+        assert!(matches!(
+            builder.instructions(&authority, &Pubkey::new_unique(), 0),
+            Err(SolanaUtilsError::InvalidInput(_))
+        ));
+        assert_eq!(MintBuilder::new(authority, 6).token_program(TokenProgram::Token).account_len().unwrap(), Mint::LEN);
+    }
+}
@@ -0,0 +1,275 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+};
+use spl_token_2022::{
+    instruction::{
+        burn_checked, freeze_account, initialize_multisig2, mint_to_checked, set_authority, thaw_account,
+        transfer_checked, AuthorityType,
+    },
+    state::Multisig,
+};
+
+use crate::amount::TokenAmount;
+use crate::error::{Result, SolanaUtilsError};
+use crate::explorer_link::LinkType;
+use crate::results::TransactionReceipt;
+use crate::token_program::TokenProgram;
+use crate::{explorer_url, send_instructions};
+
+pub use spl_token_2022::instruction::MAX_SIGNERS;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatedMultisig {
+    pub address: Pubkey,
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub token_program: TokenProgram,
+    pub explorer_link: String,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultisigInfo {
+    pub address: Pubkey,
+    pub token_program: TokenProgram,
+    /// Signatures required out of `signers.len()`.
+    pub threshold: u8,
+    pub signers: Vec<Pubkey>,
+}
+
+impl MultisigInfo {
+    /// Checks that `signers` are distinct members and enough of them to reach the threshold.
+    pub fn ensure_signers(&self, signers: &[Pubkey]) -> Result<()> {
+        for (index, signer) in signers.iter().enumerate() {
+            if !self.signers.contains(signer) {
+                return Err(SolanaUtilsError::InvalidInput(format!(
+                    "{} is not a signer of multisig {}",
+                    signer, self.address
+                )));
+            }
+            if signers[..index].contains(signer) {
+                return Err(SolanaUtilsError::InvalidInput(format!("signer {} is given twice", signer)));
+            }
+        }
+
+        if signers.len() < self.threshold as usize {
+            return Err(SolanaUtilsError::InvalidInput(format!(
+                "multisig {} needs {} of {} signers, got {}",
+                self.address,
+                self.threshold,
+                self.signers.len(),
+                signers.len()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Actions a multisig can authorize as mint, freeze or account authority.
+/// Token accounts and mints have to belong to the multisig's token program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MultisigAction {
+    MintTo {
+        mint: Pubkey,
+        destination: Pubkey,
+        amount: TokenAmount,
+    },
+    Transfer {
+        source: Pubkey,
+        mint: Pubkey,
+        destination: Pubkey,
+        amount: TokenAmount,
+    },
+    /// `account` is the mint or token account the authority belongs to,
+    /// `None` removes the authority for good.
+    SetAuthority {
+        account: Pubkey,
+        authority_type: AuthorityType,
+        new_authority: Option<Pubkey>,
+    },
+    Freeze {
+        account: Pubkey,
+        mint: Pubkey,
+    },
+    Thaw {
+        account: Pubkey,
+        mint: Pubkey,
+    },
+    Burn {
+        account: Pubkey,
+        mint: Pubkey,
+        amount: TokenAmount,
+    },
+}
+
+impl MultisigAction {
+    /// The instruction with `multisig` as authority, signed by `signers`.
+    pub fn instruction(&self, token_program: TokenProgram, multisig: &Pubkey, signers: &[Pubkey]) -> Result<Instruction> {
+        let program_id = token_program.id();
+        let signers: Vec<&Pubkey> = signers.iter().collect();
+
+        let instruction = match self {
+            MultisigAction::MintTo { mint, destination, amount } => mint_to_checked(
+                &program_id,
+                mint,
+                destination,
+                multisig,
+                &signers,
+                amount.raw(),
+                amount.decimals(),
+            )?,
+            MultisigAction::Transfer { source, mint, destination, amount } => transfer_checked(
+                &program_id,
+                source,
+                mint,
+                destination,
+                multisig,
+                &signers,
+                amount.raw(),
+                amount.decimals(),
+            )?,
+            MultisigAction::SetAuthority { account, authority_type, new_authority } => set_authority(
+                &program_id,
+                account,
+                new_authority.as_ref(),
+                authority_type.clone(),
+                multisig,
+                &signers,
+            )?,
+            MultisigAction::Freeze { account, mint } => {
+                freeze_account(&program_id, account, mint, multisig, &signers)?
+            }
+            MultisigAction::Thaw { account, mint } => thaw_account(&program_id, account, mint, multisig, &signers)?,
+            MultisigAction::Burn { account, mint, amount } => burn_checked(
+                &program_id,
+                account,
+                mint,
+                multisig,
+                &signers,
+                amount.raw(),
+                amount.decimals(),
+            )?,
+        };
+
+        Ok(instruction)
+    }
+}
+
+/// Creates an M-of-N multisig account owned by `token_program`, paid for by `payer`.
+pub async fn create_multisig(
+    connection: &RpcClient,
+    payer: &Keypair,
+    signers: &[Pubkey],
+    threshold: u8,
+    token_program: TokenProgram,
+) -> Result<CreatedMultisig> {
+    if signers.is_empty() || signers.len() > MAX_SIGNERS {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "a multisig needs between 1 and {} signers, got {}",
+            MAX_SIGNERS,
+            signers.len()
+        )));
+    }
+    if threshold == 0 || threshold as usize > signers.len() {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "threshold must be between 1 and {}, got {}",
+            signers.len(),
+            threshold
+        )));
+    }
+
+    let multisig = Keypair::new();
+    let space = Multisig::get_packed_len();
+    let rent = connection.get_minimum_balance_for_rent_exemption(space).await?;
+
+    let create_multisig_ix = create_account(
+        &payer.pubkey(),
+        &multisig.pubkey(),
+        rent,
+        space as u64,
+        &token_program.id(),
+    );
+
+    // The `2` variant doesn't need the rent sysvar and works with both programs.
+    let init_multisig_ix = initialize_multisig2(
+        &token_program.id(),
+        &multisig.pubkey(),
+        &signers.iter().collect::<Vec<_>>(),
+        threshold,
+    )?;
+
+    let receipt = send_instructions(
+        connection,
+        &[create_multisig_ix, init_multisig_ix],
+        &payer.pubkey(),
+        &[payer, &multisig],
+    ).await?;
+
+    Ok(CreatedMultisig {
+        address: multisig.pubkey(),
+        signers: signers.to_vec(),
+        threshold,
+        token_program,
+        explorer_link: explorer_url(connection, LinkType::Address, multisig.pubkey()),
+        receipt,
+    })
+}
+
+pub async fn fetch_multisig(connection: &RpcClient, multisig: &Pubkey) -> Result<MultisigInfo> {
+    let account = connection.get_account(multisig).await?;
+
+    decode_multisig(multisig, &account)
+}
+
+pub fn decode_multisig(address: &Pubkey, account: &Account) -> Result<MultisigInfo> {
+    let token_program = TokenProgram::from_program_id(&account.owner).ok_or(SolanaUtilsError::NotTokenAccount {
+        account: *address,
+        owner: account.owner,
+    })?;
+
+    if account.data.len() != Multisig::LEN {
+        return Err(SolanaUtilsError::Decode {
+            account: *address,
+            message: "not a multisig account".to_string(),
+        });
+    }
+
+    let multisig = Multisig::unpack(&account.data)?;
+
+    Ok(MultisigInfo {
+        address: *address,
+        token_program,
+        threshold: multisig.m,
+        signers: multisig.signers[..multisig.n as usize].to_vec(),
+    })
+}
+
+/// Runs `action` with the multisig as authority. `signers` must reach the threshold,
+/// `payer` only pays the fee unless it is one of them too.
+pub async fn execute_multisig_action(
+    connection: &RpcClient,
+    payer: &Keypair,
+    multisig: &Pubkey,
+    signers: &[&Keypair],
+    action: &MultisigAction,
+) -> Result<TransactionReceipt> {
+    let multisig_info = fetch_multisig(connection, multisig).await?;
+
+    let signer_pubkeys: Vec<Pubkey> = signers.iter().map(|signer| signer.pubkey()).collect();
+    multisig_info.ensure_signers(&signer_pubkeys)?;
+
+    let instruction = action.instruction(multisig_info.token_program, multisig, &signer_pubkeys)?;
+
+    // The transaction wants each key to sign exactly once.
+    let mut transaction_signers = vec![payer];
+    transaction_signers.extend(signers.iter().copied().filter(|signer| signer.pubkey() != payer.pubkey()));
+
+    send_instructions(connection, &[instruction], &payer.pubkey(), &transaction_signers).await
+}
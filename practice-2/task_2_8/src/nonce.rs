@@ -0,0 +1,397 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    nonce::state::{State, Versions},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction::{
+        advance_nonce_account, authorize_nonce_account, create_nonce_account, upgrade_nonce_account,
+        withdraw_nonce_account,
+    },
+    signature::Signature,
+};
+#[allow(deprecated)]
+use solana_sdk::system_program;
+
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::explorer_link::LinkType;
+use crate::results::TransactionReceipt;
+use crate::tx_envelope::{file_error, BlockhashSource, TxEnvelope};
+use crate::{explorer_url, send_instructions};
+
+/// Offset of the authority inside a nonce account, after the version and state tags.
+const NONCE_AUTHORITY_OFFSET: usize = 8;
+
+/// A durable nonce account, either version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NonceInfo {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    /// Used as the recent blockhash of transactions that advance this nonce.
+    pub blockhash: Hash,
+    pub lamports_per_signature: u64,
+    pub lamports: u64,
+    /// Legacy nonces share the blockhash domain and can't back durable transactions
+    /// until they are upgraded or advanced once.
+    pub legacy: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatedNonce {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    pub explorer_link: String,
+    pub receipt: TransactionReceipt,
+}
+
+/// Creates a rent-exempt nonce account controlled by `authority`, paid for by `payer`.
+pub async fn create_nonce(connection: &RpcClient, payer: &Keypair, authority: &Pubkey) -> Result<CreatedNonce> {
+    let nonce_account = Keypair::new();
+    let rent = connection.get_minimum_balance_for_rent_exemption(State::size()).await?;
+
+    let instructions = create_nonce_account(&payer.pubkey(), &nonce_account.pubkey(), authority, rent);
+
+    let receipt = send_instructions(connection, &instructions, &payer.pubkey(), &[payer, &nonce_account]).await?;
+
+    Ok(CreatedNonce {
+        address: nonce_account.pubkey(),
+        authority: *authority,
+        explorer_link: explorer_url(connection, LinkType::Address, nonce_account.pubkey()),
+        receipt,
+    })
+}
+
+pub async fn fetch_nonce(connection: &RpcClient, nonce_account: &Pubkey) -> Result<NonceInfo> {
+    let account = connection.get_account(nonce_account).await?;
+
+    decode_nonce(nonce_account, &account)
+}
+
+/// Every initialized nonce account `authority` controls, legacy ones included.
+pub async fn fetch_nonces_by_authority(connection: &RpcClient, authority: &Pubkey) -> Result<Vec<NonceInfo>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(State::size() as u64),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(NONCE_AUTHORITY_OFFSET, authority.as_ref())),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+
+    let accounts = connection
+        .get_program_accounts_with_config(&system_program::id(), config)
+        .await?;
+
+    Ok(accounts
+        .iter()
+        .filter_map(|(address, account)| decode_nonce(address, account).ok())
+        .collect())
+}
+
+pub fn decode_nonce(address: &Pubkey, account: &Account) -> Result<NonceInfo> {
+    let invalid = |message: &str| SolanaUtilsError::Decode {
+        account: *address,
+        message: message.to_string(),
+    };
+
+    if account.owner != system_program::id() {
+        return Err(invalid("not owned by the system program"));
+    }
+
+    let versions: Versions = bincode::deserialize(&account.data).map_err(|_| invalid("not a nonce account"))?;
+    let legacy = matches!(versions, Versions::Legacy(_));
+
+    match versions.state() {
+        State::Initialized(data) => Ok(NonceInfo {
+            address: *address,
+            authority: data.authority,
+            blockhash: data.blockhash(),
+            lamports_per_signature: data.get_lamports_per_signature(),
+            lamports: account.lamports,
+            legacy,
+        }),
+        State::Uninitialized => Err(invalid("nonce account is not initialized")),
+    }
+}
+
+/// Moves the nonce to a new value, invalidating transactions signed against the old one.
+/// Advancing a legacy nonce also upgrades it.
+pub async fn advance_nonce(connection: &RpcClient, authority: &Keypair, nonce_account: &Pubkey) -> Result<TransactionReceipt> {
+    let advance_ix = advance_nonce_account(nonce_account, &authority.pubkey());
+
+    send_instructions(connection, &[advance_ix], &authority.pubkey(), &[authority]).await
+}
+
+/// Upgrades a legacy nonce without its authority, anyone can pay for it.
+pub async fn upgrade_nonce(connection: &RpcClient, payer: &Keypair, nonce_account: &Pubkey) -> Result<TransactionReceipt> {
+    let upgrade_ix = upgrade_nonce_account(*nonce_account);
+
+    send_instructions(connection, &[upgrade_ix], &payer.pubkey(), &[payer]).await
+}
+
+pub async fn authorize_nonce(
+    connection: &RpcClient,
+    authority: &Keypair,
+    nonce_account: &Pubkey,
+    new_authority: &Pubkey,
+) -> Result<TransactionReceipt> {
+    let authorize_ix = authorize_nonce_account(nonce_account, &authority.pubkey(), new_authority);
+
+    send_instructions(connection, &[authorize_ix], &authority.pubkey(), &[authority]).await
+}
+
+/// Withdraws `lamports` above the rent-exempt minimum, see `close_nonce` to empty the account.
+pub async fn withdraw_from_nonce(
+    connection: &RpcClient,
+    authority: &Keypair,
+    nonce_account: &Pubkey,
+    recipient: &Pubkey,
+    lamports: u64,
+) -> Result<TransactionReceipt> {
+    let nonce = fetch_nonce(connection, nonce_account).await?;
+    let rent = connection.get_minimum_balance_for_rent_exemption(State::size()).await?;
+
+    if nonce.lamports.saturating_sub(lamports) < rent {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "withdrawing {} lamports would leave {} below the rent-exempt minimum of {}, close it instead",
+            lamports, nonce_account, rent
+        )));
+    }
+
+    let withdraw_ix = withdraw_nonce_account(nonce_account, &authority.pubkey(), recipient, lamports);
+
+    send_instructions(connection, &[withdraw_ix], &authority.pubkey(), &[authority]).await
+}
+
+/// Withdraws the whole balance, which deletes the account. The runtime refuses this
+/// in the same slot the nonce was last advanced in.
+pub async fn close_nonce(
+    connection: &RpcClient,
+    authority: &Keypair,
+    nonce_account: &Pubkey,
+    recipient: &Pubkey,
+) -> Result<TransactionReceipt> {
+    let nonce = fetch_nonce(connection, nonce_account).await?;
+
+    let withdraw_ix = withdraw_nonce_account(nonce_account, &authority.pubkey(), recipient, nonce.lamports);
+
+    send_instructions(connection, &[withdraw_ix], &authority.pubkey(), &[authority]).await
+}
+
+/// Whether a pre-signed durable nonce transaction can still land.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NonceTransactionStatus {
+    /// The nonce still holds the transaction's blockhash, anyone with it can send it.
+    Executable { nonce: NonceInfo },
+    /// The transaction itself landed, which advanced the nonce.
+    Executed { signature: Signature, slot: u64 },
+    /// The nonce moved on, the transaction can never land anymore.
+    Invalidated { nonce: NonceInfo },
+    /// The nonce account was closed.
+    NonceClosed,
+}
+
+impl NonceTransactionStatus {
+    pub fn is_executable(&self) -> bool {
+        matches!(self, NonceTransactionStatus::Executable { .. })
+    }
+}
+
+/// Looks up whether `envelope`, a durable nonce transaction, is still executable.
+/// Missing signatures aren't considered, see `TxEnvelope::missing_signers`.
+pub async fn nonce_transaction_status(connection: &RpcClient, envelope: &TxEnvelope) -> Result<NonceTransactionStatus> {
+    let BlockhashSource::Nonce { account, .. } = envelope.blockhash_source else {
+        return Err(SolanaUtilsError::InvalidInput(
+            "the transaction uses a recent blockhash, not a durable nonce".to_string(),
+        ));
+    };
+
+    let Some(nonce_account) = connection
+        .get_account_with_commitment(&account, connection.commitment())
+        .await?
+        .value
+    else {
+        return Ok(NonceTransactionStatus::NonceClosed);
+    };
+    let nonce = decode_nonce(&account, &nonce_account)?;
+
+    if nonce.blockhash == *envelope.blockhash() {
+        return Ok(NonceTransactionStatus::Executable { nonce });
+    }
+
+    // Landing advances the nonce too, tell that apart from an invalidation.
+    let signature = envelope.transaction.signatures[0];
+    if signature != Signature::default() {
+        let status = connection
+            .get_signature_statuses_with_history(&[signature])
+            .await?
+            .value
+            .into_iter()
+            .flatten()
+            .next();
+
+        if let Some(status) = status.filter(|status| status.err.is_none()) {
+            return Ok(NonceTransactionStatus::Executed { signature, slot: status.slot });
+        }
+    }
+
+    Ok(NonceTransactionStatus::Invalidated { nonce })
+}
+
+/// Revokes a pre-signed durable nonce transaction by advancing its nonce, after which
+/// no copy of it can land. `authority` has to be the nonce authority.
+pub async fn cancel_nonce_transaction(
+    connection: &RpcClient,
+    authority: &Keypair,
+    envelope: &TxEnvelope,
+) -> Result<TransactionReceipt> {
+    let nonce = match nonce_transaction_status(connection, envelope).await? {
+        NonceTransactionStatus::Executable { nonce } => nonce,
+        NonceTransactionStatus::Executed { signature, .. } => {
+            return Err(SolanaUtilsError::InvalidInput(format!(
+                "the transaction already landed as {}",
+                signature
+            )));
+        }
+        NonceTransactionStatus::Invalidated { .. } | NonceTransactionStatus::NonceClosed => {
+            return Err(SolanaUtilsError::InvalidInput(
+                "the transaction can't land anymore, there is nothing to cancel".to_string(),
+            ));
+        }
+    };
+
+    if nonce.authority != authority.pubkey() {
+        return Err(SolanaUtilsError::InvalidInput(format!(
+            "nonce {} is controlled by {}, not {}",
+            nonce.address,
+            nonce.authority,
+            authority.pubkey()
+        )));
+    }
+
+    advance_nonce(connection, authority, &nonce.address).await
+}
+
+/// Hands out the existing nonce accounts of one authority, creating new ones only
+/// when every existing nonce is already handed out. Nonces stay taken until released,
+/// since two transactions signed against the same nonce can't both land.
+#[derive(Clone, Debug)]
+pub struct NoncePool {
+    authority: Pubkey,
+    in_use: HashSet<Pubkey>,
+    /// Where the taken nonces are kept between runs, see `open`.
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NoncePoolFile {
+    authority: String,
+    in_use: Vec<String>,
+}
+
+impl NoncePool {
+    /// A pool that only lives as long as the process.
+    pub fn new(authority: Pubkey) -> Self {
+        Self {
+            authority,
+            in_use: HashSet::new(),
+            path: None,
+        }
+    }
+
+    /// A pool whose taken nonces are saved to `path` on every change, so the next
+    /// run doesn't hand out a nonce an earlier one is still waiting on.
+    pub fn open(authority: Pubkey, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut pool = Self::new(authority);
+        pool.path = Some(path.to_path_buf());
+
+        if !path.exists() {
+            return Ok(pool);
+        }
+
+        let content = fs::read_to_string(path).map_err(|error| file_error(path, error.to_string()))?;
+        let file: NoncePoolFile = serde_json::from_str(&content).map_err(|error| file_error(path, error.to_string()))?;
+
+        if parse_pubkey(&file.authority)? != authority {
+            return Err(file_error(path, format!("the pool belongs to {}, not {}", file.authority, authority)));
+        }
+        pool.in_use = file.in_use.iter().map(|nonce| parse_pubkey(nonce)).collect::<Result<_>>()?;
+
+        Ok(pool)
+    }
+
+    pub fn authority(&self) -> &Pubkey {
+        &self.authority
+    }
+
+    /// A free nonce of the authority with its current value. Legacy nonces are
+    /// upgraded first, `payer` covers that and the creation of new accounts.
+    pub async fn acquire(&mut self, connection: &RpcClient, payer: &Keypair) -> Result<NonceInfo> {
+        let nonces = fetch_nonces_by_authority(connection, &self.authority).await?;
+
+        let nonce = match nonces.into_iter().find(|nonce| !self.in_use.contains(&nonce.address)) {
+            Some(nonce) if nonce.legacy => {
+                upgrade_nonce(connection, payer, &nonce.address).await?;
+                fetch_nonce(connection, &nonce.address).await?
+            }
+            Some(nonce) => nonce,
+            None => {
+                let created = create_nonce(connection, payer, &self.authority).await?;
+                fetch_nonce(connection, &created.address).await?
+            }
+        };
+
+        self.in_use.insert(nonce.address);
+        self.save()?;
+
+        Ok(nonce)
+    }
+
+    /// Marks nonces as taken that were handed out some other way, e.g. the ones
+    /// of jobs still waiting in a `JobQueue`.
+    pub fn reserve(&mut self, nonce_accounts: impl IntoIterator<Item = Pubkey>) -> Result<()> {
+        self.in_use.extend(nonce_accounts);
+        self.save()
+    }
+
+    /// Returns the nonce to the pool once its transaction landed or was dropped.
+    pub fn release(&mut self, nonce_account: &Pubkey) -> Result<()> {
+        self.in_use.remove(nonce_account);
+        self.save()
+    }
+
+    /// Written to a temporary file first, like the jobs of a `JobQueue`.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut in_use: Vec<String> = self.in_use.iter().map(Pubkey::to_string).collect();
+        in_use.sort();
+
+        let file = NoncePoolFile {
+            authority: self.authority.to_string(),
+            in_use,
+        };
+
+        let content = serde_json::to_string_pretty(&file).map_err(|error| file_error(path, error.to_string()))?;
+        let temporary_path = path.with_extension("json.tmp");
+
+        fs::write(&temporary_path, content)
+            .and_then(|_| fs::rename(&temporary_path, path))
+            .map_err(|error| file_error(path, error.to_string()))
+    }
+}
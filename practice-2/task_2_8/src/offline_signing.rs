@@ -0,0 +1,284 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    message::Message,
+    pubkey::Pubkey,
+    signature::Keypair,
+    system_instruction::advance_nonce_account,
+    transaction::Transaction,
+};
+
+use crate::cluster::cluster_from_rpc_url;
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::inspector::MintDecimals;
+use crate::multisig::{fetch_multisig, MultisigAction, MultisigInfo};
+use crate::nonce::fetch_nonce;
+use crate::results::TransactionReceipt;
+use crate::token_program::TokenProgram;
+use crate::tx_envelope::{file_error, BlockhashSource, TxEnvelope, TxEnvelopeFile};
+use crate::tx_sender::TxSender;
+
+/// Bumped whenever the file layout changes incompatibly.
+/// Version 2 nests the transaction in a `TxEnvelope`.
+pub const OFFLINE_TRANSACTION_VERSION: u32 = 2;
+
+/// A multisig transaction that travels from signer to signer as a JSON file.
+/// Each party only needs the file and its own keypair to add a signature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfflineTransaction {
+    pub envelope: TxEnvelope,
+    pub multisig: MultisigInfo,
+    /// Whether `multisig` was read from the cluster rather than taken from the file.
+    pub verified: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OfflineTransactionFile {
+    version: u32,
+    multisig: String,
+    token_program: String,
+    threshold: u8,
+    multisig_signers: Vec<String>,
+    envelope: TxEnvelopeFile,
+}
+
+impl OfflineTransaction {
+    /// Builds the unsigned transaction for `action`, signed by `signers` on behalf of the multisig.
+    /// `fee_payer` and, with a nonce, the nonce authority have to sign as well.
+    pub async fn build(
+        connection: &RpcClient,
+        fee_payer: &Pubkey,
+        multisig: &Pubkey,
+        signers: &[Pubkey],
+        action: &MultisigAction,
+        nonce_account: Option<&Pubkey>,
+    ) -> Result<Self> {
+        let multisig = fetch_multisig(connection, multisig).await?;
+        multisig.ensure_signers(signers)?;
+
+        let instruction = action.instruction(multisig.token_program, &multisig.address, signers)?;
+
+        let (advance_nonce, blockhash, blockhash_source) = match nonce_account {
+            Some(nonce_account) => {
+                let nonce = fetch_nonce(connection, nonce_account).await?;
+                if nonce.legacy {
+                    return Err(SolanaUtilsError::InvalidInput(format!(
+                        "nonce {} is a legacy nonce, upgrade or advance it first",
+                        nonce_account
+                    )));
+                }
+
+                let source = BlockhashSource::Nonce {
+                    account: *nonce_account,
+                    authority: nonce.authority,
+                };
+                (Some(advance_nonce_account(nonce_account, &nonce.authority)), nonce.blockhash, source)
+            }
+            None => {
+                let (blockhash, last_valid_block_height) = connection
+                    .get_latest_blockhash_with_commitment(connection.commitment())
+                    .await?;
+                let source = BlockhashSource::Recent {
+                    last_valid_block_height: Some(last_valid_block_height),
+                };
+                (None, blockhash, source)
+            }
+        };
+
+        // Priced and budgeted now, advancing the nonce included, the signers can't change the instructions later.
+        let unbudgeted: Vec<_> = advance_nonce.iter().cloned().chain([instruction]).collect();
+        let mut instructions = TxSender::from_env()?
+            .budget(connection, &unbudgeted, fee_payer, None)
+            .await?
+            .instructions;
+
+        // The runtime only treats the blockhash as a nonce when advancing it comes first.
+        if let Some(advance_nonce) = advance_nonce {
+            instructions.retain(|instruction| *instruction != advance_nonce);
+            instructions.insert(0, advance_nonce);
+        }
+
+        let transaction = Transaction::new_unsigned(Message::new_with_blockhash(
+            &instructions,
+            Some(fee_payer),
+            &blockhash,
+        ));
+
+        Ok(Self {
+            envelope: TxEnvelope::new(transaction, cluster_from_rpc_url(&connection.url()), blockhash_source),
+            multisig,
+            verified: true,
+        })
+    }
+
+    /// Re-reads the multisig from the cluster and fails when the threshold or
+    /// the members recorded in the file don't match it.
+    pub async fn verify(&mut self, connection: &RpcClient) -> Result<()> {
+        let multisig = fetch_multisig(connection, &self.multisig.address).await?;
+
+        if multisig != self.multisig {
+            return Err(SolanaUtilsError::InvalidInput(format!(
+                "the file describes multisig {} as {} of {} on {}, on chain it is {} of {} on {}",
+                multisig.address,
+                self.multisig.threshold,
+                self.multisig.signers.len(),
+                self.multisig.token_program,
+                multisig.threshold,
+                multisig.signers.len(),
+                multisig.token_program
+            )));
+        }
+
+        self.verified = true;
+
+        Ok(())
+    }
+
+    /// Durable nonce backing the blockhash. Without one the transaction
+    /// expires about a minute after it was built.
+    pub fn nonce_account(&self) -> Option<Pubkey> {
+        match self.envelope.blockhash_source {
+            BlockhashSource::Nonce { account, .. } => Some(account),
+            BlockhashSource::Recent { .. } => None,
+        }
+    }
+
+    /// Every key that has to sign: the fee payer, the multisig members and the nonce authority.
+    pub fn required_signers(&self) -> &[Pubkey] {
+        self.envelope.required_signers()
+    }
+
+    pub fn missing_signers(&self) -> Vec<Pubkey> {
+        self.envelope.missing_signers()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.envelope.is_complete()
+    }
+
+    /// Adds the signature of `signer`, who has to be one of `required_signers`.
+    pub fn sign(&mut self, signer: &Keypair) -> Result<()> {
+        self.envelope.sign(signer)
+    }
+
+    /// Copies the signatures of `other`, a copy of the same transaction signed by someone else.
+    pub fn merge(&mut self, other: &OfflineTransaction) -> Result<()> {
+        self.envelope.merge(&other.envelope)
+    }
+
+    /// Human readable decoding for signers to review before they sign, see `TxEnvelope::describe`.
+    /// Threshold and members are marked unverified until `verify` checked them on chain.
+    pub fn describe(&self, decimals: &MintDecimals) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Multisig: {} ({} of {}, {}){}",
+            self.multisig.address,
+            self.multisig.threshold,
+            self.multisig.signers.len(),
+            self.multisig.token_program,
+            if self.verified { "" } else { ", unverified" }
+        )];
+
+        let member = if self.verified { "multisig member" } else { "multisig member, unverified" };
+        lines.extend(self.envelope.describe_with_roles(decimals, |signer| {
+            self.multisig.signers.contains(signer).then_some(member)
+        }));
+
+        lines
+    }
+
+    /// Sends the transaction once every required signature is present.
+    /// A nonce transaction stays valid, it can simply be submitted again.
+    pub async fn submit(&self, connection: &RpcClient) -> Result<TransactionReceipt> {
+        self.envelope.submit(connection).await
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        let file = OfflineTransactionFile {
+            version: OFFLINE_TRANSACTION_VERSION,
+            multisig: self.multisig.address.to_string(),
+            token_program: self.multisig.token_program.to_string(),
+            threshold: self.multisig.threshold,
+            multisig_signers: self.multisig.signers.iter().map(|signer| signer.to_string()).collect(),
+            envelope: self.envelope.to_file()?,
+        };
+
+        let content = serde_json::to_string_pretty(&file).map_err(|error| file_error(path, error.to_string()))?;
+        fs::write(path, content).map_err(|error| file_error(path, error.to_string()))
+    }
+
+    /// Reads a file written by `save`, rejecting it if the envelope doesn't check out
+    /// or the multisig isn't the one the transaction acts for. Threshold and members
+    /// come from the file unchecked, see `verify`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let content = fs::read_to_string(path).map_err(|error| file_error(path, error.to_string()))?;
+        let file: OfflineTransactionFile =
+            serde_json::from_str(&content).map_err(|error| file_error(path, error.to_string()))?;
+
+        if file.version != OFFLINE_TRANSACTION_VERSION {
+            return Err(file_error(path, format!("unsupported version {}", file.version)));
+        }
+
+        let envelope = TxEnvelope::from_file(file.envelope)?;
+        let (address, token_program) = multisig_authority(&envelope)
+            .ok_or_else(|| file_error(path, "the transaction has no token instruction signed by a multisig".to_string()))?;
+
+        if parse_pubkey(&file.multisig)? != address || file.token_program.parse::<TokenProgram>()? != token_program {
+            return Err(file_error(
+                path,
+                format!("the file names multisig {}, the transaction acts for {}", file.multisig, address),
+            ));
+        }
+
+        Ok(Self {
+            envelope,
+            multisig: MultisigInfo {
+                address,
+                token_program,
+                threshold: file.threshold,
+                signers: file
+                    .multisig_signers
+                    .iter()
+                    .map(|signer| parse_pubkey(signer))
+                    .collect::<Result<_>>()?,
+            },
+            verified: false,
+        })
+    }
+}
+
+/// The multisig a token instruction of `envelope` acts for: the account right before
+/// the member signatures, which the token program expects at the end.
+fn multisig_authority(envelope: &TxEnvelope) -> Option<(Pubkey, TokenProgram)> {
+    let message = &envelope.transaction.message;
+    let account_keys = message.static_account_keys();
+    let num_signers = message.header().num_required_signatures;
+
+    message.instructions().iter().find_map(|instruction| {
+        let token_program = TokenProgram::from_program_id(account_keys.get(instruction.program_id_index as usize)?)?;
+        let first_member = instruction.accounts.iter().position(|index| *index < num_signers)?;
+        let multisig = account_keys.get(*instruction.accounts.get(first_member.checked_sub(1)?)? as usize)?;
+
+        Some((*multisig, token_program))
+    })
+}
+
+/// Loads `paths`, merges their signatures into the first one and returns it.
+pub fn merge_files(paths: &[impl AsRef<Path>]) -> Result<OfflineTransaction> {
+    let Some((first, rest)) = paths.split_first() else {
+        return Err(SolanaUtilsError::InvalidInput("nothing to merge".to_string()));
+    };
+
+    let mut merged = OfflineTransaction::load(first)?;
+    for path in rest {
+        merged.merge(&OfflineTransaction::load(path)?)?;
+    }
+
+    Ok(merged)
+}
@@ -0,0 +1,159 @@
+use mpl_token_metadata::accounts::Metadata;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::{Account as TokenAccount, AccountState, Mint};
+use spl_token_metadata_interface::state::TokenMetadata;
+
+use crate::amount::TokenAmount;
+use crate::error::{Result, SolanaUtilsError};
+use crate::metadata::metaplex_metadata_address;
+use crate::token_program::TokenProgram;
+
+/// Offset of the owner inside a token account, after the 32 byte mint.
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MintInfo {
+    pub address: Pubkey,
+    pub token_program: TokenProgram,
+    pub supply: TokenAmount,
+    pub decimals: u8,
+    pub mint_authority: Option<Pubkey>,
+    pub freeze_authority: Option<Pubkey>,
+    pub extensions: Vec<ExtensionType>,
+    /// Metadata embedded via the token metadata extension, if any.
+    pub token_metadata: Option<TokenMetadata>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenAccountInfo {
+    pub address: Pubkey,
+    pub token_program: TokenProgram,
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    /// Balance in base units, see `MintInfo::decimals` to scale it.
+    pub amount: u64,
+    pub delegate: Option<Pubkey>,
+    pub delegated_amount: u64,
+    pub state: AccountState,
+    pub close_authority: Option<Pubkey>,
+    pub extensions: Vec<ExtensionType>,
+}
+
+pub async fn fetch_mint(connection: &RpcClient, mint: &Pubkey) -> Result<MintInfo> {
+    let account = connection.get_account(mint).await?;
+
+    decode_mint(mint, &account)
+}
+
+pub async fn fetch_token_account(connection: &RpcClient, token_account: &Pubkey) -> Result<TokenAccountInfo> {
+    let account = connection.get_account(token_account).await?;
+
+    decode_token_account(token_account, &account)
+}
+
+/// Every token account of `owner` under both token programs, not just the associated ones.
+pub async fn fetch_token_accounts_by_owner(connection: &RpcClient, owner: &Pubkey) -> Result<Vec<TokenAccountInfo>> {
+    let mut token_accounts = Vec::new();
+
+    for token_program in [TokenProgram::Token, TokenProgram::Token2022] {
+        let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            TOKEN_ACCOUNT_OWNER_OFFSET,
+            owner.as_ref(),
+        ))];
+
+        // Token-2022 accounts grow with their extensions, so their size can't be filtered on.
+        if token_program == TokenProgram::Token {
+            filters.push(RpcFilterType::DataSize(TokenAccount::LEN as u64));
+        }
+
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+
+        let accounts = connection
+            .get_program_accounts_with_config(&token_program.id(), config)
+            .await?;
+
+        // The memcmp can also match Token-2022 mints, which don't decode as accounts.
+        token_accounts.extend(
+            accounts
+                .iter()
+                .filter_map(|(address, account)| decode_token_account(address, account).ok()),
+        );
+    }
+
+    Ok(token_accounts)
+}
+
+/// Metaplex metadata of `mint`, `None` when it has no metadata account.
+pub async fn fetch_metaplex_metadata(connection: &RpcClient, mint: &Pubkey) -> Result<Option<Metadata>> {
+    let metadata_address = metaplex_metadata_address(mint);
+
+    let Some(account) = connection
+        .get_account_with_commitment(&metadata_address, connection.commitment())
+        .await?
+        .value
+    else {
+        return Ok(None);
+    };
+
+    let metadata = Metadata::safe_deserialize(&account.data).map_err(|error| SolanaUtilsError::Decode {
+        account: metadata_address,
+        message: error.to_string(),
+    })?;
+
+    Ok(Some(metadata))
+}
+
+pub fn decode_mint(address: &Pubkey, account: &Account) -> Result<MintInfo> {
+    let token_program = token_program_of(address, account)?;
+    let mint_state = StateWithExtensions::<Mint>::unpack(&account.data)?;
+    let mint = mint_state.base;
+
+    Ok(MintInfo {
+        address: *address,
+        token_program,
+        supply: TokenAmount::from_raw(mint.supply, mint.decimals),
+        decimals: mint.decimals,
+        mint_authority: mint.mint_authority.into(),
+        freeze_authority: mint.freeze_authority.into(),
+        extensions: mint_state.get_extension_types()?,
+        token_metadata: mint_state.get_variable_len_extension::<TokenMetadata>().ok(),
+    })
+}
+
+pub fn decode_token_account(address: &Pubkey, account: &Account) -> Result<TokenAccountInfo> {
+    let token_program = token_program_of(address, account)?;
+    let account_state = StateWithExtensions::<TokenAccount>::unpack(&account.data)?;
+    let token_account = account_state.base;
+
+    Ok(TokenAccountInfo {
+        address: *address,
+        token_program,
+        mint: token_account.mint,
+        owner: token_account.owner,
+        amount: token_account.amount,
+        delegate: token_account.delegate.into(),
+        delegated_amount: token_account.delegated_amount,
+        state: token_account.state,
+        close_authority: token_account.close_authority.into(),
+        extensions: account_state.get_extension_types()?,
+    })
+}
+
+fn token_program_of(address: &Pubkey, account: &Account) -> Result<TokenProgram> {
+    TokenProgram::from_program_id(&account.owner).ok_or(SolanaUtilsError::NotTokenAccount {
+        account: *address,
+        owner: account.owner,
+    })
+}
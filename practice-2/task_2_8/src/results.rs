@@ -0,0 +1,64 @@
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use spl_token_2022::extension::ExtensionType;
+
+use crate::amount::TokenAmount;
+use crate::token_program::TokenProgram;
+
+/// What every helper that sends a transaction gets back once it is confirmed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionReceipt {
+    pub signature: Signature,
+    pub slot: u64,
+    pub explorer_link: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolTransfer {
+    pub recipient: Pubkey,
+    pub lamports: u64,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatedMint {
+    pub mint: Pubkey,
+    pub mint_authority: Pubkey,
+    pub decimals: u8,
+    pub token_program: TokenProgram,
+    pub extensions: Vec<ExtensionType>,
+    pub explorer_link: String,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatedTokenAccount {
+    pub token_account: Pubkey,
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: TokenProgram,
+    pub explorer_link: String,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MintedTokens {
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: TokenAmount,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreatedMetadata {
+    pub mint: Pubkey,
+    pub metadata: Pubkey,
+    pub explorer_link: String,
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UpdatedMetadata {
+    pub mint: Pubkey,
+    pub metadata: Pubkey,
+    pub receipt: TransactionReceipt,
+}
@@ -0,0 +1,609 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::hash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+};
+use tokio::time::sleep;
+
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::nonce::{nonce_transaction_status, NonceTransactionStatus};
+use crate::signing_policy::SigningPolicy;
+use crate::tx_envelope::{BlockhashSource, TxEnvelope, TxEnvelopeFile};
+
+/// Bumped whenever the job file layout changes incompatibly.
+pub const SCHEDULED_JOB_VERSION: u32 = 1;
+
+/// When a scheduled transaction becomes due.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trigger {
+    /// Not before this Unix time, in seconds.
+    NotBefore(u64),
+    /// Once the cluster reached this slot.
+    Slot(u64),
+    /// Once `account` holds at least `lamports`, e.g. after a deposit arrived.
+    Balance { account: Pubkey, lamports: u64 },
+}
+
+impl Trigger {
+    /// `NotBefore` the given time from now.
+    pub fn after(delay: Duration) -> Self {
+        Trigger::NotBefore(unix_now() + delay.as_secs())
+    }
+
+    pub async fn is_due(&self, connection: &RpcClient) -> Result<bool> {
+        Ok(match self {
+            Trigger::NotBefore(timestamp) => unix_now() >= *timestamp,
+            Trigger::Slot(slot) => connection.get_slot().await? >= *slot,
+            Trigger::Balance { account, lamports } => connection.get_balance(account).await? >= *lamports,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Trigger::NotBefore(timestamp) => format!("not before Unix time {}", timestamp),
+            Trigger::Slot(slot) => format!("at slot {}", slot),
+            Trigger::Balance { account, lamports } => format!("once {} holds {} lamports", account, lamports),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobStatus {
+    /// Waiting for the trigger or for missing signatures.
+    Pending,
+    /// Sent but not confirmed yet. Resending is safe, the nonce lets it land only once.
+    Sent { signature: Signature },
+    Confirmed { signature: Signature, slot: u64 },
+    /// Rejected by the cluster, or the nonce account is gone.
+    Failed { message: String },
+    /// Someone else advanced the nonce, the transaction can never land.
+    Stale,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobStatus::Pending | JobStatus::Sent { .. })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Sent { .. } => "sent",
+            JobStatus::Confirmed { .. } => "confirmed",
+            JobStatus::Failed { .. } => "failed",
+            JobStatus::Stale => "stale",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A durable nonce transaction waiting in a `JobQueue`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScheduledJob {
+    /// Hash of the message, stays the same while signatures are added.
+    pub id: String,
+    pub envelope: TxEnvelope,
+    pub trigger: Trigger,
+    pub status: JobStatus,
+    /// Unix times in seconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// How often the transaction was sent.
+    pub attempts: u32,
+    /// Why the last pass couldn't process the job, e.g. an RPC error. It is retried on the next pass.
+    pub last_error: Option<String>,
+}
+
+impl ScheduledJob {
+    /// The durable nonce the transaction is signed against.
+    pub fn nonce_account(&self) -> Option<Pubkey> {
+        match self.envelope.blockhash_source {
+            BlockhashSource::Nonce { account, .. } => Some(account),
+            BlockhashSource::Recent { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JobFile {
+    version: u32,
+    id: String,
+    trigger: TriggerFile,
+    status: StatusFile,
+    created_at: u64,
+    updated_at: u64,
+    attempts: u32,
+    #[serde(default)]
+    last_error: Option<String>,
+    envelope: TxEnvelopeFile,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TriggerFile {
+    NotBefore { unix_timestamp: u64 },
+    Slot { slot: u64 },
+    Balance { account: String, lamports: u64 },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StatusFile {
+    status: String,
+    #[serde(default)]
+    signature: Option<String>,
+    #[serde(default)]
+    slot: Option<u64>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// Scheduled transactions kept as one JSON file each in a directory,
+/// so a runner can pick them up again after a restart.
+#[derive(Clone, Debug)]
+pub struct JobQueue {
+    dir: PathBuf,
+}
+
+impl JobQueue {
+    /// Opens the queue in `dir`, creating the directory if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+
+        fs::create_dir_all(dir).map_err(|error| job_error(dir, error.to_string()))?;
+
+        Ok(Self { dir: dir.to_path_buf() })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Adds a transaction to run once `trigger` fires. Only durable nonce transactions
+    /// can wait, missing signatures can still be added until it is sent.
+    pub fn schedule(&self, envelope: TxEnvelope, trigger: Trigger) -> Result<ScheduledJob> {
+        if !matches!(envelope.blockhash_source, BlockhashSource::Nonce { .. }) {
+            return Err(SolanaUtilsError::InvalidInput(
+                "only durable nonce transactions can be scheduled, a recent blockhash expires in about a minute"
+                    .to_string(),
+            ));
+        }
+        envelope.check_blockhash_source()?;
+
+        let id = hash(&envelope.transaction.message.serialize()).to_string();
+        if self.path(&id).exists() {
+            return Err(SolanaUtilsError::InvalidInput(format!("the transaction is already scheduled as {}", id)));
+        }
+
+        let now = unix_now();
+        let job = ScheduledJob {
+            id,
+            envelope,
+            trigger,
+            status: JobStatus::Pending,
+            created_at: now,
+            updated_at: now,
+            attempts: 0,
+            last_error: None,
+        };
+        self.save(&job)?;
+
+        Ok(job)
+    }
+
+    pub fn job(&self, id: &str) -> Result<ScheduledJob> {
+        self.load(&self.path(id))
+    }
+
+    /// Every job in the queue, oldest first. Files that can't be read are skipped,
+    /// so one of them doesn't stop the runner, `invalid_jobs` reports them.
+    pub fn jobs(&self) -> Result<Vec<ScheduledJob>> {
+        let mut jobs: Vec<ScheduledJob> = self.read_jobs()?.into_iter().filter_map(std::result::Result::ok).collect();
+        jobs.sort_by_key(|job| job.created_at);
+
+        Ok(jobs)
+    }
+
+    /// Why each job file `jobs` skips can't be read, e.g. edited by hand or written by another version.
+    pub fn invalid_jobs(&self) -> Result<Vec<SolanaUtilsError>> {
+        Ok(self.read_jobs()?.into_iter().filter_map(std::result::Result::err).collect())
+    }
+
+    /// Nonce accounts of the jobs that are pending or sent. Signing anything else
+    /// against them would invalidate those jobs.
+    pub fn nonces_in_use(&self) -> Result<Vec<Pubkey>> {
+        Ok(self
+            .jobs()?
+            .iter()
+            .filter(|job| !job.status.is_finished())
+            .filter_map(ScheduledJob::nonce_account)
+            .collect())
+    }
+
+    /// Stops the runner from sending a pending job. A copy signed elsewhere can still
+    /// land until the nonce is advanced.
+    pub fn cancel(&self, id: &str) -> Result<ScheduledJob> {
+        let mut job = self.job(id)?;
+
+        if job.status != JobStatus::Pending {
+            return Err(SolanaUtilsError::InvalidInput(format!(
+                "job {} is {}, only pending jobs can be cancelled",
+                id,
+                job.status.as_str()
+            )));
+        }
+
+        job.status = JobStatus::Cancelled;
+        self.save(&job)?;
+
+        Ok(job)
+    }
+
+    /// Written to a temporary file first, so an interruption never leaves half a job behind.
+    pub fn save(&self, job: &ScheduledJob) -> Result<()> {
+        let path = self.path(&job.id);
+
+        let file = JobFile {
+            version: SCHEDULED_JOB_VERSION,
+            id: job.id.clone(),
+            trigger: match &job.trigger {
+                Trigger::NotBefore(unix_timestamp) => TriggerFile::NotBefore { unix_timestamp: *unix_timestamp },
+                Trigger::Slot(slot) => TriggerFile::Slot { slot: *slot },
+                Trigger::Balance { account, lamports } => TriggerFile::Balance {
+                    account: account.to_string(),
+                    lamports: *lamports,
+                },
+            },
+            status: StatusFile {
+                status: job.status.as_str().to_string(),
+                signature: match &job.status {
+                    JobStatus::Sent { signature } | JobStatus::Confirmed { signature, .. } => Some(signature.to_string()),
+                    _ => None,
+                },
+                slot: match &job.status {
+                    JobStatus::Confirmed { slot, .. } => Some(*slot),
+                    _ => None,
+                },
+                message: match &job.status {
+                    JobStatus::Failed { message } => Some(message.clone()),
+                    _ => None,
+                },
+            },
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+            attempts: job.attempts,
+            last_error: job.last_error.clone(),
+            envelope: job.envelope.to_file()?,
+        };
+
+        let content = serde_json::to_string_pretty(&file).map_err(|error| job_error(&path, error.to_string()))?;
+        let temporary_path = path.with_extension("json.tmp");
+
+        fs::write(&temporary_path, content)
+            .and_then(|_| fs::rename(&temporary_path, &path))
+            .map_err(|error| job_error(&path, error.to_string()))
+    }
+
+    fn read_jobs(&self) -> Result<Vec<Result<ScheduledJob>>> {
+        let entries = fs::read_dir(&self.dir).map_err(|error| job_error(&self.dir, error.to_string()))?;
+
+        let mut jobs = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|error| job_error(&self.dir, error.to_string()))?.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                jobs.push(self.load(&path));
+            }
+        }
+
+        Ok(jobs)
+    }
+
+    fn load(&self, path: &Path) -> Result<ScheduledJob> {
+        let content = fs::read_to_string(path).map_err(|error| job_error(path, error.to_string()))?;
+        let file: JobFile = serde_json::from_str(&content).map_err(|error| job_error(path, error.to_string()))?;
+
+        if file.version != SCHEDULED_JOB_VERSION {
+            return Err(job_error(path, format!("unsupported version {}", file.version)));
+        }
+
+        let signature = || -> Result<Signature> {
+            let signature = file
+                .status
+                .signature
+                .as_deref()
+                .ok_or_else(|| job_error(path, "missing signature".to_string()))?;
+            Signature::from_str(signature).map_err(|error| job_error(path, error.to_string()))
+        };
+
+        let status = match file.status.status.as_str() {
+            "pending" => JobStatus::Pending,
+            "sent" => JobStatus::Sent { signature: signature()? },
+            "confirmed" => JobStatus::Confirmed {
+                signature: signature()?,
+                slot: file.status.slot.unwrap_or_default(),
+            },
+            "failed" => JobStatus::Failed {
+                message: file.status.message.clone().unwrap_or_default(),
+            },
+            "stale" => JobStatus::Stale,
+            "cancelled" => JobStatus::Cancelled,
+            other => return Err(job_error(path, format!("unknown status `{}`", other))),
+        };
+
+        // `from_file` also rejects a nonce source the transaction doesn't advance.
+        let envelope = TxEnvelope::from_file(file.envelope)?;
+        if !matches!(envelope.blockhash_source, BlockhashSource::Nonce { .. }) {
+            return Err(job_error(path, "not a durable nonce transaction".to_string()));
+        }
+
+        Ok(ScheduledJob {
+            id: file.id,
+            envelope,
+            trigger: match file.trigger {
+                TriggerFile::NotBefore { unix_timestamp } => Trigger::NotBefore(unix_timestamp),
+                TriggerFile::Slot { slot } => Trigger::Slot(slot),
+                TriggerFile::Balance { account, lamports } => Trigger::Balance {
+                    account: parse_pubkey(&account)?,
+                    lamports,
+                },
+            },
+            status,
+            created_at: file.created_at,
+            updated_at: file.updated_at,
+            attempts: file.attempts,
+            last_error: file.last_error,
+        })
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+/// One pass over the queue: sends the due jobs and settles the ones sent earlier.
+/// `signers` add their signatures to due jobs that still miss them, as far as
+/// `policy` lets them, a job it rejects fails. An error with one job, e.g. a flaky RPC node, is kept in its `last_error`
+/// and the job is retried on the next pass. Returns the jobs that changed.
+pub async fn run_due(
+    connection: &RpcClient,
+    queue: &JobQueue,
+    signers: &[&Keypair],
+    policy: &SigningPolicy,
+) -> Result<Vec<ScheduledJob>> {
+    let mut changed = Vec::new();
+
+    for mut job in queue.jobs()? {
+        let (status_before, error_before) = (job.status.clone(), job.last_error.clone());
+
+        let result = match &job.status {
+            JobStatus::Pending => run_pending(connection, queue, &mut job, signers, policy).await,
+            JobStatus::Sent { signature } => {
+                let signature = *signature;
+                settle_sent(connection, queue, &mut job, &signature).await
+            }
+            _ => continue,
+        };
+        job.last_error = result.err().map(|error| error.to_string());
+
+        if job.status != status_before || job.last_error != error_before {
+            job.updated_at = unix_now();
+            queue.save(&job)?;
+            changed.push(job);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Repeats `run_due` every `poll_interval` until no job is pending or sent.
+/// Returns every job that changed on the way, in order.
+pub async fn run_until_idle(
+    connection: &RpcClient,
+    queue: &JobQueue,
+    signers: &[&Keypair],
+    policy: &SigningPolicy,
+    poll_interval: Duration,
+) -> Result<Vec<ScheduledJob>> {
+    let mut changed = Vec::new();
+
+    loop {
+        changed.extend(run_due(connection, queue, signers, policy).await?);
+
+        if queue.jobs()?.iter().all(|job| job.status.is_finished()) {
+            return Ok(changed);
+        }
+
+        sleep(poll_interval).await;
+    }
+}
+
+async fn run_pending(
+    connection: &RpcClient,
+    queue: &JobQueue,
+    job: &mut ScheduledJob,
+    signers: &[&Keypair],
+    policy: &SigningPolicy,
+) -> Result<()> {
+    if !job.trigger.is_due(connection).await? {
+        return Ok(());
+    }
+
+    // Checked again now, the job may have been queued without the policy or before it changed.
+    let missing = job.envelope.missing_signers();
+    for signer in signers.iter().filter(|signer| missing.contains(&signer.pubkey())) {
+        match policy.sign(&mut job.envelope, signer) {
+            Err(error @ SolanaUtilsError::SigningRejected { .. }) => {
+                job.status = JobStatus::Failed { message: error.to_string() };
+                return Ok(());
+            }
+            result => result?,
+        }
+    }
+
+    // Stays pending until the remaining signers add theirs.
+    if !job.envelope.is_complete() {
+        return Ok(());
+    }
+
+    if !check_nonce(connection, job).await? {
+        return Ok(());
+    }
+
+    send(connection, queue, job).await
+}
+
+async fn settle_sent(
+    connection: &RpcClient,
+    queue: &JobQueue,
+    job: &mut ScheduledJob,
+    signature: &Signature,
+) -> Result<()> {
+    match signature_status(connection, signature).await? {
+        Some(Landed::Failed(message)) => {
+            job.status = JobStatus::Failed { message };
+            Ok(())
+        }
+        Some(Landed::Succeeded { slot, confirmed }) => {
+            if confirmed {
+                job.status = JobStatus::Confirmed { signature: *signature, slot };
+            }
+            Ok(())
+        }
+        // Dropped on the way, the same transaction is still good while the nonce hasn't moved.
+        None => {
+            if check_nonce(connection, job).await? {
+                send(connection, queue, job).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Marks the job stale or failed and returns `false` when its nonce can't be used anymore.
+async fn check_nonce(connection: &RpcClient, job: &mut ScheduledJob) -> Result<bool> {
+    let BlockhashSource::Nonce { account, authority } = job.envelope.blockhash_source else {
+        job.status = JobStatus::Failed {
+            message: "not a durable nonce transaction".to_string(),
+        };
+        return Ok(false);
+    };
+
+    job.status = match nonce_transaction_status(connection, &job.envelope).await? {
+        NonceTransactionStatus::Executable { nonce } if nonce.authority == authority => return Ok(true),
+        NonceTransactionStatus::Executable { nonce } => JobStatus::Failed {
+            message: format!("nonce {} is now controlled by {}", account, nonce.authority),
+        },
+        // A copy sent without the runner landed.
+        NonceTransactionStatus::Executed { signature, slot } => JobStatus::Confirmed { signature, slot },
+        NonceTransactionStatus::Invalidated { .. } => JobStatus::Stale,
+        NonceTransactionStatus::NonceClosed => JobStatus::Failed {
+            message: format!("nonce account {} no longer exists", account),
+        },
+    };
+
+    Ok(false)
+}
+
+/// Records the signature before sending, so a crash in between is settled on the next pass.
+async fn send(connection: &RpcClient, queue: &JobQueue, job: &mut ScheduledJob) -> Result<()> {
+    job.status = JobStatus::Sent {
+        signature: job.envelope.transaction.signatures[0],
+    };
+    job.attempts += 1;
+    job.updated_at = unix_now();
+    queue.save(job)?;
+
+    if let Err(error) = connection.send_transaction(&job.envelope.transaction).await {
+        match SolanaUtilsError::from_transaction_error(error, &job.envelope.error_message()) {
+            error @ (SolanaUtilsError::Simulation { .. } | SolanaUtilsError::InsufficientFunds { .. }) => {
+                job.status = JobStatus::Failed { message: error.to_string() };
+            }
+            error => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+/// What became of a transaction the cluster has seen.
+enum Landed {
+    Failed(String),
+    /// `confirmed` once it reached the connection's commitment.
+    Succeeded { slot: u64, confirmed: bool },
+}
+
+async fn signature_status(connection: &RpcClient, signature: &Signature) -> Result<Option<Landed>> {
+    let status = connection
+        .get_signature_statuses_with_history(&[*signature])
+        .await?
+        .value
+        .into_iter()
+        .flatten()
+        .next();
+
+    Ok(status.map(|status| match status.err {
+        Some(error) => Landed::Failed(error.to_string()),
+        None => Landed::Succeeded {
+            slot: status.slot,
+            confirmed: status.satisfies_commitment(connection.commitment()),
+        },
+    }))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn job_error(path: &Path, message: String) -> SolanaUtilsError {
+    SolanaUtilsError::File {
+        path: path.to_path_buf(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    #[allow(deprecated)]
+    use solana_sdk::system_instruction;
+    use solana_sdk::{message::Message, transaction::Transaction};
+
+    use super::*;
+    use crate::explorer_link::Cluster;
+
+    #[test]
+    fn skips_job_files_that_cant_be_read() {
+        let dir = env::temp_dir().join(format!("job-queue-{}", Pubkey::new_unique()));
+        let queue = JobQueue::open(&dir).unwrap();
+
+        let nonce = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let instructions = [
+            system_instruction::advance_nonce_account(&nonce, &authority),
+            system_instruction::transfer(&authority, &Pubkey::new_unique(), 1),
+        ];
+        let transaction = Transaction::new_unsigned(Message::new(&instructions, Some(&authority)));
+        let envelope = TxEnvelope::new(transaction, Cluster::Devnet, BlockhashSource::Nonce { account: nonce, authority });
+        let job = queue.schedule(envelope, Trigger::NotBefore(0)).unwrap();
+
+        let garbage = dir.join("garbage.json");
+        fs::write(&garbage, "{ not a job").unwrap();
+
+        let jobs = queue.jobs();
+        let invalid = queue.invalid_jobs();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(jobs.unwrap(), vec![job]);
+        let invalid = invalid.unwrap();
+        assert_eq!(invalid.len(), 1);
+        assert!(matches!(&invalid[0], SolanaUtilsError::File { path, .. } if *path == garbage));
+    }
+}
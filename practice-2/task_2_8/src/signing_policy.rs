@@ -0,0 +1,745 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+#[allow(deprecated)]
+use solana_sdk::{compute_budget, system_program};
+use solana_sdk::{
+    instruction::CompiledInstruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+    system_instruction::SystemInstruction,
+};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+use spl_token_2022::extension::transfer_fee::instruction::TransferFeeInstruction;
+use spl_token_2022::instruction::TokenInstruction;
+
+use crate::amount::TokenAmount;
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::inspector::mint_position;
+use crate::token_program::TokenProgram;
+use crate::tx_envelope::{BlockhashSource, TxEnvelope};
+use crate::tx_sender::MAX_COMPUTE_UNIT_LIMIT;
+
+pub const SIGNING_POLICY_ENV: &str = "SIGNING_POLICY";
+
+/// Base fee of every signature the transaction needs.
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+/// Compute units each instruction gets when the transaction doesn't set a limit.
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u64 = 200_000;
+/// A Token-2022 account with the immutable owner extension, the largest account
+/// an associated token account instruction creates.
+const ASSOCIATED_TOKEN_ACCOUNT_LEN: usize = 170;
+
+/// What a co-signer agrees to sign, checked before the signature is added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SigningPolicy {
+    /// Every instruction has to call one of these.
+    pub allowed_programs: Vec<Pubkey>,
+    /// Upper bound for the lamports leaving the signer: fees, transfers and rent it funds.
+    pub max_sol_outflow: Option<TokenAmount>,
+    /// Token instructions may only touch these mints, empty allows any mint.
+    pub allowed_mints: Vec<Pubkey>,
+    /// SOL and tokens may only go to these accounts or their associated token
+    /// accounts, empty allows any destination. Authorities and delegates of the
+    /// signer's token accounts may only be handed to these accounts.
+    pub allowed_destinations: Vec<Pubkey>,
+    /// Only sign transactions that start by advancing a durable nonce.
+    pub require_nonce_advance: bool,
+    /// Sign system instructions this version can't decode, whose effect on the signer is unknown.
+    pub allow_undecoded_system_instructions: bool,
+    /// Sign token instructions this version can't decode, whose effect on the signer is unknown.
+    pub allow_undecoded_token_instructions: bool,
+}
+
+/// Layout of the YAML policy file, every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PolicyFile {
+    allowed_programs: Option<Vec<String>>,
+    /// In SOL, e.g. `"0.01"`.
+    max_sol_outflow: Option<String>,
+    allowed_mints: Vec<String>,
+    allowed_destinations: Vec<String>,
+    require_nonce_advance: bool,
+    allow_undecoded_system_instructions: bool,
+    allow_undecoded_token_instructions: bool,
+}
+
+/// Whether the policy lets a signer sign, and why not.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PolicyVerdict {
+    Accept,
+    Reject { reasons: Vec<String> },
+}
+
+impl PolicyVerdict {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, PolicyVerdict::Accept)
+    }
+}
+
+impl fmt::Display for PolicyVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyVerdict::Accept => f.write_str("accepted"),
+            PolicyVerdict::Reject { reasons } => write!(f, "rejected: {}", reasons.join("; ")),
+        }
+    }
+}
+
+impl Default for SigningPolicy {
+    /// System, token, memo and compute budget instructions moving at most 0.01 SOL
+    /// out of the signer, enough for fees and a few token accounts.
+    fn default() -> Self {
+        Self {
+            allowed_programs: vec![
+                system_program::id(),
+                TokenProgram::Token.id(),
+                TokenProgram::Token2022.id(),
+                spl_associated_token_account::id(),
+                spl_memo::id(),
+                compute_budget::id(),
+            ],
+            max_sol_outflow: Some(TokenAmount::from_lamports(10_000_000)),
+            allowed_mints: Vec::new(),
+            allowed_destinations: Vec::new(),
+            require_nonce_advance: false,
+            allow_undecoded_system_instructions: false,
+            allow_undecoded_token_instructions: false,
+        }
+    }
+}
+
+impl SigningPolicy {
+    /// Reads a YAML policy, fields left out keep their defaults.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config_error = |message: String| SolanaUtilsError::Config(format!("{}: {}", path.display(), message));
+
+        let content = fs::read_to_string(path).map_err(|error| config_error(error.to_string()))?;
+        let file: PolicyFile = serde_yaml::from_str(&content).map_err(|error| config_error(error.to_string()))?;
+
+        let mut policy = Self::default();
+
+        if let Some(programs) = file.allowed_programs {
+            policy.allowed_programs = programs.iter().map(|program| parse_pubkey(program)).collect::<Result<_>>()?;
+        }
+        if let Some(max_sol_outflow) = file.max_sol_outflow {
+            policy.max_sol_outflow = Some(TokenAmount::sol(&max_sol_outflow)?);
+        }
+        policy.allowed_mints = file.allowed_mints.iter().map(|mint| parse_pubkey(mint)).collect::<Result<_>>()?;
+        policy.allowed_destinations = file
+            .allowed_destinations
+            .iter()
+            .map(|destination| parse_pubkey(destination))
+            .collect::<Result<_>>()?;
+        policy.require_nonce_advance = file.require_nonce_advance;
+        policy.allow_undecoded_system_instructions = file.allow_undecoded_system_instructions;
+        policy.allow_undecoded_token_instructions = file.allow_undecoded_token_instructions;
+
+        Ok(policy)
+    }
+
+    /// The policy file named by `SIGNING_POLICY`, or the defaults when it is not set.
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        match env::var(SIGNING_POLICY_ENV) {
+            Ok(path) => Self::from_file(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Checks `envelope` on behalf of `signer`, collecting every violation.
+    /// Works offline, token amounts aren't needed to decide.
+    pub fn evaluate(&self, envelope: &TxEnvelope, signer: &Pubkey) -> PolicyVerdict {
+        let mut reasons = Vec::new();
+
+        if !envelope.required_signers().contains(signer) {
+            reasons.push(format!("{} is not a signer of this transaction", signer));
+        }
+
+        let uses_nonce = matches!(envelope.blockhash_source, BlockhashSource::Nonce { .. })
+            && envelope.transaction.uses_durable_nonce();
+        if self.require_nonce_advance && !uses_nonce {
+            reasons.push("the transaction doesn't start by advancing a durable nonce".to_string());
+        }
+
+        let account_keys = envelope.transaction.message.static_account_keys();
+        for (index, instruction) in envelope.transaction.message.instructions().iter().enumerate() {
+            let program_id = account_keys[instruction.program_id_index as usize];
+
+            if !self.allowed_programs.contains(&program_id) {
+                reasons.push(format!("instruction {} calls program {}, which is not allowed", index, program_id));
+                continue;
+            }
+
+            let checks = [
+                self.check_system(envelope, &program_id, instruction, signer),
+                self.check_token(envelope, &program_id, instruction, signer),
+                self.check_mint(envelope, &program_id, instruction),
+                self.check_destination(envelope, &program_id, instruction),
+            ];
+            for reason in checks.into_iter().filter_map(std::result::Result::err) {
+                reasons.push(format!("instruction {}: {}", index, reason));
+            }
+        }
+
+        if let Some(max_sol_outflow) = &self.max_sol_outflow {
+            let outflow = sol_outflow(envelope, signer);
+            if outflow > max_sol_outflow.raw() {
+                reasons.push(format!(
+                    "{} would spend up to {} SOL, the limit is {} SOL",
+                    signer,
+                    TokenAmount::from_lamports(outflow),
+                    max_sol_outflow
+                ));
+            }
+        }
+
+        if reasons.is_empty() {
+            PolicyVerdict::Accept
+        } else {
+            PolicyVerdict::Reject { reasons }
+        }
+    }
+
+    /// Adds the signature of `signer` only when the policy accepts the transaction.
+    pub fn sign(&self, envelope: &mut TxEnvelope, signer: &Keypair) -> Result<()> {
+        if let PolicyVerdict::Reject { reasons } = self.evaluate(envelope, &signer.pubkey()) {
+            return Err(SolanaUtilsError::SigningRejected {
+                signer: signer.pubkey(),
+                reasons,
+            });
+        }
+
+        envelope.sign(signer)
+    }
+
+    /// System instructions that hand the signer's account or nonce to someone else
+    /// move no lamports right away, so `sol_outflow` can't catch them.
+    fn check_system(
+        &self,
+        envelope: &TxEnvelope,
+        program_id: &Pubkey,
+        instruction: &CompiledInstruction,
+        signer: &Pubkey,
+    ) -> std::result::Result<(), String> {
+        if *program_id != system_program::id() {
+            return Ok(());
+        }
+
+        let Ok(system_instruction) = bincode::deserialize::<SystemInstruction>(&instruction.data) else {
+            if self.allow_undecoded_system_instructions {
+                return Ok(());
+            }
+            return Err("the system instruction can't be decoded".to_string());
+        };
+
+        // Positions of the accounts the signer must not be: the target, or the base or authority signing for it.
+        let (action, positions): (&str, &[usize]) = match system_instruction {
+            SystemInstruction::Assign { .. } => ("assign", &[0]),
+            SystemInstruction::Allocate { .. } => ("allocate", &[0]),
+            SystemInstruction::AssignWithSeed { .. } | SystemInstruction::AllocateWithSeed { .. } => {
+                ("assign or allocate", &[0, 1])
+            }
+            SystemInstruction::AuthorizeNonceAccount(_) => ("hand over a nonce of", &[1]),
+            SystemInstruction::WithdrawNonceAccount(_) => ("withdraw from a nonce of", &[4]),
+            _ => return Ok(()),
+        };
+
+        if positions.iter().any(|position| account(envelope, instruction, *position) == Some(*signer)) {
+            return Err(format!("the instruction would {} {}", action, signer));
+        }
+
+        Ok(())
+    }
+
+    /// Token instructions that hand the signer's authority over a token account or mint
+    /// to someone else, the tokens can be moved later without another signature.
+    fn check_token(
+        &self,
+        envelope: &TxEnvelope,
+        program_id: &Pubkey,
+        instruction: &CompiledInstruction,
+        signer: &Pubkey,
+    ) -> std::result::Result<(), String> {
+        if TokenProgram::from_program_id(program_id).is_none() {
+            return Ok(());
+        }
+
+        let Ok(token_instruction) = TokenInstruction::unpack(&instruction.data) else {
+            if self.allow_undecoded_token_instructions {
+                return Ok(());
+            }
+            return Err("the token instruction can't be decoded".to_string());
+        };
+
+        // Position of the current authority and the account it hands over to, `None` when it is removed.
+        let (action, authority_position, new_authority) = match token_instruction {
+            TokenInstruction::SetAuthority { new_authority, .. } => ("set the authority", 1, new_authority.into()),
+            TokenInstruction::Approve { .. } => (
+                "approve a delegate",
+                2,
+                Some(account(envelope, instruction, 1).ok_or("the delegate has to be a static account")?),
+            ),
+            TokenInstruction::ApproveChecked { .. } => (
+                "approve a delegate",
+                3,
+                Some(account(envelope, instruction, 2).ok_or("the delegate has to be a static account")?),
+            ),
+            _ => return Ok(()),
+        };
+
+        if account(envelope, instruction, authority_position) != Some(*signer) {
+            return Ok(());
+        }
+
+        match new_authority {
+            Some(new_authority) if new_authority != *signer && !self.allowed_destinations.contains(&new_authority) => {
+                Err(format!("the instruction would {} of {} to {}", action, signer, new_authority))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_mint(
+        &self,
+        envelope: &TxEnvelope,
+        program_id: &Pubkey,
+        instruction: &CompiledInstruction,
+    ) -> std::result::Result<(), String> {
+        if self.allowed_mints.is_empty() {
+            return Ok(());
+        }
+
+        let Some(position) = mint_position(program_id, &instruction.data)? else {
+            return Ok(());
+        };
+
+        let mint = account(envelope, instruction, position).ok_or("the mint has to be a static account")?;
+        if !self.allowed_mints.contains(&mint) {
+            return Err(format!("mint {} is not allowed", mint));
+        }
+
+        Ok(())
+    }
+
+    #[allow(deprecated)]
+    fn check_destination(
+        &self,
+        envelope: &TxEnvelope,
+        program_id: &Pubkey,
+        instruction: &CompiledInstruction,
+    ) -> std::result::Result<(), String> {
+        if self.allowed_destinations.is_empty() {
+            return Ok(());
+        }
+
+        let data = &instruction.data;
+
+        // Position of the receiving account and, for token transfers that name it, of the mint.
+        let (position, token_mint) = if *program_id == system_program::id() {
+            match bincode::deserialize::<SystemInstruction>(data) {
+                Ok(SystemInstruction::Transfer { .. } | SystemInstruction::WithdrawNonceAccount(_)) => (1, None),
+                Ok(SystemInstruction::TransferWithSeed { .. }) => (2, None),
+                _ => return Ok(()),
+            }
+        } else if let Some(token_program) = TokenProgram::from_program_id(program_id) {
+            match TokenInstruction::unpack(data) {
+                Ok(TokenInstruction::Transfer { .. } | TokenInstruction::CloseAccount) => (1, None),
+                Ok(TokenInstruction::TransferChecked { .. }) => (2, Some((1, token_program))),
+                Ok(TokenInstruction::MintTo { .. } | TokenInstruction::MintToChecked { .. }) => {
+                    (1, Some((0, token_program)))
+                }
+                Ok(TokenInstruction::TransferFeeExtension) => match TransferFeeInstruction::unpack(&data[1..]) {
+                    Ok(TransferFeeInstruction::TransferCheckedWithFee { .. }) => (2, Some((1, token_program))),
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            }
+        } else {
+            return Ok(());
+        };
+
+        let destination = account(envelope, instruction, position).ok_or("the destination has to be a static account")?;
+        if self.allowed_destinations.contains(&destination) {
+            return Ok(());
+        }
+
+        // Tokens may also go to the associated token account of an allowed wallet.
+        let to_allowed_wallet = token_mint.is_some_and(|(mint_position, token_program)| {
+            account(envelope, instruction, mint_position).is_some_and(|mint| {
+                self.allowed_destinations.iter().any(|wallet| {
+                    get_associated_token_address_with_program_id(wallet, &mint, &token_program.id()) == destination
+                })
+            })
+        });
+        if to_allowed_wallet {
+            return Ok(());
+        }
+
+        Err(format!("destination {} is not allowed", destination))
+    }
+}
+
+/// The account at `position` of `instruction`, `None` when it comes from a lookup table.
+fn account(envelope: &TxEnvelope, instruction: &CompiledInstruction, position: usize) -> Option<Pubkey> {
+    let index = *instruction.accounts.get(position)? as usize;
+
+    envelope.transaction.message.static_account_keys().get(index).copied()
+}
+
+/// The most lamports `signer` can lose: the fee when it pays it, SOL it transfers
+/// and the rent of accounts it funds. Other programs can't move its lamports
+/// without being allowed by the policy first.
+fn sol_outflow(envelope: &TxEnvelope, signer: &Pubkey) -> u64 {
+    let message = &envelope.transaction.message;
+    let account_keys = message.static_account_keys();
+    let is_signer_at = |instruction: &CompiledInstruction, position: usize| {
+        instruction
+            .accounts
+            .get(position)
+            .is_some_and(|index| account_keys.get(*index as usize) == Some(signer))
+    };
+
+    let mut outflow: u64 = 0;
+    let mut compute_unit_limit = None;
+    let mut compute_unit_price: u64 = 0;
+    let mut instruction_count: u64 = 0;
+
+    for instruction in message.instructions() {
+        let program_id = account_keys[instruction.program_id_index as usize];
+
+        if program_id == compute_budget::id() {
+            // Tag byte followed by a little endian value, see `ComputeBudgetInstruction`.
+            match instruction.data.as_slice() {
+                [2, limit @ ..] if limit.len() == 4 => {
+                    compute_unit_limit = Some(u32::from_le_bytes(limit.try_into().unwrap()) as u64);
+                }
+                [3, price @ ..] if price.len() == 8 => {
+                    compute_unit_price = u64::from_le_bytes(price.try_into().unwrap());
+                }
+                _ => {}
+            }
+            continue;
+        }
+        instruction_count += 1;
+
+        let spent = if program_id == system_program::id() {
+            match bincode::deserialize::<SystemInstruction>(&instruction.data) {
+                Ok(SystemInstruction::Transfer { lamports })
+                | Ok(SystemInstruction::CreateAccount { lamports, .. })
+                | Ok(SystemInstruction::CreateAccountWithSeed { lamports, .. })
+                    if is_signer_at(instruction, 0) =>
+                {
+                    lamports
+                }
+                // The lamports leave an address derived from the signer, the base.
+                Ok(SystemInstruction::TransferWithSeed { lamports, .. }) if is_signer_at(instruction, 1) => lamports,
+                _ => 0,
+            }
+        } else if program_id == spl_associated_token_account::id() && is_signer_at(instruction, 0) {
+            // Counted even for create if missing, the account may not exist yet.
+            Rent::default().minimum_balance(ASSOCIATED_TOKEN_ACCOUNT_LEN)
+        } else {
+            0
+        };
+        outflow = outflow.saturating_add(spent);
+    }
+
+    if account_keys.first() == Some(signer) {
+        let signatures = envelope.required_signers().len() as u64;
+        let compute_units = compute_unit_limit
+            .unwrap_or(instruction_count * DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+            .min(MAX_COMPUTE_UNIT_LIMIT as u64);
+        // The price is in micro-lamports per compute unit, rounded up.
+        let priority_fee = (compute_units as u128 * compute_unit_price as u128).div_ceil(1_000_000) as u64;
+
+        outflow = outflow
+            .saturating_add(signatures * LAMPORTS_PER_SIGNATURE)
+            .saturating_add(priority_fee);
+    }
+
+    outflow
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(deprecated)]
+    use solana_sdk::system_instruction;
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+        transaction::Transaction,
+    };
+
+    use super::*;
+    use crate::explorer_link::Cluster;
+
+    fn envelope(payer: &Pubkey, instructions: &[Instruction]) -> TxEnvelope {
+        let transaction = Transaction::new_unsigned(Message::new(instructions, Some(payer)));
+
+        TxEnvelope::new(transaction, Cluster::Devnet, BlockhashSource::Recent { last_valid_block_height: None })
+    }
+
+    fn reasons(verdict: PolicyVerdict) -> Vec<String> {
+        match verdict {
+            PolicyVerdict::Accept => Vec::new(),
+            PolicyVerdict::Reject { reasons } => reasons,
+        }
+    }
+
+    #[test]
+    fn accepts_a_small_transfer() {
+        let signer = Pubkey::new_unique();
+        let envelope = envelope(&signer, &[system_instruction::transfer(&signer, &Pubkey::new_unique(), 1_000_000)]);
+
+        assert_eq!(SigningPolicy::default().evaluate(&envelope, &signer), PolicyVerdict::Accept);
+    }
+
+    #[test]
+    fn rejects_transfers_over_the_limit() {
+        let signer = Pubkey::new_unique();
+        let envelope = envelope(&signer, &[system_instruction::transfer(&signer, &Pubkey::new_unique(), 20_000_000)]);
+
+        let reasons = reasons(SigningPolicy::default().evaluate(&envelope, &signer));
+        assert_eq!(reasons.len(), 1);
+        assert!(reasons[0].contains("the limit is 0.01 SOL"), "{:?}", reasons);
+    }
+
+    #[test]
+    fn sol_outflow_counts_fees_only_for_the_fee_payer() {
+        let payer = Pubkey::new_unique();
+        let signer = Pubkey::new_unique();
+        let envelope = envelope(
+            &payer,
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(100_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000_000),
+                system_instruction::transfer(&signer, &payer, 1_000),
+            ],
+        );
+
+        // Two signatures plus 100_000 units at one lamport each.
+        assert_eq!(sol_outflow(&envelope, &payer), 2 * LAMPORTS_PER_SIGNATURE + 100_000);
+        assert_eq!(sol_outflow(&envelope, &signer), 1_000);
+    }
+
+    #[test]
+    fn sol_outflow_counts_funded_accounts() {
+        let signer = Pubkey::new_unique();
+        let envelope = envelope(
+            &signer,
+            &[
+                system_instruction::create_account(&signer, &Pubkey::new_unique(), 3_000_000, 0, &Pubkey::new_unique()),
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &signer,
+                    &Pubkey::new_unique(),
+                    &Pubkey::new_unique(),
+                    &spl_token::id(),
+                ),
+            ],
+        );
+
+        assert_eq!(
+            sol_outflow(&envelope, &signer),
+            3_000_000 + Rent::default().minimum_balance(ASSOCIATED_TOKEN_ACCOUNT_LEN) + 2 * LAMPORTS_PER_SIGNATURE
+        );
+    }
+
+    #[test]
+    fn rejects_handing_the_signer_over() {
+        let signer = Pubkey::new_unique();
+        let nonce = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+
+        let instructions = [
+            system_instruction::assign(&signer, &program),
+            system_instruction::allocate(&signer, 1_000),
+            system_instruction::assign_with_seed(
+                &Pubkey::create_with_seed(&signer, "seed", &program).unwrap(),
+                &signer,
+                "seed",
+                &program,
+            ),
+            system_instruction::authorize_nonce_account(&nonce, &signer, &Pubkey::new_unique()),
+            system_instruction::withdraw_nonce_account(&nonce, &signer, &Pubkey::new_unique(), 1_000),
+        ];
+
+        for instruction in instructions {
+            let envelope = envelope(&signer, &[instruction]);
+            let reasons = reasons(SigningPolicy::default().evaluate(&envelope, &signer));
+
+            assert_eq!(reasons.len(), 1, "{:?}", reasons);
+            assert!(reasons[0].contains(&signer.to_string()), "{:?}", reasons);
+        }
+    }
+
+    #[test]
+    fn accepts_handing_over_someone_elses_account() {
+        let signer = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let envelope = envelope(&signer, &[system_instruction::assign(&other, &Pubkey::new_unique())]);
+
+        assert_eq!(SigningPolicy::default().evaluate(&envelope, &signer), PolicyVerdict::Accept);
+    }
+
+    #[test]
+    fn rejects_undecoded_system_instructions_unless_allowed() {
+        let signer = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(system_program::id(), &[0xff; 4], vec![AccountMeta::new(signer, true)]);
+        let envelope = envelope(&signer, &[instruction]);
+
+        let mut policy = SigningPolicy::default();
+        assert!(!policy.evaluate(&envelope, &signer).is_accepted());
+
+        policy.allow_undecoded_system_instructions = true;
+        assert_eq!(policy.evaluate(&envelope, &signer), PolicyVerdict::Accept);
+    }
+
+    #[test]
+    fn rejects_programs_that_are_not_allowed() {
+        let signer = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let envelope = envelope(&signer, &[Instruction::new_with_bytes(program, &[], vec![AccountMeta::new(signer, true)])]);
+
+        let reasons = reasons(SigningPolicy::default().evaluate(&envelope, &signer));
+        assert_eq!(reasons, vec![format!("instruction 0 calls program {}, which is not allowed", program)]);
+    }
+
+    #[test]
+    fn rejects_non_signers_and_missing_nonce() {
+        let payer = Pubkey::new_unique();
+        let envelope = envelope(&payer, &[system_instruction::transfer(&payer, &Pubkey::new_unique(), 1)]);
+        let policy = SigningPolicy {
+            require_nonce_advance: true,
+            ..SigningPolicy::default()
+        };
+
+        assert_eq!(reasons(policy.evaluate(&envelope, &payer)).len(), 1);
+        assert_eq!(reasons(policy.evaluate(&envelope, &Pubkey::new_unique())).len(), 2);
+    }
+
+    #[test]
+    fn allows_token_transfers_to_an_allowed_wallet() {
+        let signer = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let source = get_associated_token_address_with_program_id(&signer, &mint, &spl_token::id());
+        let transfer_to = |destination: &Pubkey| {
+            spl_token::instruction::transfer_checked(&spl_token::id(), &source, &mint, destination, &signer, &[], 1, 0)
+                .unwrap()
+        };
+        let policy = SigningPolicy {
+            allowed_destinations: vec![wallet],
+            ..SigningPolicy::default()
+        };
+
+        let allowed = get_associated_token_address_with_program_id(&wallet, &mint, &spl_token::id());
+        assert_eq!(policy.evaluate(&envelope(&signer, &[transfer_to(&allowed)]), &signer), PolicyVerdict::Accept);
+
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            reasons(policy.evaluate(&envelope(&signer, &[transfer_to(&other)]), &signer)),
+            vec![format!("instruction 0: destination {} is not allowed", other)]
+        );
+    }
+
+    #[test]
+    fn rejects_handing_token_authority_over() {
+        use spl_token_2022::instruction::{approve, approve_checked, set_authority, AuthorityType};
+
+        let signer = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let attacker = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let account = get_associated_token_address_with_program_id(&signer, &mint, &spl_token_2022::id());
+        let program = spl_token_2022::id();
+        let policy = SigningPolicy {
+            allowed_destinations: vec![wallet],
+            ..SigningPolicy::default()
+        };
+
+        let handovers = |to: &Pubkey| {
+            [
+                set_authority(&program, &account, Some(to), AuthorityType::AccountOwner, &signer, &[]).unwrap(),
+                set_authority(&program, &account, Some(to), AuthorityType::CloseAccount, &signer, &[]).unwrap(),
+                approve(&program, &account, to, &signer, &[], 1).unwrap(),
+                approve_checked(&program, &account, &mint, to, &signer, &[], 1, 0).unwrap(),
+            ]
+        };
+
+        for instruction in handovers(&attacker) {
+            let reasons = reasons(policy.evaluate(&envelope(&signer, &[instruction]), &signer));
+
+            assert_eq!(reasons.len(), 1, "{:?}", reasons);
+            assert!(reasons[0].contains(&attacker.to_string()), "{:?}", reasons);
+        }
+
+        for instruction in handovers(&wallet) {
+            assert_eq!(policy.evaluate(&envelope(&signer, &[instruction]), &signer), PolicyVerdict::Accept);
+        }
+
+        let removal = set_authority(&program, &account, None, AuthorityType::CloseAccount, &signer, &[]).unwrap();
+        assert_eq!(policy.evaluate(&envelope(&signer, &[removal]), &signer), PolicyVerdict::Accept);
+    }
+
+    #[test]
+    fn checks_the_destination_of_mints_and_fee_transfers() {
+        use spl_token_2022::extension::transfer_fee::instruction::transfer_checked_with_fee;
+        use spl_token_2022::instruction::{mint_to, mint_to_checked};
+
+        let signer = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let program = spl_token_2022::id();
+        let source = get_associated_token_address_with_program_id(&signer, &mint, &program);
+        let policy = SigningPolicy {
+            allowed_destinations: vec![wallet],
+            ..SigningPolicy::default()
+        };
+
+        let to = |destination: &Pubkey| {
+            [
+                mint_to(&program, &mint, destination, &signer, &[], 1).unwrap(),
+                mint_to_checked(&program, &mint, destination, &signer, &[], 1, 0).unwrap(),
+                transfer_checked_with_fee(&program, &source, &mint, destination, &signer, &[], 1, 0, 0).unwrap(),
+            ]
+        };
+
+        let other = Pubkey::new_unique();
+        for instruction in to(&other) {
+            assert_eq!(
+                reasons(policy.evaluate(&envelope(&signer, &[instruction]), &signer)),
+                vec![format!("instruction 0: destination {} is not allowed", other)]
+            );
+        }
+
+        let allowed = get_associated_token_address_with_program_id(&wallet, &mint, &program);
+        for instruction in to(&allowed) {
+            assert_eq!(policy.evaluate(&envelope(&signer, &[instruction]), &signer), PolicyVerdict::Accept);
+        }
+    }
+
+    #[test]
+    fn rejects_undecoded_token_instructions_unless_allowed() {
+        let signer = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(spl_token::id(), &[0xff; 4], vec![AccountMeta::new(signer, true)]);
+        let envelope = envelope(&signer, &[instruction]);
+
+        let mut policy = SigningPolicy::default();
+        assert_eq!(
+            reasons(policy.evaluate(&envelope, &signer)),
+            vec!["instruction 0: the token instruction can't be decoded".to_string()]
+        );
+
+        policy.allow_undecoded_token_instructions = true;
+        assert_eq!(policy.evaluate(&envelope, &signer), PolicyVerdict::Accept);
+    }
+}
@@ -0,0 +1,137 @@
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use solana_sdk::{
+    account::Account,
+    instruction::Instruction,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Keypair,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+
+use crate::error::Result;
+use crate::queries::decode_token_account;
+
+/// Balances of one account before and after the simulated transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BalanceChange {
+    pub account: Pubkey,
+    pub lamports_before: u64,
+    pub lamports_after: u64,
+    /// Token balances in base units, `None` when the account isn't a token account.
+    pub token_amount_before: Option<u64>,
+    pub token_amount_after: Option<u64>,
+}
+
+impl BalanceChange {
+    pub fn lamports_delta(&self) -> i128 {
+        self.lamports_after as i128 - self.lamports_before as i128
+    }
+
+    pub fn token_amount_delta(&self) -> Option<i128> {
+        match (self.token_amount_before, self.token_amount_after) {
+            (None, None) => None,
+            (before, after) => Some(after.unwrap_or(0) as i128 - before.unwrap_or(0) as i128),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulationReport {
+    /// Set when the transaction would fail, the rest of the report is still filled in.
+    pub error: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: u64,
+    /// Base fee in lamports for the signatures, without any priority fee.
+    pub fee: u64,
+    /// Only the accounts whose lamports or token balance would change.
+    pub balance_changes: Vec<BalanceChange>,
+}
+
+/// Signs `instructions` as a legacy transaction exactly as given and runs it
+/// through `simulateTransaction` without submitting it. No compute budget
+/// instructions are added, pass the ones `TxSender::budget` returns to simulate
+/// what the send path would submit.
+pub async fn simulate_instructions(
+    connection: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&Keypair],
+) -> Result<SimulationReport> {
+    let recent_blockhash = connection.get_latest_blockhash().await?;
+
+    let mut transaction = Transaction::new_with_payer(instructions, Some(payer));
+    transaction.try_sign(signers, recent_blockhash)?;
+
+    simulate_transaction(connection, &transaction.into(), true).await
+}
+
+/// Runs an already built legacy or v0 transaction through `simulateTransaction`.
+/// `sig_verify` can be turned off for transactions still missing signatures.
+pub async fn simulate_transaction(
+    connection: &RpcClient,
+    transaction: &VersionedTransaction,
+    sig_verify: bool,
+) -> Result<SimulationReport> {
+    let fee = match &transaction.message {
+        VersionedMessage::Legacy(message) => connection.get_fee_for_message(message).await?,
+        VersionedMessage::V0(message) => connection.get_fee_for_message(message).await?,
+    };
+
+    let accounts = transaction.message.static_account_keys().to_vec();
+    let accounts_before = connection.get_multiple_accounts(&accounts).await?;
+
+    let simulation = connection
+        .simulate_transaction_with_config(
+            transaction,
+            RpcSimulateTransactionConfig {
+                sig_verify,
+                commitment: Some(connection.commitment()),
+                accounts: Some(RpcSimulateTransactionAccountsConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    addresses: accounts.iter().map(Pubkey::to_string).collect(),
+                }),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .await?
+        .value;
+
+    // A failed simulation returns no post-state, so there is nothing to diff.
+    let accounts_after: Vec<Option<Account>> = match simulation.accounts {
+        Some(accounts_after) => accounts_after
+            .iter()
+            .map(|account| account.as_ref().and_then(|account| account.decode()))
+            .collect(),
+        None => accounts_before.clone(),
+    };
+
+    let balance_changes = accounts
+        .iter()
+        .zip(accounts_before.iter().zip(&accounts_after))
+        .map(|(address, (before, after))| BalanceChange {
+            account: *address,
+            lamports_before: before.as_ref().map_or(0, |account| account.lamports),
+            lamports_after: after.as_ref().map_or(0, |account| account.lamports),
+            token_amount_before: token_amount(address, before.as_ref()),
+            token_amount_after: token_amount(address, after.as_ref()),
+        })
+        .filter(|change| {
+            change.lamports_before != change.lamports_after
+                || change.token_amount_before != change.token_amount_after
+        })
+        .collect();
+
+    Ok(SimulationReport {
+        error: simulation.err,
+        logs: simulation.logs.unwrap_or_default(),
+        units_consumed: simulation.units_consumed.unwrap_or_default(),
+        fee,
+        balance_changes,
+    })
+}
+
+fn token_amount(address: &Pubkey, account: Option<&Account>) -> Option<u64> {
+    decode_token_account(address, account?).ok().map(|token_account| token_account.amount)
+}
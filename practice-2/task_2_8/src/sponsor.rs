@@ -0,0 +1,429 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+#[allow(deprecated)]
+use solana_sdk::compute_budget;
+use solana_sdk::{
+    instruction::CompiledInstruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+use spl_token_2022::instruction::TokenInstruction;
+
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::inspector::mint_position;
+use crate::results::TransactionReceipt;
+use crate::simulation::simulate_transaction;
+use crate::token_program::TokenProgram;
+use crate::tx_envelope::TxEnvelope;
+
+pub const SPONSOR_POLICY_ENV: &str = "SPONSOR_POLICY";
+
+/// Signature fees and a priority fee, no rent.
+const DEFAULT_MAX_FEE_PAYER_LAMPORTS: u64 = 100_000;
+
+/// What a fee payer agrees to co-sign for other people.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SponsorPolicy {
+    /// Every instruction has to call one of these.
+    pub allowed_programs: Vec<Pubkey>,
+    /// Token instructions may only touch these mints, empty allows any mint.
+    pub allowed_mints: Vec<Pubkey>,
+    /// Upper bound for the fee plus any rent the fee payer funds, checked by simulation.
+    /// Funding rent is opt-in: a sender can close the funded token account in a later
+    /// sponsored transaction and keep the rent, and the rate limit doesn't stop it
+    /// because any client can sign with a fresh sender keypair each time.
+    pub max_fee_payer_lamports: u64,
+    /// Transactions one sender may get sponsored per `rate_limit_window`.
+    pub max_transactions_per_sender: usize,
+    pub rate_limit_window: Duration,
+}
+
+/// Layout of the YAML policy file, every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PolicyFile {
+    allowed_programs: Option<Vec<String>>,
+    allowed_mints: Vec<String>,
+    max_fee_payer_lamports: Option<u64>,
+    max_transactions_per_sender: Option<usize>,
+    rate_limit_window_secs: Option<u64>,
+}
+
+impl Default for SponsorPolicy {
+    /// Token transfers on either token program, with memos and priority fees, up to
+    /// 0.0001 SOL in fees each and 10 per sender a minute. Token accounts aren't funded.
+    fn default() -> Self {
+        Self {
+            allowed_programs: vec![
+                TokenProgram::Token.id(),
+                TokenProgram::Token2022.id(),
+                spl_associated_token_account::id(),
+                spl_memo::id(),
+                compute_budget::id(),
+            ],
+            allowed_mints: Vec::new(),
+            max_fee_payer_lamports: DEFAULT_MAX_FEE_PAYER_LAMPORTS,
+            max_transactions_per_sender: 10,
+            rate_limit_window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl SponsorPolicy {
+    /// Reads a YAML policy, fields left out keep their defaults.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let config_error = |message: String| SolanaUtilsError::Config(format!("{}: {}", path.display(), message));
+
+        let content = fs::read_to_string(path).map_err(|error| config_error(error.to_string()))?;
+        let file: PolicyFile = serde_yaml::from_str(&content).map_err(|error| config_error(error.to_string()))?;
+
+        let mut policy = Self::default();
+
+        if let Some(programs) = file.allowed_programs {
+            policy.allowed_programs = programs.iter().map(|program| parse_pubkey(program)).collect::<Result<_>>()?;
+        }
+        policy.allowed_mints = file.allowed_mints.iter().map(|mint| parse_pubkey(mint)).collect::<Result<_>>()?;
+        if let Some(max_fee_payer_lamports) = file.max_fee_payer_lamports {
+            policy.max_fee_payer_lamports = max_fee_payer_lamports;
+        }
+        if let Some(max_transactions) = file.max_transactions_per_sender {
+            policy.max_transactions_per_sender = max_transactions;
+        }
+        if let Some(window) = file.rate_limit_window_secs {
+            policy.rate_limit_window = Duration::from_secs(window);
+        }
+
+        Ok(policy)
+    }
+
+    /// The policy file named by `SPONSOR_POLICY`, or the defaults when it is not set.
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        match env::var(SPONSOR_POLICY_ENV) {
+            Ok(path) => Self::from_file(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+/// Co-signs and submits other people's transactions as their fee payer,
+/// as long as they stay within the policy.
+pub struct Sponsor {
+    fee_payer: Keypair,
+    policy: SponsorPolicy,
+    /// When each sender last got a transaction sponsored, oldest first.
+    history: Mutex<HashMap<Pubkey, VecDeque<Instant>>>,
+}
+
+impl Sponsor {
+    pub fn new(fee_payer: Keypair, policy: SponsorPolicy) -> Self {
+        Self {
+            fee_payer,
+            policy,
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The key clients have to set as fee payer of the transactions they send in.
+    pub fn fee_payer(&self) -> Pubkey {
+        self.fee_payer.pubkey()
+    }
+
+    pub fn policy(&self) -> &SponsorPolicy {
+        &self.policy
+    }
+
+    /// Checks `envelope` against the policy without signing it or counting it
+    /// towards the rate limit. Returns the lamports the fee payer would spend.
+    pub async fn check(&self, connection: &RpcClient, envelope: &TxEnvelope) -> Result<u64> {
+        self.check_transaction(envelope)?;
+        self.check_spending(connection, envelope).await
+    }
+
+    /// Signs as fee payer and submits once `envelope` passes the policy.
+    /// The sender's other signatures have to be in place already.
+    pub async fn sponsor(&self, connection: &RpcClient, mut envelope: TxEnvelope) -> Result<TransactionReceipt> {
+        let sender = self.check_transaction(&envelope)?;
+        self.check_spending(connection, &envelope).await?;
+
+        // Counted before sending, so parallel requests can't slip past the limit.
+        self.record(&sender)?;
+
+        envelope.sign(&self.fee_payer)?;
+        envelope.submit(connection).await
+    }
+
+    /// The checks that don't need the cluster. Returns the sender, the first signer after the fee payer.
+    fn check_transaction(&self, envelope: &TxEnvelope) -> Result<Pubkey> {
+        let fee_payer = self.fee_payer.pubkey();
+        let required_signers = envelope.required_signers();
+
+        if required_signers[0] != fee_payer {
+            return Err(policy_error(format!("the fee payer has to be {}", fee_payer)));
+        }
+
+        let missing = envelope.missing_signers();
+        if missing != [fee_payer] {
+            let missing: Vec<String> = missing.iter().filter(|signer| **signer != fee_payer).map(Pubkey::to_string).collect();
+            return Err(policy_error(format!("missing signatures from {}", missing.join(", "))));
+        }
+
+        let sender = *required_signers
+            .get(1)
+            .ok_or_else(|| policy_error("the transaction has no signer besides the fee payer".to_string()))?;
+
+        // Checked here as well, an envelope doesn't have to come from `from_wire`.
+        envelope.check_signatures()?;
+
+        let account_keys = envelope.transaction.message.static_account_keys();
+        let mut funded_accounts = Vec::new();
+        for instruction in envelope.transaction.message.instructions() {
+            let program_id = account_keys[instruction.program_id_index as usize];
+
+            if !self.policy.allowed_programs.contains(&program_id) {
+                return Err(policy_error(format!("program {} is not sponsored", program_id)));
+            }
+
+            // The fee payer pays, it never authorizes anything. Funding a new token
+            // account is the one exception, its rent counts towards the spending limit.
+            let uses_fee_payer = instruction.accounts.contains(&0);
+            let funds_token_account = program_id == spl_associated_token_account::id()
+                && matches!(instruction.data.as_slice(), [] | [0] | [1])
+                && instruction.accounts.iter().skip(1).all(|index| *index != 0);
+            if uses_fee_payer && !funds_token_account {
+                return Err(policy_error(format!("the fee payer can't be an account of a {} instruction", program_id)));
+            }
+            if uses_fee_payer {
+                funded_accounts.extend(instruction.accounts.get(1).copied());
+            }
+
+            if !self.policy.allowed_mints.is_empty() {
+                self.check_mint(envelope, &program_id, instruction)?;
+            }
+        }
+
+        // Otherwise the rent the fee payer just put in could be taken out again in the same transaction.
+        for instruction in envelope.transaction.message.instructions() {
+            let program_id = account_keys[instruction.program_id_index as usize];
+            if TokenProgram::from_program_id(&program_id).is_none() {
+                continue;
+            }
+
+            let withdraws = matches!(
+                TokenInstruction::unpack(&instruction.data),
+                Ok(TokenInstruction::CloseAccount | TokenInstruction::WithdrawExcessLamports)
+            );
+            if withdraws && instruction.accounts.first().is_some_and(|index| funded_accounts.contains(index)) {
+                return Err(policy_error("a token account the fee payer funds can't be closed in the same transaction".to_string()));
+            }
+        }
+
+        // Caught by the simulation as well, but a policy that doesn't fund accounts can say so up front.
+        let token_account_rent = Rent::default().minimum_balance(spl_token_2022::state::Account::LEN);
+        if !funded_accounts.is_empty() && self.policy.max_fee_payer_lamports < token_account_rent {
+            return Err(policy_error(format!(
+                "the fee payer doesn't fund token accounts, the limit of {} lamports is below the rent",
+                self.policy.max_fee_payer_lamports
+            )));
+        }
+
+        Ok(sender)
+    }
+
+    fn check_mint(&self, envelope: &TxEnvelope, program_id: &Pubkey, instruction: &CompiledInstruction) -> Result<()> {
+        let position = mint_position(program_id, &instruction.data).map_err(|reason| policy_error(reason.to_string()))?;
+        let Some(position) = position else {
+            return Ok(());
+        };
+
+        let mint = instruction
+            .accounts
+            .get(position)
+            .and_then(|index| envelope.transaction.message.static_account_keys().get(*index as usize))
+            .ok_or_else(|| policy_error("the mint has to be a static account".to_string()))?;
+
+        if !self.policy.allowed_mints.contains(mint) {
+            return Err(policy_error(format!("mint {} is not sponsored", mint)));
+        }
+
+        Ok(())
+    }
+
+    /// Simulates without the fee payer's signature and measures what it would pay.
+    async fn check_spending(&self, connection: &RpcClient, envelope: &TxEnvelope) -> Result<u64> {
+        let report = simulate_transaction(connection, &envelope.transaction, false).await?;
+
+        if let Some(error) = report.error {
+            return Err(SolanaUtilsError::Simulation { error, logs: report.logs });
+        }
+
+        let fee_payer = self.fee_payer.pubkey();
+        let spent = report
+            .balance_changes
+            .iter()
+            .find(|change| change.account == fee_payer)
+            .map_or(0, |change| (-change.lamports_delta()).max(0) as u64)
+            .max(report.fee);
+
+        if spent > self.policy.max_fee_payer_lamports {
+            return Err(policy_error(format!(
+                "the fee payer would spend {} lamports, the limit is {}",
+                spent, self.policy.max_fee_payer_lamports
+            )));
+        }
+
+        Ok(spent)
+    }
+
+    fn record(&self, sender: &Pubkey) -> Result<()> {
+        let mut history = self.history.lock().unwrap();
+        let sent = history.entry(*sender).or_default();
+        let now = Instant::now();
+
+        while sent.front().is_some_and(|at| now.duration_since(*at) >= self.policy.rate_limit_window) {
+            sent.pop_front();
+        }
+
+        if sent.len() >= self.policy.max_transactions_per_sender {
+            let retry_after = match sent.front() {
+                Some(oldest) => self.policy.rate_limit_window - now.duration_since(*oldest),
+                None => self.policy.rate_limit_window,
+            };
+            return Err(SolanaUtilsError::RateLimited {
+                sender: *sender,
+                retry_after,
+            });
+        }
+
+        sent.push_back(now);
+
+        Ok(())
+    }
+}
+
+fn policy_error(message: String) -> SolanaUtilsError {
+    SolanaUtilsError::Policy(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{hash::Hash, instruction::Instruction, message::Message, transaction::Transaction};
+    use spl_associated_token_account::{
+        get_associated_token_address_with_program_id, instruction::create_associated_token_account,
+    };
+
+    use super::*;
+    use crate::explorer_link::Cluster;
+    use crate::tx_envelope::BlockhashSource;
+
+    /// Adds a memo signed by `sender`, so the sender is the second signer.
+    fn envelope(fee_payer: &Pubkey, sender: &Keypair, instructions: &[Instruction]) -> TxEnvelope {
+        let mut instructions = instructions.to_vec();
+        instructions.push(spl_memo::build_memo(b"sponsored", &[&sender.pubkey()]));
+
+        let mut transaction = Transaction::new_unsigned(Message::new(&instructions, Some(fee_payer)));
+        transaction.partial_sign(&[sender], Hash::new_unique());
+
+        TxEnvelope::new(transaction, Cluster::Devnet, BlockhashSource::Recent { last_valid_block_height: None })
+    }
+
+    fn create_token_account(fee_payer: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Instruction {
+        create_associated_token_account(fee_payer, owner, mint, &spl_token::id())
+    }
+
+    fn funding_policy() -> SponsorPolicy {
+        SponsorPolicy {
+            max_fee_payer_lamports: 3_000_000,
+            ..SponsorPolicy::default()
+        }
+    }
+
+    #[test]
+    fn sponsors_funding_a_token_account() {
+        let sponsor = Sponsor::new(Keypair::new(), funding_policy());
+        let sender = Keypair::new();
+        let mint = Pubkey::new_unique();
+
+        let envelope = envelope(
+            &sponsor.fee_payer(),
+            &sender,
+            &[create_token_account(&sponsor.fee_payer(), &sender.pubkey(), &mint)],
+        );
+
+        assert_eq!(sponsor.check_transaction(&envelope).unwrap(), sender.pubkey());
+    }
+
+    #[test]
+    fn rejects_funding_a_token_account_by_default() {
+        let sponsor = Sponsor::new(Keypair::new(), SponsorPolicy::default());
+        let sender = Keypair::new();
+        let mint = Pubkey::new_unique();
+
+        let envelope = envelope(
+            &sponsor.fee_payer(),
+            &sender,
+            &[create_token_account(&sponsor.fee_payer(), &sender.pubkey(), &mint)],
+        );
+
+        assert!(matches!(
+            sponsor.check_transaction(&envelope),
+            Err(SolanaUtilsError::Policy(reason)) if reason.contains("doesn't fund token accounts")
+        ));
+    }
+
+    #[test]
+    fn rejects_closing_the_funded_token_account() {
+        let sponsor = Sponsor::new(Keypair::new(), funding_policy());
+        let sender = Keypair::new();
+        let mint = Pubkey::new_unique();
+        let token_account = get_associated_token_address_with_program_id(&sender.pubkey(), &mint, &spl_token::id());
+
+        let envelope = envelope(
+            &sponsor.fee_payer(),
+            &sender,
+            &[
+                create_token_account(&sponsor.fee_payer(), &sender.pubkey(), &mint),
+                spl_token::instruction::close_account(
+                    &spl_token::id(),
+                    &token_account,
+                    &sender.pubkey(),
+                    &sender.pubkey(),
+                    &[],
+                )
+                .unwrap(),
+            ],
+        );
+
+        assert!(matches!(
+            sponsor.check_transaction(&envelope),
+            Err(SolanaUtilsError::Policy(reason)) if reason.contains("can't be closed")
+        ));
+    }
+
+    #[test]
+    fn rejects_an_invalid_sender_signature() {
+        let sponsor = Sponsor::new(Keypair::new(), SponsorPolicy::default());
+        let sender = Keypair::new();
+        let mint = Pubkey::new_unique();
+
+        let mut envelope = envelope(
+            &sponsor.fee_payer(),
+            &sender,
+            &[create_token_account(&sponsor.fee_payer(), &sender.pubkey(), &mint)],
+        );
+        envelope.transaction.signatures[1] = Keypair::new().sign_message(b"something else");
+
+        assert!(matches!(sponsor.check_transaction(&envelope), Err(SolanaUtilsError::Envelope(_))));
+    }
+}
@@ -0,0 +1,97 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+
+pub const TOKEN_PROGRAM_ENV: &str = "TOKEN_PROGRAM";
+
+/// The two token programs a mint can belong to. Instruction builders from
+/// `spl_token_2022` accept either id, the instruction layouts are shared.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TokenProgram {
+    /// The classic SPL Token program.
+    Token,
+    #[default]
+    Token2022,
+}
+
+impl TokenProgram {
+    pub fn id(&self) -> Pubkey {
+        match self {
+            TokenProgram::Token => spl_token::id(),
+            TokenProgram::Token2022 => spl_token_2022::id(),
+        }
+    }
+
+    pub fn from_program_id(program_id: &Pubkey) -> Option<Self> {
+        if *program_id == spl_token::id() {
+            Some(TokenProgram::Token)
+        } else if *program_id == spl_token_2022::id() {
+            Some(TokenProgram::Token2022)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the program from the owner of the mint account.
+    pub async fn for_mint(connection: &RpcClient, mint: &Pubkey) -> Result<Self> {
+        let account = connection.get_account(mint).await?;
+
+        Self::from_program_id(&account.owner).ok_or(SolanaUtilsError::NotTokenAccount {
+            account: *mint,
+            owner: account.owner,
+        })
+    }
+
+    /// Reads `TOKEN_PROGRAM`, defaulting to Token-2022 when it is not set.
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        match env::var(TOKEN_PROGRAM_ENV) {
+            Ok(value) => value.parse(),
+            Err(_) => Ok(TokenProgram::default()),
+        }
+    }
+
+    pub fn associated_token_address(&self, owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, mint, &self.id())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenProgram::Token => "token",
+            TokenProgram::Token2022 => "token-2022",
+        }
+    }
+}
+
+impl fmt::Display for TokenProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Accepts `token` (or `spl-token`, `legacy`), `token-2022` (or `token2022`)
+/// and either program id.
+impl FromStr for TokenProgram {
+    type Err = SolanaUtilsError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "token" | "spl-token" | "legacy" => Ok(TokenProgram::Token),
+            "token-2022" | "token2022" => Ok(TokenProgram::Token2022),
+            _ => {
+                let program_id = parse_pubkey(value.trim())?;
+
+                Self::from_program_id(&program_id).ok_or_else(|| {
+                    SolanaUtilsError::InvalidInput(format!("{} is not a token program", program_id))
+                })
+            }
+        }
+    }
+}
@@ -0,0 +1,590 @@
+use std::fs;
+use std::path::Path;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::{hash, Hash},
+    message::{Message, VersionedMessage},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::VersionedTransaction,
+};
+
+use crate::cluster::parse_cluster;
+use crate::error::{parse_pubkey, Result, SolanaUtilsError};
+use crate::explorer_link::{Cluster, LinkType};
+use crate::explorer_url;
+use crate::inspector::{inspect, MintDecimals};
+use crate::results::TransactionReceipt;
+use crate::tx_sender::wait_for_confirmation;
+
+/// Bumped whenever the JSON layout changes incompatibly.
+pub const TX_ENVELOPE_VERSION: u32 = 1;
+
+/// Where the blockhash of the transaction comes from, which decides how long it stays valid.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockhashSource {
+    /// A recent blockhash, valid until the cluster passes `last_valid_block_height`.
+    Recent { last_valid_block_height: Option<u64> },
+    /// A durable nonce, valid until the nonce is advanced.
+    Nonce { account: Pubkey, authority: Pubkey },
+}
+
+/// A legacy or v0 transaction on its way between signers, with the context they need
+/// to check it. The transaction itself is kept in the standard wire format, the same bytes
+/// `@solana/web3.js` produces with `serialize()` and the RPC accepts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxEnvelope {
+    pub transaction: VersionedTransaction,
+    pub cluster: Cluster,
+    pub blockhash_source: BlockhashSource,
+}
+
+/// Signatures in the shape of the Solana CLI `--sign-only --output json` output.
+/// Each entry of `signers` can be passed back to the CLI as `--signer PUBKEY=SIGNATURE`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignOnlyOutput {
+    pub blockhash: String,
+    pub signers: Vec<String>,
+    #[serde(default)]
+    pub absent: Vec<String>,
+    #[serde(default)]
+    pub bad_sig: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct TxEnvelopeFile {
+    version: u32,
+    cluster: String,
+    /// `legacy` or `v0`.
+    message_version: String,
+    blockhash: String,
+    blockhash_source: BlockhashSourceFile,
+    signers: Vec<SignerFile>,
+    /// Base64 of the wire format transaction, signatures included.
+    transaction: String,
+    /// Base58 SHA-256 of the wire format bytes.
+    checksum: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BlockhashSourceFile {
+    Recent {
+        #[serde(default)]
+        last_valid_block_height: Option<u64>,
+    },
+    Nonce {
+        account: String,
+        authority: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SignerFile {
+    pubkey: String,
+    #[serde(default)]
+    signature: Option<String>,
+}
+
+impl TxEnvelope {
+    pub fn new(transaction: impl Into<VersionedTransaction>, cluster: Cluster, blockhash_source: BlockhashSource) -> Self {
+        Self {
+            transaction: transaction.into(),
+            cluster,
+            blockhash_source,
+        }
+    }
+
+    /// Wraps a base64 or base58 wire format transaction, e.g. from `@solana/web3.js`.
+    /// A durable nonce is detected from the leading advance nonce instruction.
+    /// Signatures already present have to verify.
+    pub fn from_wire(encoded: &str, cluster: Cluster) -> Result<Self> {
+        let encoded = encoded.trim();
+        // A base58 string can also be valid base64, so fall back to base58 when the base64 bytes aren't a transaction.
+        let decoded = [STANDARD.decode(encoded).ok(), bs58::decode(encoded).into_vec().ok()];
+        let mut candidates = decoded.iter().flatten().map(|bytes| deserialize_wire(bytes));
+        let transaction = candidates
+            .next()
+            .ok_or_else(|| envelope_error("transaction is neither base64 nor base58"))?
+            .or_else(|error| candidates.find_map(std::result::Result::ok).ok_or(error))
+            .map_err(envelope_error)?;
+
+        let blockhash_source = nonce_source(&transaction)
+            .unwrap_or(BlockhashSource::Recent { last_valid_block_height: None });
+
+        let envelope = Self::new(transaction, cluster, blockhash_source);
+        envelope.check_signatures()?;
+
+        Ok(envelope)
+    }
+
+    /// Base64 of the wire format, what `sendTransaction` and web3.js `deserialize` expect.
+    pub fn to_wire(&self) -> Result<String> {
+        Ok(STANDARD.encode(self.wire_bytes()?))
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        matches!(self.transaction.message, VersionedMessage::Legacy(_))
+    }
+
+    pub fn blockhash(&self) -> &Hash {
+        self.transaction.message.recent_blockhash()
+    }
+
+    /// The fee payer first, then every other key that has to sign.
+    pub fn required_signers(&self) -> &[Pubkey] {
+        let message = &self.transaction.message;
+
+        &message.static_account_keys()[..message.header().num_required_signatures as usize]
+    }
+
+    pub fn signed_by(&self) -> Vec<Pubkey> {
+        self.signers_where(|signature| *signature != Signature::default())
+    }
+
+    pub fn missing_signers(&self) -> Vec<Pubkey> {
+        self.signers_where(|signature| *signature == Signature::default())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing_signers().is_empty()
+    }
+
+    /// Adds the signature of `signer`, who has to be one of `required_signers`.
+    pub fn sign(&mut self, signer: &Keypair) -> Result<()> {
+        let index = self.signer_index(&signer.pubkey())?;
+        let signature = signer.try_sign_message(&self.transaction.message.serialize())?;
+
+        self.transaction.signatures[index] = signature;
+
+        Ok(())
+    }
+
+    /// Adds a signature made elsewhere, e.g. by a hardware wallet or the Solana CLI.
+    pub fn add_signature(&mut self, signer: &Pubkey, signature: Signature) -> Result<()> {
+        let index = self.signer_index(signer)?;
+
+        if !signature.verify(signer.as_ref(), &self.transaction.message.serialize()) {
+            return Err(envelope_error(format!("invalid signature from {}", signer)));
+        }
+
+        self.transaction.signatures[index] = signature;
+
+        Ok(())
+    }
+
+    /// Copies the signatures of `other`, a copy of the same transaction signed by someone else.
+    pub fn merge(&mut self, other: &TxEnvelope) -> Result<()> {
+        if self.transaction.message != other.transaction.message {
+            return Err(envelope_error("can't merge signatures of a different transaction"));
+        }
+
+        other.check_signatures()?;
+
+        for (index, signature) in other.transaction.signatures.iter().enumerate() {
+            if *signature != Signature::default() {
+                self.transaction.signatures[index] = *signature;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The signatures collected so far, as the Solana CLI prints them with `--sign-only`.
+    pub fn sign_only_output(&self) -> SignOnlyOutput {
+        let results = self.transaction.verify_with_results();
+        let mut output = SignOnlyOutput {
+            blockhash: self.blockhash().to_string(),
+            signers: Vec::new(),
+            absent: Vec::new(),
+            bad_sig: Vec::new(),
+        };
+
+        for (index, (signer, signature)) in self.required_signers().iter().zip(&self.transaction.signatures).enumerate() {
+            if *signature == Signature::default() {
+                output.absent.push(signer.to_string());
+            } else if results[index] {
+                output.signers.push(format!("{}={}", signer, signature));
+            } else {
+                output.bad_sig.push(signer.to_string());
+            }
+        }
+
+        output
+    }
+
+    /// Takes the signatures from a Solana CLI `--sign-only` run over the same message.
+    pub fn merge_sign_only(&mut self, output: &SignOnlyOutput) -> Result<()> {
+        if output.blockhash != self.blockhash().to_string() {
+            return Err(envelope_error(format!(
+                "signatures are for blockhash {}, not {}",
+                output.blockhash,
+                self.blockhash()
+            )));
+        }
+
+        for entry in &output.signers {
+            let (signer, signature) = entry
+                .split_once('=')
+                .ok_or_else(|| envelope_error(format!("expected PUBKEY=SIGNATURE, got `{}`", entry)))?;
+            let signature = signature
+                .parse::<Signature>()
+                .map_err(|error| envelope_error(format!("signature `{}`: {}", signature, error)))?;
+
+            self.add_signature(&parse_pubkey(signer)?, signature)?;
+        }
+
+        Ok(())
+    }
+
+    /// Human readable decoding for signers to review before they sign.
+    /// Token amounts stay in base units unless their mint is in `decimals`,
+    /// see `inspector::fetch_decimals`.
+    pub fn describe(&self, decimals: &MintDecimals) -> Vec<String> {
+        self.describe_with_roles(decimals, |_| None)
+    }
+
+    /// `describe` with a note next to the signers `role` knows about.
+    pub(crate) fn describe_with_roles(
+        &self,
+        decimals: &MintDecimals,
+        role: impl Fn(&Pubkey) -> Option<&'static str>,
+    ) -> Vec<String> {
+        let message = &self.transaction.message;
+        let account_keys = message.static_account_keys();
+
+        let mut lines = vec![
+            format!(
+                "Transaction: {} on {}",
+                if self.is_legacy() { "legacy" } else { "v0" },
+                self.cluster.as_str()
+            ),
+            format!("Fee payer: {}", account_keys[0]),
+            match &self.blockhash_source {
+                BlockhashSource::Recent { .. } => {
+                    format!("Blockhash: {} (expires about a minute after it was fetched)", self.blockhash())
+                }
+                BlockhashSource::Nonce { account, .. } => {
+                    format!("Blockhash: {} (durable nonce {})", self.blockhash(), account)
+                }
+            },
+        ];
+
+        let summaries = inspect(self, decimals);
+        for (index, (instruction, summary)) in message.instructions().iter().zip(summaries).enumerate() {
+            lines.push(format!("Instruction {}: {}", index, summary));
+
+            for account_index in &instruction.accounts {
+                let account_index = *account_index as usize;
+                let Some(account) = account_keys.get(account_index) else {
+                    lines.push(format!("   lookup table account #{}", account_index - account_keys.len()));
+                    continue;
+                };
+
+                let mut flags = Vec::new();
+                if message.is_signer(account_index) {
+                    flags.push("signer");
+                }
+                if message.is_maybe_writable(account_index, None) {
+                    flags.push("writable");
+                }
+                lines.push(format!("   {} [{}]", account, flags.join(", ")));
+            }
+        }
+
+        let missing = self.missing_signers();
+        lines.push(format!(
+            "Signatures: {} of {}",
+            self.required_signers().len() - missing.len(),
+            self.required_signers().len()
+        ));
+        for signer in self.required_signers() {
+            let status = if missing.contains(signer) { "⏳" } else { "✅" };
+            match role(signer) {
+                Some(role) => lines.push(format!("   {} {} ({})", status, signer, role)),
+                None => lines.push(format!("   {} {}", status, signer)),
+            }
+        }
+
+        lines
+    }
+
+    /// Sends the transaction once every required signature is present.
+    pub async fn submit(&self, connection: &RpcClient) -> Result<TransactionReceipt> {
+        let missing = self.missing_signers();
+        if !missing.is_empty() {
+            let missing: Vec<String> = missing.iter().map(|signer| signer.to_string()).collect();
+            return Err(SolanaUtilsError::InvalidInput(format!(
+                "still missing signatures from {}",
+                missing.join(", ")
+            )));
+        }
+
+        self.check_signatures()?;
+
+        // Nonce transactions don't expire with block height, waiting for one
+        // blockhash lifetime is enough to tell it was dropped and can be resent.
+        let last_valid_block_height = match self.blockhash_source {
+            BlockhashSource::Recent { last_valid_block_height: Some(height) } => height,
+            _ => {
+                connection
+                    .get_latest_blockhash_with_commitment(connection.commitment())
+                    .await?
+                    .1
+            }
+        };
+
+        let error_message = self.error_message();
+
+        let signature = connection
+            .send_transaction(&self.transaction)
+            .await
+            .map_err(|error| SolanaUtilsError::from_transaction_error(error, &error_message))?;
+
+        let slot = wait_for_confirmation(connection, &signature, &error_message, last_valid_block_height)
+            .await?
+            .ok_or(SolanaUtilsError::Expired { attempts: 1 })?;
+
+        Ok(TransactionReceipt {
+            signature,
+            slot,
+            explorer_link: explorer_url(connection, LinkType::Transaction, signature),
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.to_file()?).map_err(|error| envelope_error(error.to_string()))
+    }
+
+    /// Parses an envelope, rejecting it when the checksum, the recorded fields
+    /// or any signature present don't match the transaction.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let file: TxEnvelopeFile = serde_json::from_str(json).map_err(|error| envelope_error(error.to_string()))?;
+
+        Self::from_file(file)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+
+        fs::write(path, self.to_json()?).map_err(|error| file_error(path, error.to_string()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let content = fs::read_to_string(path).map_err(|error| file_error(path, error.to_string()))?;
+
+        Self::from_json(&content)
+    }
+
+    pub(crate) fn to_file(&self) -> Result<TxEnvelopeFile> {
+        let bytes = self.wire_bytes()?;
+
+        Ok(TxEnvelopeFile {
+            version: TX_ENVELOPE_VERSION,
+            cluster: self.cluster.as_str(),
+            message_version: if self.is_legacy() { "legacy" } else { "v0" }.to_string(),
+            blockhash: self.blockhash().to_string(),
+            blockhash_source: match &self.blockhash_source {
+                BlockhashSource::Recent { last_valid_block_height } => BlockhashSourceFile::Recent {
+                    last_valid_block_height: *last_valid_block_height,
+                },
+                BlockhashSource::Nonce { account, authority } => BlockhashSourceFile::Nonce {
+                    account: account.to_string(),
+                    authority: authority.to_string(),
+                },
+            },
+            signers: self
+                .required_signers()
+                .iter()
+                .zip(&self.transaction.signatures)
+                .map(|(signer, signature)| SignerFile {
+                    pubkey: signer.to_string(),
+                    signature: (*signature != Signature::default()).then(|| signature.to_string()),
+                })
+                .collect(),
+            transaction: STANDARD.encode(&bytes),
+            checksum: hash(&bytes).to_string(),
+        })
+    }
+
+    pub(crate) fn from_file(file: TxEnvelopeFile) -> Result<Self> {
+        if file.version != TX_ENVELOPE_VERSION {
+            return Err(envelope_error(format!("unsupported version {}", file.version)));
+        }
+
+        let bytes = STANDARD
+            .decode(&file.transaction)
+            .map_err(|error| envelope_error(error.to_string()))?;
+        if hash(&bytes).to_string() != file.checksum {
+            return Err(envelope_error("checksum doesn't match the transaction"));
+        }
+
+        let transaction: VersionedTransaction =
+            bincode::deserialize(&bytes).map_err(|error| envelope_error(error.to_string()))?;
+        transaction.sanitize().map_err(|error| envelope_error(error.to_string()))?;
+
+        let envelope = Self {
+            transaction,
+            cluster: parse_cluster(&file.cluster)?,
+            blockhash_source: match file.blockhash_source {
+                BlockhashSourceFile::Recent { last_valid_block_height } => {
+                    BlockhashSource::Recent { last_valid_block_height }
+                }
+                BlockhashSourceFile::Nonce { account, authority } => BlockhashSource::Nonce {
+                    account: parse_pubkey(&account)?,
+                    authority: parse_pubkey(&authority)?,
+                },
+            },
+        };
+
+        // The readable fields are only a view of the transaction, a mismatch means the file was edited.
+        let recorded_signers: Vec<&str> = file.signers.iter().map(|signer| signer.pubkey.as_str()).collect();
+        let actual_signers: Vec<String> = envelope.required_signers().iter().map(Pubkey::to_string).collect();
+        if file.blockhash != envelope.blockhash().to_string()
+            || file.message_version != if envelope.is_legacy() { "legacy" } else { "v0" }
+            || recorded_signers != actual_signers
+        {
+            return Err(envelope_error("recorded fields don't match the transaction"));
+        }
+
+        envelope.check_blockhash_source()?;
+        envelope.check_signatures()?;
+
+        Ok(envelope)
+    }
+
+    /// A nonce source has to match the leading advance nonce instruction, and
+    /// a transaction with one can't claim a recent blockhash.
+    pub(crate) fn check_blockhash_source(&self) -> Result<()> {
+        match (&self.blockhash_source, nonce_source(&self.transaction)) {
+            (BlockhashSource::Recent { .. }, None) => Ok(()),
+            (claimed @ BlockhashSource::Nonce { .. }, Some(actual)) if *claimed == actual => Ok(()),
+            (_, Some(_)) => Err(envelope_error("the blockhash source doesn't match the advance nonce instruction")),
+            (_, None) => Err(envelope_error("the blockhash source names a nonce the transaction doesn't advance")),
+        }
+    }
+
+    /// Legacy view of the message for classifying errors, program ids always sit in the static keys.
+    pub(crate) fn error_message(&self) -> Message {
+        match &self.transaction.message {
+            VersionedMessage::Legacy(message) => message.clone(),
+            VersionedMessage::V0(message) => Message {
+                header: message.header,
+                account_keys: message.account_keys.clone(),
+                recent_blockhash: message.recent_blockhash,
+                instructions: message.instructions.clone(),
+            },
+        }
+    }
+
+    /// Signatures that are present have to verify, missing ones are fine.
+    pub(crate) fn check_signatures(&self) -> Result<()> {
+        let results = self.transaction.verify_with_results();
+
+        for (index, signature) in self.transaction.signatures.iter().enumerate() {
+            if *signature != Signature::default() && !results[index] {
+                return Err(envelope_error(format!(
+                    "invalid signature from {}",
+                    self.required_signers()[index]
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn signer_index(&self, signer: &Pubkey) -> Result<usize> {
+        self.required_signers()
+            .iter()
+            .position(|required| required == signer)
+            .ok_or_else(|| SolanaUtilsError::InvalidInput(format!("{} is not a signer of this transaction", signer)))
+    }
+
+    fn signers_where(&self, predicate: impl Fn(&Signature) -> bool) -> Vec<Pubkey> {
+        self.required_signers()
+            .iter()
+            .zip(&self.transaction.signatures)
+            .filter(|(_, signature)| predicate(signature))
+            .map(|(signer, _)| *signer)
+            .collect()
+    }
+
+    fn wire_bytes(&self) -> Result<Vec<u8>> {
+        bincode::serialize(&self.transaction).map_err(|error| envelope_error(error.to_string()))
+    }
+}
+
+/// The nonce account and authority of a leading advance nonce instruction.
+fn nonce_source(transaction: &VersionedTransaction) -> Option<BlockhashSource> {
+    if !transaction.uses_durable_nonce() {
+        return None;
+    }
+
+    let instruction = transaction.message.instructions().first()?;
+    let account_keys = transaction.message.static_account_keys();
+
+    // AdvanceNonceAccount takes the nonce, the recent blockhashes sysvar and the authority.
+    Some(BlockhashSource::Nonce {
+        account: *account_keys.get(*instruction.accounts.first()? as usize)?,
+        authority: *account_keys.get(*instruction.accounts.get(2)? as usize)?,
+    })
+}
+
+/// Wire format bytes to a sanitized transaction, trailing bytes are an error.
+fn deserialize_wire(bytes: &[u8]) -> std::result::Result<VersionedTransaction, String> {
+    let transaction: VersionedTransaction = bincode::deserialize(bytes).map_err(|error| error.to_string())?;
+    if bincode::serialized_size(&transaction).ok() != Some(bytes.len() as u64) {
+        return Err("trailing bytes after the transaction".to_string());
+    }
+    transaction.sanitize().map_err(|error| error.to_string())?;
+
+    Ok(transaction)
+}
+
+fn envelope_error(message: impl Into<String>) -> SolanaUtilsError {
+    SolanaUtilsError::Envelope(message.into())
+}
+
+pub(crate) fn file_error(path: &Path, message: String) -> SolanaUtilsError {
+    SolanaUtilsError::File {
+        path: path.to_path_buf(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{instruction::Instruction, transaction::Transaction};
+
+    use super::*;
+
+    #[test]
+    fn reads_base58_that_is_also_valid_base64() {
+        let payer = Keypair::new();
+
+        // Grow a memo until the base58 encoding also decodes as base64.
+        let (transaction, encoded) = (0..256)
+            .find_map(|len| {
+                let memo = Instruction::new_with_bytes(spl_memo::id(), &vec![b'a'; len], Vec::new());
+                let message = Message::new(&[memo], Some(&payer.pubkey()));
+                let transaction = Transaction::new(&[&payer], message, Hash::new_unique());
+                let encoded = bs58::encode(bincode::serialize(&transaction).unwrap()).into_string();
+
+                STANDARD.decode(&encoded).is_ok().then_some((transaction, encoded))
+            })
+            .unwrap();
+        assert_eq!(encoded.len() % 4, 0);
+        assert!(deserialize_wire(&STANDARD.decode(&encoded).unwrap()).is_err());
+
+        let envelope = TxEnvelope::from_wire(&encoded, Cluster::Devnet).unwrap();
+        assert_eq!(envelope.transaction, VersionedTransaction::from(transaction));
+        assert_eq!(TxEnvelope::from_wire(&envelope.to_wire().unwrap(), Cluster::Devnet).unwrap(), envelope);
+    }
+}
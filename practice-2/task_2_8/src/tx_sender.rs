@@ -0,0 +1,385 @@
+use std::env;
+use std::time::Duration;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig};
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, Message, VersionedMessage},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    transaction::VersionedTransaction,
+};
+use tokio::time::sleep;
+
+use crate::error::{Result, SolanaUtilsError};
+use crate::explorer_link::LinkType;
+use crate::explorer_url;
+use crate::results::TransactionReceipt;
+
+pub const PRIORITY_FEE_ENV: &str = "SOLANA_PRIORITY_FEE";
+pub const SEND_ATTEMPTS_ENV: &str = "SOLANA_SEND_ATTEMPTS";
+
+/// Upper bound the runtime allows for a single transaction.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// `getRecentPrioritizationFees` only looks at up to 128 accounts.
+const MAX_PRIORITY_FEE_ACCOUNTS: usize = 128;
+
+/// Price paid per compute unit, in micro-lamports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriorityFee {
+    None,
+    Fixed(u64),
+    /// A percentile of the fees recently paid for the writable accounts of the transaction.
+    Recent { percentile: u8 },
+}
+
+impl PriorityFee {
+    /// Accepts `none`, `auto` (median of recent fees), `auto:<percentile>`
+    /// or a fixed number of micro-lamports per compute unit.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let invalid = || SolanaUtilsError::Config(format!("invalid priority fee `{}`", value));
+
+        match value {
+            "none" | "0" => Ok(PriorityFee::None),
+            "auto" => Ok(PriorityFee::Recent { percentile: 50 }),
+            _ => match value.strip_prefix("auto:") {
+                Some(percentile) => match percentile.parse::<u8>() {
+                    Ok(percentile) if percentile <= 100 => Ok(PriorityFee::Recent { percentile }),
+                    _ => Err(invalid()),
+                },
+                None => value.parse().map(PriorityFee::Fixed).map_err(|_| invalid()),
+            },
+        }
+    }
+}
+
+/// What a transaction went through before it landed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentTransaction {
+    pub receipt: TransactionReceipt,
+    /// Number of times the transaction was signed and sent, one plus the expired attempts.
+    pub attempts: usize,
+    pub units_consumed: u64,
+    pub compute_unit_limit: u32,
+    pub compute_unit_price: u64,
+}
+
+/// Instructions with the compute budget set from a simulation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetedInstructions {
+    pub instructions: Vec<Instruction>,
+    pub units_consumed: u64,
+    pub compute_unit_limit: u32,
+    pub compute_unit_price: u64,
+}
+
+/// Simulates, budgets, sends and confirms transactions.
+///
+/// The compute unit limit is taken from the simulation plus `compute_unit_margin`
+/// percent. When the blockhash expires before confirmation the transaction is
+/// signed again with a fresh one, up to `max_attempts` times.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxSender {
+    pub priority_fee: PriorityFee,
+    pub compute_unit_margin: u32,
+    pub max_attempts: usize,
+}
+
+impl Default for TxSender {
+    fn default() -> Self {
+        Self {
+            priority_fee: PriorityFee::Recent { percentile: 50 },
+            compute_unit_margin: 10,
+            max_attempts: 3,
+        }
+    }
+}
+
+impl TxSender {
+    pub fn with_priority_fee(mut self, priority_fee: PriorityFee) -> Self {
+        self.priority_fee = priority_fee;
+        self
+    }
+
+    pub fn with_compute_unit_margin(mut self, compute_unit_margin: u32) -> Self {
+        self.compute_unit_margin = compute_unit_margin;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Defaults overridden by `SOLANA_PRIORITY_FEE` and `SOLANA_SEND_ATTEMPTS`.
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+
+        let mut sender = Self::default();
+
+        if let Ok(priority_fee) = env::var(PRIORITY_FEE_ENV) {
+            sender.priority_fee = PriorityFee::parse(&priority_fee)?;
+        }
+
+        if let Ok(attempts) = env::var(SEND_ATTEMPTS_ENV) {
+            let attempts = attempts
+                .trim()
+                .parse()
+                .map_err(|_| SolanaUtilsError::Config(format!("invalid {} `{}`", SEND_ATTEMPTS_ENV, attempts)))?;
+            sender = sender.with_max_attempts(attempts);
+        }
+
+        Ok(sender)
+    }
+
+    /// Sends a legacy transaction.
+    pub async fn send(
+        &self,
+        connection: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&Keypair],
+    ) -> Result<SentTransaction> {
+        self.send_message(connection, instructions, payer, signers, None).await
+    }
+
+    /// Sends a v0 transaction, resolving accounts through `lookup_tables` so more
+    /// of them fit than the legacy format allows. Signers can't come from a table.
+    pub async fn send_v0(
+        &self,
+        connection: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&Keypair],
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> Result<SentTransaction> {
+        self.send_message(connection, instructions, payer, signers, Some(lookup_tables)).await
+    }
+
+    /// Runs the transaction with the maximum compute budget and returns the units it used.
+    /// `lookup_tables` selects a v0 transaction like in `send_v0`.
+    pub async fn simulate(
+        &self,
+        connection: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        lookup_tables: Option<&[AddressLookupTableAccount]>,
+    ) -> Result<u64> {
+        let instructions = with_compute_budget(instructions, MAX_COMPUTE_UNIT_LIMIT, 0);
+        let message = compile(&instructions, payer, lookup_tables, Hash::default())?;
+        let transaction = VersionedTransaction {
+            signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+            message,
+        };
+
+        let simulation = connection
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(connection.commitment()),
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .await?
+            .value;
+
+        if let Some(error) = simulation.err {
+            return Err(SolanaUtilsError::from_transaction_failure(
+                error,
+                simulation.logs.unwrap_or_default(),
+                &Message::new(&instructions, Some(payer)),
+            ));
+        }
+
+        Ok(simulation.units_consumed.unwrap_or(MAX_COMPUTE_UNIT_LIMIT as u64))
+    }
+
+    /// Simulates `instructions` and prepends the compute budget instructions for them.
+    pub async fn budget(
+        &self,
+        connection: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        lookup_tables: Option<&[AddressLookupTableAccount]>,
+    ) -> Result<BudgetedInstructions> {
+        let units_consumed = self.simulate(connection, instructions, payer, lookup_tables).await?;
+
+        let compute_unit_limit = units_consumed
+            .saturating_mul(100 + self.compute_unit_margin as u64)
+            .div_ceil(100)
+            .min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32;
+        let compute_unit_price = self.compute_unit_price(connection, instructions).await?;
+
+        Ok(BudgetedInstructions {
+            instructions: with_compute_budget(instructions, compute_unit_limit, compute_unit_price),
+            units_consumed,
+            compute_unit_limit,
+            compute_unit_price,
+        })
+    }
+
+    async fn send_message(
+        &self,
+        connection: &RpcClient,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &[&Keypair],
+        lookup_tables: Option<&[AddressLookupTableAccount]>,
+    ) -> Result<SentTransaction> {
+        let BudgetedInstructions {
+            instructions,
+            units_consumed,
+            compute_unit_limit,
+            compute_unit_price,
+        } = self.budget(connection, instructions, payer, lookup_tables).await?;
+
+        // Errors are classified against the legacy layout, which keeps the
+        // instruction order and resolves every account key.
+        let error_message = Message::new(&instructions, Some(payer));
+
+        for attempt in 1..=self.max_attempts {
+            let (blockhash, last_valid_block_height) = connection
+                .get_latest_blockhash_with_commitment(connection.commitment())
+                .await?;
+
+            let transaction = VersionedTransaction::try_new(
+                compile(&instructions, payer, lookup_tables, blockhash)?,
+                signers,
+            )?;
+
+            let signature = connection
+                .send_transaction_with_config(
+                    &transaction,
+                    RpcSendTransactionConfig {
+                        // Already simulated above.
+                        skip_preflight: true,
+                        ..RpcSendTransactionConfig::default()
+                    },
+                )
+                .await
+                .map_err(|error| SolanaUtilsError::from_transaction_error(error, &error_message))?;
+
+            if let Some(slot) =
+                wait_for_confirmation(connection, &signature, &error_message, last_valid_block_height).await?
+            {
+                return Ok(SentTransaction {
+                    receipt: TransactionReceipt {
+                        signature,
+                        slot,
+                        explorer_link: explorer_url(connection, LinkType::Transaction, signature),
+                    },
+                    attempts: attempt,
+                    units_consumed,
+                    compute_unit_limit,
+                    compute_unit_price,
+                });
+            }
+        }
+
+        Err(SolanaUtilsError::Expired { attempts: self.max_attempts })
+    }
+
+    pub async fn compute_unit_price(&self, connection: &RpcClient, instructions: &[Instruction]) -> Result<u64> {
+        let percentile = match self.priority_fee {
+            PriorityFee::None => return Ok(0),
+            PriorityFee::Fixed(price) => return Ok(price),
+            PriorityFee::Recent { percentile } => percentile,
+        };
+
+        let mut writable_accounts: Vec<Pubkey> = instructions
+            .iter()
+            .flat_map(|instruction| &instruction.accounts)
+            .filter(|account| account.is_writable)
+            .map(|account| account.pubkey)
+            .collect();
+        writable_accounts.sort();
+        writable_accounts.dedup();
+        writable_accounts.truncate(MAX_PRIORITY_FEE_ACCOUNTS);
+
+        let mut fees: Vec<u64> = connection
+            .get_recent_prioritization_fees(&writable_accounts)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+
+        if fees.is_empty() {
+            return Ok(0);
+        }
+
+        fees.sort_unstable();
+        let index = (fees.len() - 1) * percentile as usize / 100;
+
+        Ok(fees[index])
+    }
+}
+
+fn with_compute_budget(instructions: &[Instruction], compute_unit_limit: u32, compute_unit_price: u64) -> Vec<Instruction> {
+    let mut budgeted = vec![ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit)];
+
+    if compute_unit_price > 0 {
+        budgeted.push(ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price));
+    }
+
+    budgeted.extend_from_slice(instructions);
+    budgeted
+}
+
+fn compile(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    lookup_tables: Option<&[AddressLookupTableAccount]>,
+    blockhash: Hash,
+) -> Result<VersionedMessage> {
+    Ok(match lookup_tables {
+        None => VersionedMessage::Legacy(Message::new_with_blockhash(instructions, Some(payer), &blockhash)),
+        Some(lookup_tables) => VersionedMessage::V0(v0::Message::try_compile(
+            payer,
+            instructions,
+            lookup_tables,
+            blockhash,
+        )?),
+    })
+}
+
+/// Waits for `signature` to reach the connection's commitment. Returns its slot,
+/// or `None` once the blockhash has expired without the transaction landing.
+pub async fn wait_for_confirmation(
+    connection: &RpcClient,
+    signature: &Signature,
+    message: &Message,
+    last_valid_block_height: u64,
+) -> Result<Option<u64>> {
+    loop {
+        let status = connection
+            .get_signature_statuses(&[*signature])
+            .await?
+            .value
+            .into_iter()
+            .flatten()
+            .next();
+
+        if let Some(status) = status {
+            if let Some(error) = status.err {
+                return Err(SolanaUtilsError::from_transaction_failure(error, Vec::new(), message));
+            }
+
+            if status.satisfies_commitment(connection.commitment()) {
+                return Ok(Some(status.slot));
+            }
+        } else if connection.get_block_height().await? > last_valid_block_height {
+            return Ok(None);
+        }
+
+        sleep(CONFIRMATION_POLL_INTERVAL).await;
+    }
+}